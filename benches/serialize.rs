@@ -0,0 +1,138 @@
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use serde::Serialize;
+use serini::{ser::Serializer, to_string};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    collections::BTreeMap,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// Counts allocations, to compare a reused serializer with a new one per value
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// Allocations made by one call, after a first call to warm up any buffers
+fn allocations(mut f: impl FnMut()) -> usize {
+    f();
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+#[derive(Serialize)]
+struct Config {
+    name: String,
+    debug: bool,
+    tags: Vec<String>,
+    server: Server,
+    database: Database,
+}
+
+#[derive(Serialize)]
+struct Server {
+    host: String,
+    port: u16,
+    timeout: f64,
+    tls: Tls,
+}
+
+#[derive(Serialize)]
+struct Tls {
+    cert: String,
+    key: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Database {
+    url: String,
+    max_connections: u32,
+}
+
+fn config() -> Config {
+    Config {
+        name: "my-app".to_string(),
+        debug: false,
+        tags: vec!["web".to_string(), "frontend".to_string()],
+        server: Server {
+            host: "0.0.0.0".to_string(),
+            port: 8080,
+            timeout: 30.5,
+            tls: Tls {
+                cert: "/etc/ssl/cert.pem".to_string(),
+                key: None,
+            },
+        },
+        database: Database {
+            url: "postgres://localhost/mydb".to_string(),
+            max_connections: 100,
+        },
+    }
+}
+
+fn reuse<T: Serialize>(serializer: &mut Serializer<Vec<u8>>, value: &T) {
+    serializer.reset();
+    serializer.get_mut().clear();
+    black_box(value).serialize(serializer).unwrap();
+}
+
+fn serialize(c: &mut Criterion) {
+    let config = config();
+
+    c.bench_function("to_string", |b| {
+        b.iter(|| to_string(black_box(&config)).unwrap())
+    });
+
+    // Many sections nested two levels deep
+    let servers: BTreeMap<String, Server> = (0..100)
+        .map(|i| {
+            let server = Server {
+                host: format!("10.0.0.{i}"),
+                port: 8000 + i,
+                timeout: 30.5,
+                tls: Tls {
+                    cert: "/etc/ssl/cert.pem".to_string(),
+                    key: Some("/etc/ssl/key.pem".to_string()),
+                },
+            };
+            (format!("server{i}"), server)
+        })
+        .collect();
+    c.bench_function("to_string many sections", |b| {
+        b.iter(|| to_string(black_box(&servers)).unwrap())
+    });
+
+    // A serializer reused across values keeps its buffers, those of its sections and the
+    // output's capacity
+    let mut serializer = Serializer::new(Vec::new());
+    println!(
+        "allocations per value with many sections: {} reusing a serializer, {} with to_string",
+        allocations(|| reuse(&mut serializer, &servers)),
+        allocations(|| drop(to_string(black_box(&servers)).unwrap())),
+    );
+    c.bench_function("reused serializer", |b| {
+        b.iter(|| reuse(&mut serializer, &config))
+    });
+}
+
+criterion_group!(benches, serialize);
+criterion_main!(benches);
@@ -1,518 +1,2276 @@
 use crate::{Error, error::Result};
 use serde::{
     Deserialize,
-    de::{self, IntoDeserializer},
+    de::{self, DeserializeOwned, IntoDeserializer, value::MapAccessDeserializer},
 };
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash};
+use std::ops::Range;
 use std::str::FromStr;
+use std::{fmt, io, str};
+
+pub struct Deserializer<'de> {
+    // Sections in file order, starting with the headerless root section
+    sections: Vec<(String, Section<'de>)>,
+    // Names of the subsections directly within each section in file order, by the path of the
+    // section, including sections only implied by a subsection like `[a]` by `[a.b]`
+    subsections: HashMap<String, Vec<String>>,
+    options: DeserializeOptions,
+    // Number of the line being parsed, starting at 1
+    line: usize,
+    // Byte offset of the line being parsed within the input
+    offset: usize,
+    // Styles of the first indented and the first key/value line, which later lines must match
+    indentation: Option<char>,
+    separator: Option<(bool, bool)>,
+    warnings: Vec<Warning>,
+    // Start of a line ending in a backslash, with its line number, waiting for the next line
+    continuation: Option<(String, usize)>,
+    // Number of sections the one being read is nested in
+    depth: usize,
+}
+
+// Keys of a section in file order, each with every value it was assigned
+#[derive(Default)]
+struct Section<'de> {
+    entries: Vec<(String, Vec<RawValue<'de>>)>,
+    // Lines of the headers starting the section, more than one if it is repeated
+    headers: Vec<usize>,
+}
+
+// A value as written in the file, with the line it appeared on for error reporting. It borrows
+// from the input unless it was read from a reader or spans several lines.
+#[derive(Clone)]
+struct RawValue<'de> {
+    text: Cow<'de, str>,
+    line: usize,
+    // Byte range within the input, unless the value was continued across lines
+    span: Option<Range<usize>>,
+    // A key written without a delimiter, read as `true` by booleans
+    bare: bool,
+}
+
+impl RawValue<'_> {
+    // Attach the line and byte range of the value to an error it caused
+    fn locate(&self, error: Error) -> Error {
+        error.at_span(self.line, self.span.clone())
+    }
+}
+
+impl<'de> Section<'de> {
+    fn insert(&mut self, key: String, value: RawValue<'de>) {
+        match self.entries.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, values)) => values.push(value),
+            None => self.entries.push((key, vec![value])),
+        }
+    }
+}
+
+/// Options controlling how INI input is parsed.
+#[derive(Debug, Clone)]
+pub struct DeserializeOptions {
+    /// Character separating a key from its value, `=` by default.
+    ///
+    /// Lines are split at its first occurrence, so the value may contain it as well.
+    pub delimiter: char,
+    /// How a value is split into elements when deserializing a sequence.
+    pub seq_delimiter: SeqDelimiter,
+    /// How a key assigned more than once is handled when deserializing a single value.
+    pub duplicate_keys: DuplicateKeys,
+    /// Whether a `;` or `#` after a value starts a comment.
+    pub inline_comments: InlineComments,
+    /// Reject keys and sections that don't match a field with [`Error::UnknownField`], instead of
+    /// ignoring them.
+    pub deny_unknown_fields: bool,
+    /// Match section and key names regardless of case, e.g. `[Database]` with `Port = 80` fills
+    /// a `database` field with a `port` field.
+    ///
+    /// Names are compared with the field names after serde's `rename`, so a field renamed to
+    /// `Port` matches `port` as well. Keys of maps are read lowercased.
+    pub case_insensitive: bool,
+    /// Name of a section whose keys are read as root fields, e.g. `DEFAULT`.
+    pub root_section: Option<String>,
+    /// Replace escape sequences in values, `true` by default. Otherwise values are read verbatim.
+    ///
+    /// Must match [`SerializeOptions::escape`](crate::SerializeOptions::escape) of the writer.
+    pub unescape: bool,
+    /// Escape sequences replaced by [`unescape`](Self::unescape), backslash escapes by default.
+    ///
+    /// Must match [`SerializeOptions::escape_mode`](crate::SerializeOptions::escape_mode) of the
+    /// writer.
+    pub escape_mode: EscapeMode,
+    /// How a section header appearing more than once is handled.
+    pub duplicate_sections: DuplicateSections,
+    /// Read values as booleans or numbers where their type isn't known in advance, such as the
+    /// fields of a `#[serde(flatten)]` struct or untyped fields like [`Value`](crate::Value) and
+    /// `serde_json::Value`, instead of as strings. `true` and `false` are read as booleans,
+    /// integers and floats as numbers. Quoted values are always read as strings. On by default.
+    ///
+    /// Needed for flattened fields that aren't strings. A flattened map of strings holding
+    /// numbers has to be read without it.
+    pub infer_types: bool,
+    /// Key under which a top-level map holds the root keys, making every entry of the map a
+    /// section, e.g. `HashMap<String, HashMap<String, String>>` for a whole file.
+    ///
+    /// Otherwise a top-level map is read like a root struct, with the root keys as entries
+    /// alongside the sections.
+    pub root_map_key: Option<String>,
+    /// Read a line holding only a key, e.g. `verbose`, as a key with an empty value, which a
+    /// `bool` field reads as `true`. Otherwise such lines are ignored.
+    pub bare_keys: bool,
+    /// Keys that may be written bare, as paths like `verbose` for a root key or `server.tls`
+    /// for a key of the section `[server]`. Any other bare key fails with
+    /// [`Error::MissingValue`], so fields can require an explicit value while flags don't.
+    ///
+    /// Setting this reads bare keys as if [`bare_keys`](Self::bare_keys) was set. Otherwise
+    /// every key may be written bare.
+    pub flag_fields: Option<Vec<String>>,
+    /// Key naming the variant of an enum read from a section, `type` by default. The other keys
+    /// of the section fill the fields of the variant.
+    ///
+    /// Enums with `#[serde(tag = "...")]` name their own tag key instead.
+    pub enum_tag: String,
+    /// Read a key with an empty value, e.g. `password = `, as `None` when deserializing an
+    /// `Option`, instead of `Some("")` for an `Option<String>` or an error for other types. A
+    /// quoted empty value `""` is still `Some("")`.
+    ///
+    /// [`OptionStyle::EmptyValue`](crate::OptionStyle::EmptyValue) writes `None` and `Some("")`
+    /// that way, so the two round-trip when used together.
+    pub empty_as_none: bool,
+    /// Expand `${NAME}` references within values from these variables, after replacing escape
+    /// sequences. Values are read as written by default.
+    #[cfg(feature = "interpolate")]
+    pub variables: Option<crate::Variables>,
+    /// Number of levels sections may be nested in, 128 by default. Deeper sections fail with
+    /// [`Error::UnsupportedFeature`] rather than running out of stack, as each level is read by
+    /// recursion.
+    pub max_depth: usize,
+}
+
+impl Default for DeserializeOptions {
+    fn default() -> Self {
+        DeserializeOptions {
+            delimiter: '=',
+            seq_delimiter: SeqDelimiter::default(),
+            duplicate_keys: DuplicateKeys::default(),
+            inline_comments: InlineComments::default(),
+            deny_unknown_fields: false,
+            case_insensitive: false,
+            root_section: None,
+            unescape: true,
+            escape_mode: EscapeMode::default(),
+            duplicate_sections: DuplicateSections::default(),
+            infer_types: true,
+            root_map_key: None,
+            bare_keys: false,
+            flag_fields: None,
+            enum_tag: "type".to_string(),
+            empty_as_none: false,
+            #[cfg(feature = "interpolate")]
+            variables: None,
+            max_depth: 128,
+        }
+    }
+}
+
+/// Handling of a section header that appears more than once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateSections {
+    /// Add the keys following each header to the same section. Keys repeated across them are
+    /// handled according to [`DeserializeOptions::duplicate_keys`].
+    ///
+    /// A sequence of structs read from the section still gets one element for each header.
+    #[default]
+    Merge,
+    /// Fail with [`Error::DuplicateSection`].
+    Error,
+}
+
+/// Handling of comment characters within a value.
+///
+/// Escaped `\;` and `\#` never start a comment, and neither do those within double quotes, so
+/// `note = "a ; b"` is read as `a ; b`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InlineComments {
+    /// Comments only span whole lines, `;` and `#` after a delimiter are part of the value.
+    Never,
+    /// Any unescaped `;` or `#` ends the value and starts a comment.
+    Anywhere,
+    /// Only an unescaped `;` or `#` following whitespace starts a comment, so
+    /// `url = http://x#y` is kept whole while `port = 8080 # http` isn't. The default.
+    #[default]
+    AfterWhitespace,
+}
+
+/// Escape sequences standing for special characters within values and keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EscapeMode {
+    /// A backslash followed by a character, e.g. `\n` for a line break and `\;` for `;`.
+    #[default]
+    Backslash,
+    /// Percent-encoded UTF-8 bytes as in URLs, e.g. `%0A` for a line break and `%3B` for `;`.
+    /// Only `%`, `\`, `"`, control characters and comment characters are encoded.
+    ///
+    /// Sequences that don't encode valid UTF-8 are read as the replacement character `\u{FFFD}`.
+    Percent,
+}
 
-pub struct Deserializer {
-    sections: HashMap<String, HashMap<String, String>>,
+impl EscapeMode {
+    // Character starting each escape sequence
+    pub(crate) fn escape_char(self) -> char {
+        match self {
+            EscapeMode::Backslash => '\\',
+            EscapeMode::Percent => '%',
+        }
+    }
+}
+
+/// Separator between the elements of a sequence value.
+///
+/// Elements wrapped in double quotes are kept intact, even if they contain the delimiter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeqDelimiter {
+    /// Split on a single character, trimming whitespace around each element (`a, b, c`).
+    Char(char),
+    /// Split on any run of whitespace (`a b c`).
+    Whitespace,
+}
+
+impl Default for SeqDelimiter {
+    fn default() -> Self {
+        SeqDelimiter::Char(',')
+    }
+}
+
+/// Handling of a key that appears more than once within a section.
+///
+/// Sequences always collect every occurrence, this only applies to single values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// Use the last value assigned to the key.
+    #[default]
+    Last,
+    /// Reject the input with [`Error::DuplicateKey`].
+    Error,
+}
+
+/// A style inconsistency or ignored key found in the input, reported by
+/// [`from_str_with_warnings`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// The line is indented with tabs where earlier lines use spaces, or the other way around.
+    InconsistentIndentation { line: usize },
+    /// Whitespace around the delimiter differs from the first key/value pair, e.g. `key=value`
+    /// after `key = value`.
+    InconsistentSeparator { line: usize },
+    /// A key or section matched no field of the target struct and was ignored, likely a typo.
+    /// `section` is the path of the section holding it, empty for the root.
+    ///
+    /// Only reported where [`DeserializeOptions::deny_unknown_fields`] would reject it, so keys
+    /// read by maps and `#[serde(flatten)]` fields aren't unknown.
+    UnknownField {
+        key: String,
+        section: String,
+        line: usize,
+    },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::InconsistentIndentation { line } => {
+                write!(
+                    f,
+                    "line {line}: inconsistent indentation, mixing tabs and spaces"
+                )
+            }
+            Warning::InconsistentSeparator { line } => {
+                write!(
+                    f,
+                    "line {line}: inconsistent whitespace around the delimiter"
+                )
+            }
+            Warning::UnknownField { key, section, line } => {
+                write!(
+                    f,
+                    "line {line}: unknown field: {key}{}",
+                    crate::error::in_section(section)
+                )
+            }
+        }
+    }
 }
 
 pub fn from_str<'a, T>(s: &'a str) -> Result<T>
 where
     T: Deserialize<'a>,
 {
-    let mut deserializer = Deserializer::from_str(s)?;
+    from_str_with(s, &DeserializeOptions::default())
+}
+
+pub fn from_str_with<'a, T>(s: &'a str, options: &DeserializeOptions) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::from_str(s, options)?;
+    let t = T::deserialize(&mut deserializer)?;
+    Ok(t)
+}
+
+/// Deserializes a value from several INI strings layered on top of each other, such as a base
+/// config followed by a machine-specific override.
+///
+/// Later sources take precedence: a key they assign replaces all values of that key from
+/// earlier sources within the same section, sequences included. Keys and sections they don't
+/// mention are kept, and new ones are added. Line numbers in errors refer to the source the
+/// value came from.
+pub fn from_strs<'a, T>(sources: &[&'a str]) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    from_strs_with(sources, &DeserializeOptions::default())
+}
+
+/// Like [`from_strs`], parsing every source with the given options.
+pub fn from_strs_with<'a, T>(sources: &[&'a str], options: &DeserializeOptions) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::new(options);
+    for source in sources {
+        deserializer.merge(Deserializer::from_str(source, options)?);
+    }
+    T::deserialize(&mut deserializer)
+}
+
+/// Deserializes an INI string over an existing value, assigning the keys and sections the string
+/// contains and keeping all other fields, such as the defaults of a config before applying a
+/// user's file.
+///
+/// Which field a key assigns is up to the target's [`IniOverlay`] implementation. The string is
+/// parsed before anything is assigned, so the target is left as it was if it isn't valid INI.
+/// Values are assigned in the order they are read though, so if one of them fails to
+/// deserialize, the fields read before it keep their new values. Overlay a clone to apply a
+/// file all or nothing.
+///
+/// ```rust
+/// use serde::de::{IgnoredAny, MapAccess};
+/// use serini::{IniOverlay, OverlaySeed};
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Config {
+///     name: String,
+///     server: Server,
+/// }
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Server {
+///     host: String,
+///     port: u16,
+/// }
+///
+/// impl<'de> IniOverlay<'de> for Config {
+///     fn overlay<A: MapAccess<'de>>(&mut self, mut map: A) -> Result<(), A::Error> {
+///         while let Some(key) = map.next_key::<String>()? {
+///             match key.as_str() {
+///                 "name" => self.name = map.next_value()?,
+///                 "server" => map.next_value_seed(OverlaySeed(&mut self.server))?,
+///                 _ => map.next_value::<IgnoredAny>().map(drop)?,
+///             }
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// impl<'de> IniOverlay<'de> for Server {
+///     fn overlay<A: MapAccess<'de>>(&mut self, mut map: A) -> Result<(), A::Error> {
+///         while let Some(key) = map.next_key::<String>()? {
+///             match key.as_str() {
+///                 "host" => self.host = map.next_value()?,
+///                 "port" => self.port = map.next_value()?,
+///                 _ => map.next_value::<IgnoredAny>().map(drop)?,
+///             }
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let mut config = Config {
+///     name: "app".to_string(),
+///     server: Server { host: "0.0.0.0".to_string(), port: 80 },
+/// };
+/// serini::from_str_into("[server]\nport = 8080\n", &mut config).unwrap();
+/// assert_eq!(config.name, "app");
+/// assert_eq!(config.server.host, "0.0.0.0");
+/// assert_eq!(config.server.port, 8080);
+/// ```
+pub fn from_str_into<'a, T>(s: &'a str, target: &mut T) -> Result<()>
+where
+    T: IniOverlay<'a>,
+{
+    from_str_into_with(s, target, &DeserializeOptions::default())
+}
+
+/// Like [`from_str_into`], parsing the string with the given options.
+pub fn from_str_into_with<'a, T>(
+    s: &'a str,
+    target: &mut T,
+    options: &DeserializeOptions,
+) -> Result<()>
+where
+    T: IniOverlay<'a>,
+{
+    let mut deserializer = Deserializer::from_str(s, options)?;
+    // The root keys and sections, like a root struct
+    de::Visitor::visit_map(
+        OverlaySeed(target),
+        StructAccess::new(&mut deserializer, "", &[]),
+    )
+}
+
+/// A value that the keys of an INI string can be assigned to one by one, keeping the fields the
+/// string doesn't mention, see [`from_str_into`].
+///
+/// `overlay` is given the keys of the root or a section together with the sections nested in
+/// it, each named as in the file and only if present. Values are read with
+/// [`MapAccess::next_value`](de::MapAccess::next_value), replacing the field as a whole, or
+/// overlaid on a field that implements `IniOverlay` itself with [`OverlaySeed`]. Keys are only
+/// available as owned strings, and are lowercased when matching regardless of case.
+///
+/// Maps are overlaid entry by entry, replacing the values of the keys present.
+pub trait IniOverlay<'de> {
+    /// Assigns the entries of `map` to the fields they name.
+    fn overlay<A>(&mut self, map: A) -> std::result::Result<(), A::Error>
+    where
+        A: de::MapAccess<'de>;
+}
+
+/// Deserializes a section over an existing value with [`IniOverlay::overlay`], rather than
+/// creating a new one.
+pub struct OverlaySeed<'t, T>(pub &'t mut T);
+
+impl<'de, T> de::DeserializeSeed<'de> for OverlaySeed<'_, T>
+where
+    T: IniOverlay<'de>,
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> std::result::Result<(), D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, T> de::Visitor<'de> for OverlaySeed<'_, T>
+where
+    T: IniOverlay<'de>,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a section")
+    }
+
+    fn visit_map<A>(self, map: A) -> std::result::Result<(), A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        self.0.overlay(map)
+    }
+}
+
+// Present keys replace their values, others are kept
+macro_rules! overlay_map {
+    ($map:ty, $($bound:tt)*) => {
+        impl<'de, $($bound)*> IniOverlay<'de> for $map {
+            fn overlay<A>(&mut self, mut map: A) -> std::result::Result<(), A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                while let Some((key, value)) = map.next_entry()? {
+                    self.insert(key, value);
+                }
+                Ok(())
+            }
+        }
+    };
+}
+
+overlay_map!(BTreeMap<K, V>, K: Deserialize<'de> + Ord, V: Deserialize<'de>);
+overlay_map!(
+    HashMap<K, V, S>,
+    K: Deserialize<'de> + Eq + Hash, V: Deserialize<'de>, S: BuildHasher
+);
+#[cfg(feature = "indexmap")]
+overlay_map!(
+    indexmap::IndexMap<K, V, S>,
+    K: Deserialize<'de> + Eq + Hash, V: Deserialize<'de>, S: BuildHasher
+);
+
+/// Like [`from_str_with`], also returning the style inconsistencies found in the input and the
+/// keys and sections that were ignored.
+pub fn from_str_with_warnings<'a, T>(
+    s: &'a str,
+    options: &DeserializeOptions,
+) -> Result<(T, Vec<Warning>)>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::from_str(s, options)?;
+    let t = T::deserialize(&mut deserializer)?;
+    Ok((t, deserializer.warnings))
+}
+
+/// Deserializes a value from UTF-8 encoded bytes.
+pub fn from_slice<'a, T>(bytes: &'a [u8]) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    from_str(str::from_utf8(bytes)?)
+}
+
+/// Sections by name with their keys and values, as returned by [`parse_raw`].
+pub type RawSections = Vec<(String, Vec<(String, String)>)>;
+
+/// Parses an INI string without deserializing it into a type, returning each section with its
+/// keys and unescaped values in file order.
+///
+/// Root keys are listed under the section `""`, which always comes first. A repeated key is
+/// listed once per occurrence, following its first one.
+pub fn parse_raw(s: &str) -> Result<RawSections> {
+    parse_raw_with(s, &DeserializeOptions::default())
+}
+
+/// Like [`parse_raw`], parsing with the given options.
+pub fn parse_raw_with(s: &str, options: &DeserializeOptions) -> Result<RawSections> {
+    let deserializer = Deserializer::from_str(s, options)?;
+    deserializer
+        .sections
+        .iter()
+        .map(|(name, section)| {
+            let entries = section
+                .entries
+                .iter()
+                .flat_map(|(key, values)| {
+                    values.iter().map(move |value| {
+                        let text = ValueDeserializer::new(&value.text, options)
+                            .map_err(|e| value.locate(e))?
+                            .value
+                            .into_owned();
+                        Ok((key.clone(), text))
+                    })
+                })
+                .collect::<Result<_>>()?;
+            Ok((name.clone(), entries))
+        })
+        .collect()
+}
+
+pub fn from_reader<R, T>(reader: R) -> Result<T>
+where
+    R: io::Read,
+    T: DeserializeOwned,
+{
+    from_reader_with(reader, &DeserializeOptions::default())
+}
+
+pub fn from_reader_with<R, T>(reader: R, options: &DeserializeOptions) -> Result<T>
+where
+    R: io::Read,
+    T: DeserializeOwned,
+{
+    let mut deserializer = Deserializer::from_reader(io::BufReader::new(reader), options)?;
     let t = T::deserialize(&mut deserializer)?;
     Ok(t)
 }
 
-impl Deserializer {
-    fn from_str(input: &str) -> Result<Self> {
-        let mut sections = HashMap::new();
+// Byte order mark at the start of UTF-8 input
+pub(crate) const BOM: char = '\u{FEFF}';
+
+// The line without the `\n` or `\r\n` ending it, as `str::lines` yields it
+fn strip_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+impl<'de> Deserializer<'de> {
+    fn new(options: &DeserializeOptions) -> Self {
+        Deserializer {
+            sections: vec![(String::new(), Section::default())],
+            subsections: HashMap::from([(String::new(), Vec::new())]),
+            options: options.clone(),
+            line: 0,
+            offset: 0,
+            indentation: None,
+            separator: None,
+            warnings: Vec::new(),
+            continuation: None,
+            depth: 0,
+        }
+    }
+
+    fn from_str(input: &'de str, options: &DeserializeOptions) -> Result<Self> {
+        let mut deserializer = Deserializer::new(options);
         let mut current_section = String::new();
-        sections.insert(current_section.clone(), HashMap::new());
 
+        // Editors on Windows often start the file with a byte order mark
+        let start = input.as_ptr() as usize;
+        let input = input.strip_prefix(BOM).unwrap_or(input);
+        // Splits after `\n` and `\r\n` alike, so either ending may be mixed with the other
         for line in input.lines() {
-            let line = line.trim();
+            deserializer.offset = line.as_ptr() as usize - start;
+            deserializer.parse_line(Cow::Borrowed(line), &mut current_section)?;
+        }
+        deserializer.finish(&mut current_section)?;
+
+        Ok(deserializer)
+    }
+
+    fn from_reader<R: io::BufRead>(mut reader: R, options: &DeserializeOptions) -> Result<Self> {
+        let mut deserializer = Deserializer::new(options);
+        let mut current_section = String::new();
+        let mut line = String::new();
+        let mut offset = 0;
+
+        // Reuse a single line buffer so only the parsed data is held in memory
+        while let read @ 1.. = reader.read_line(&mut line)? {
+            let text = match deserializer.line {
+                0 => line.strip_prefix(BOM).unwrap_or(&line),
+                _ => &line,
+            };
+            let text = strip_line_ending(text);
+            deserializer.offset = offset + (text.as_ptr() as usize - line.as_ptr() as usize);
+            deserializer.parse_line(Cow::Owned(text.to_string()), &mut current_section)?;
+            offset += read;
+            line.clear();
+        }
+        deserializer.finish(&mut current_section)?;
+
+        Ok(deserializer)
+    }
+
+    // Overlay the sections of another source, its keys replacing those already present
+    fn merge(&mut self, other: Deserializer<'de>) {
+        for (name, section) in other.sections {
+            let Some(existing) = self.section_mut(&name) else {
+                self.add_section(name, section);
+                continue;
+            };
+            // The elements of repeated sections can't be matched up, so they replace each other
+            if existing.headers.len() > 1 || section.headers.len() > 1 {
+                *existing = section;
+                continue;
+            }
+            for (key, values) in section.entries {
+                match existing.entries.iter_mut().find(|(k, _)| *k == key) {
+                    Some((_, existing_values)) => *existing_values = values,
+                    None => existing.entries.push((key, values)),
+                }
+            }
+        }
+    }
+
+    fn parse_line(&mut self, line: Cow<'de, str>, current_section: &mut String) -> Result<()> {
+        self.line += 1;
+
+        // Join a line continued with a trailing backslash with the one following it
+        // Joined lines don't appear in the input as such, so their values have no span
+        let (line, line_number, offset) = match self.continuation.take() {
+            Some((mut joined, start)) => {
+                joined.push_str(line.trim());
+                (Cow::Owned(joined), start, None)
+            }
+            None => {
+                self.check_indentation(&line);
+                (line, self.line, Some(self.offset))
+            }
+        };
+
+        match Self::strip_continuation(&line, self.options.inline_comments) {
+            Some(start) => {
+                self.continuation = Some((start.to_string(), line_number));
+                Ok(())
+            }
+            None => self.parse_logical_line(line, line_number, offset, current_section),
+        }
+    }
+
+    // Parse a line left over from a continuation at the end of the input
+    fn finish(&mut self, current_section: &mut String) -> Result<()> {
+        match self.continuation.take() {
+            Some((line, line_number)) => {
+                self.parse_logical_line(Cow::Owned(line), line_number, None, current_section)
+            }
+            None => Ok(()),
+        }
+    }
+
+    // The line without its trailing backslash, if it is continued on the next line
+    pub(crate) fn strip_continuation(line: &str, rule: InlineComments) -> Option<&str> {
+        let line = line.trim_end();
+        if line.trim_start().starts_with([';', '#']) {
+            return None;
+        }
+        // A backslash ending a comment is part of the comment
+        if Self::strip_inline_comment(line, rule).len() < line.len() {
+            return None;
+        }
+
+        // An escaped backslash `\\` at the end is part of the value
+        let backslashes = line.chars().rev().take_while(|&c| c == '\\').count();
+        (backslashes % 2 == 1).then(|| &line[..line.len() - 1])
+    }
+
+    fn parse_logical_line(
+        &mut self,
+        input: Cow<'de, str>,
+        line_number: usize,
+        offset: Option<usize>,
+        current_section: &mut String,
+    ) -> Result<()> {
+        let line = input.trim();
+        // Byte range of a part of the line within the input
+        let span = |part: &str| {
+            offset.map(|offset| {
+                let start = offset + (part.as_ptr() as usize - input.as_ptr() as usize);
+                start..start + part.len()
+            })
+        };
+
+        // Skip empty lines and comments
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            return Ok(());
+        }
+
+        // Section header
+        if let Some(name) = Self::section_header(line, &self.options) {
+            *current_section = self.name(name);
+            let root = self
+                .options
+                .root_section
+                .as_deref()
+                .map(|name| self.name(name));
+            if root.as_ref() == Some(current_section) {
+                // Keys of the root section are kept, they may have come before the header
+                current_section.clear();
+                return Ok(());
+            }
+            if self.section(current_section).is_none() {
+                self.add_section(current_section.clone(), Section::default());
+            } else if self.options.duplicate_sections == DuplicateSections::Error {
+                return Err(Error::DuplicateSection {
+                    name: current_section.clone(),
+                }
+                .at_span(line_number, span(line)));
+            }
+            if let Some(section) = self.section_mut(current_section) {
+                section.headers.push(line_number);
+            }
+            // A repeated header continues the section, adding to its keys
+            return Ok(());
+        }
+
+        // Key-value pair, split at the first delimiter as the value may contain more of them
+        if let Some((key, value)) = Self::split_key(line, &self.options) {
+            self.check_separator(key, value);
+            let key = self.name(&Self::unescape_key(key.trim(), &self.options));
+            let value = Self::strip_inline_comment(value, self.options.inline_comments).trim();
+            // Values are kept raw and unescaped on access, so sequences can be split first
+            let value = RawValue {
+                text: Self::slice(&input, value),
+                line: line_number,
+                span: span(value),
+                bare: false,
+            };
+
+            if let Some(section) = self.section_mut(current_section) {
+                section.insert(key, value);
+            }
+        } else if self.options.bare_keys || self.options.flag_fields.is_some() {
+            let key = Self::strip_inline_comment(line, self.options.inline_comments).trim_end();
+            // Errors about a bare key point at the key, as it has no value
+            let value = RawValue {
+                text: Cow::Borrowed(""),
+                line: line_number,
+                span: span(key),
+                bare: true,
+            };
+            let key = self.name(&Self::unescape_key(key, &self.options));
+
+            if let Some(section) = self.section_mut(current_section) {
+                section.insert(key, value);
+            }
+        }
+
+        Ok(())
+    }
+
+    // A part of a line, borrowing from the input if the line does
+    fn slice(line: &Cow<'de, str>, part: &str) -> Cow<'de, str> {
+        match line {
+            Cow::Borrowed(line) => {
+                // The part was taken from the line, so it starts within it
+                let start = part.as_ptr() as usize - line.as_ptr() as usize;
+                Cow::Borrowed(&line[start..start + part.len()])
+            }
+            Cow::Owned(_) => Cow::Owned(part.to_string()),
+        }
+    }
+
+    // Section or key name as it is stored, lowercased when matching regardless of case
+    fn name(&self, name: &str) -> String {
+        if self.options.case_insensitive {
+            name.to_lowercase()
+        } else {
+            name.to_string()
+        }
+    }
+
+    fn check_indentation(&mut self, line: &str) {
+        // Blank lines carry no indentation style
+        if line.trim().is_empty() {
+            return;
+        }
+
+        let indent: Vec<char> = line
+            .chars()
+            .take_while(|&c| c == ' ' || c == '\t')
+            .collect();
+        let Some(&first) = indent.first() else {
+            return;
+        };
+
+        let mixed = indent.iter().any(|&c| c != first);
+        let expected = *self.indentation.get_or_insert(first);
+        if mixed || first != expected {
+            self.warnings
+                .push(Warning::InconsistentIndentation { line: self.line });
+        }
+    }
+
+    fn check_separator(&mut self, key: &str, value: &str) {
+        let style = (
+            key.ends_with(char::is_whitespace),
+            value.starts_with(char::is_whitespace),
+        );
+
+        match self.separator {
+            // An empty value has no whitespace after `=` to compare
+            None if value.is_empty() => {}
+            None => self.separator = Some(style),
+            Some((before, after)) => {
+                if before != style.0 || (!value.is_empty() && after != style.1) {
+                    self.warnings
+                        .push(Warning::InconsistentSeparator { line: self.line });
+                }
+            }
+        }
+    }
+
+    fn section(&self, name: &str) -> Option<&Section<'de>> {
+        self.sections
+            .iter()
+            .find(|(section, _)| section == name)
+            .map(|(_, section)| section)
+    }
+
+    fn section_mut(&mut self, name: &str) -> Option<&mut Section<'de>> {
+        self.sections
+            .iter_mut()
+            .find(|(section, _)| section == name)
+            .map(|(_, section)| section)
+    }
+
+    fn add_section(&mut self, name: String, section: Section<'de>) {
+        self.link_section(&name);
+        self.sections.push((name, section));
+    }
+
+    // List a section within its parent, and the parent within its own if it is new as well.
+    // Each path is linked once, so deeply nested sections take time linear in their number.
+    fn link_section(&mut self, mut name: &str) {
+        if self.subsections.contains_key(name) {
+            return;
+        }
+        self.subsections.insert(name.to_string(), Vec::new());
+        loop {
+            let (parent, child) = match name.rsplit_once('.') {
+                // The root section only holds sections whose first segment isn't empty
+                Some(("", _)) => return,
+                Some(split) => split,
+                None => ("", name),
+            };
+            let is_new = !self.subsections.contains_key(parent);
+            let children = self.subsections.entry(parent.to_string()).or_default();
+            if !child.is_empty() {
+                children.push(child.to_string());
+            }
+            if !is_new {
+                return;
+            }
+            name = parent;
+        }
+    }
+
+    // Whether the section exists, either with its own header or implied by a subsection
+    fn has_section(&self, name: &str) -> bool {
+        self.subsections.contains_key(name)
+    }
+
+    // Whether a field is assigned within a section, as a key or a subsection
+    fn has_field(&self, section: &str, field: &str) -> bool {
+        let field = self.name(field);
+        let path = if section.is_empty() {
+            field.clone()
+        } else {
+            format!("{section}.{field}")
+        };
+        self.has_section(&path)
+            || self
+                .section(section)
+                .is_some_and(|section| section.entries.iter().any(|(key, _)| *key == field))
+    }
+
+    // Split a line into key and value at the first delimiter that isn't escaped as `\=`, which
+    // belongs to the key instead
+    pub(crate) fn split_key<'l>(
+        line: &'l str,
+        options: &DeserializeOptions,
+    ) -> Option<(&'l str, &'l str)> {
+        if !options.unescape || options.escape_mode == EscapeMode::Percent {
+            return line.split_once(options.delimiter);
+        }
+        let mut escaped = false;
+        for (i, c) in line.char_indices() {
+            if c == options.delimiter && !escaped {
+                return Some((&line[..i], &line[i + c.len_utf8()..]));
+            }
+            escaped = c == '\\' && !escaped;
+        }
+        None
+    }
+
+    // Undo `SerializeOptions::escape_key`: keys with whitespace at either end are quoted, and
+    // besides the escape sequences of values, `\[` and the escaped delimiter may appear in keys
+    pub(crate) fn unescape_key<'k>(key: &'k str, options: &DeserializeOptions) -> Cow<'k, str> {
+        if !options.unescape {
+            return Cow::Borrowed(key);
+        }
+        let key = Self::unquote(key);
+        if options.escape_mode == EscapeMode::Percent {
+            return match key.contains('%') {
+                true => Cow::Owned(Self::percent_decode(key)),
+                false => Cow::Borrowed(key),
+            };
+        }
+        if !key.contains('\\') {
+            return Cow::Borrowed(key);
+        }
+
+        let mut output = String::with_capacity(key.len());
+        let mut rest = key;
+        while let Some(i) = rest.find('\\') {
+            output.push_str(&rest[..i]);
+            let mut chars = rest[i + 1..].chars();
+            match chars.next() {
+                Some(c) if c == options.delimiter || c == '[' => output.push(c),
+                Some(c) => {
+                    let sequence = &rest[i..i + 1 + c.len_utf8()];
+                    output.push_str(&Self::unescape_value(sequence, EscapeMode::Backslash));
+                }
+                None => output.push('\\'),
+            }
+            rest = chars.as_str();
+        }
+        output.push_str(rest);
+        Cow::Owned(output)
+    }
+
+    // The name of a section header like `[name]`, which may be followed by a comment
+    pub(crate) fn section_header<'l>(
+        line: &'l str,
+        options: &DeserializeOptions,
+    ) -> Option<&'l str> {
+        let line = line.trim();
+        if !line.starts_with('[') {
+            return None;
+        }
+        // Names may contain comment characters, so only a line not ending the header is cut
+        let header = match line.ends_with(']') {
+            true => line,
+            false => Self::strip_inline_comment(line, options.inline_comments).trim_end(),
+        };
+        header.strip_prefix('[')?.strip_suffix(']').map(str::trim)
+    }
+
+    // Cut the value at the first comment character that isn't escaped or quoted
+    pub(crate) fn strip_inline_comment(value: &str, rule: InlineComments) -> &str {
+        if rule == InlineComments::Never {
+            return value;
+        }
+
+        let mut escaped = false;
+        let mut after_whitespace = false;
+        // End of the quoted part being skipped, comment characters within quotes are kept
+        let mut quoted_until = 0;
+
+        for (i, c) in value.char_indices() {
+            if i < quoted_until {
+                continue;
+            }
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                // A quote that is never closed is an ordinary character
+                if let Some(end) = Self::closing_quote(&value[i..]) {
+                    quoted_until = i + end + 1;
+                }
+            } else if (c == ';' || c == '#')
+                && (rule == InlineComments::Anywhere || after_whitespace)
+            {
+                return &value[..i];
+            }
+            after_whitespace = c.is_whitespace();
+        }
+
+        value
+    }
+
+    // Byte offset of the quote closing the one `quoted` starts with, skipping escaped quotes
+    fn closing_quote(quoted: &str) -> Option<usize> {
+        let mut escaped = false;
+        for (i, c) in quoted.char_indices().skip(1) {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    pub(crate) fn unescape_value(value: &str, mode: EscapeMode) -> String {
+        if mode == EscapeMode::Percent {
+            return Self::percent_decode(value);
+        }
+
+        let mut output = String::with_capacity(value.len());
+        let mut chars = value.chars();
+
+        // Consume one escape sequence at a time, so `\\n` stays a backslash followed by `n`
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                output.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('\\') => output.push('\\'),
+                Some('n') => output.push('\n'),
+                Some('r') => output.push('\r'),
+                Some('t') => output.push('\t'),
+                Some('"') => output.push('"'),
+                Some(';') => output.push(';'),
+                Some('#') => output.push('#'),
+                // Unknown escapes and a trailing backslash are kept as written
+                Some(other) => {
+                    output.push('\\');
+                    output.push(other);
+                }
+                None => output.push('\\'),
+            }
+        }
 
-            // Skip empty lines and comments
-            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+        output
+    }
+
+    // Decode `%XX` sequences, keeping a `%` not followed by two hex digits as written
+    fn percent_decode(value: &str) -> String {
+        let mut bytes = Vec::with_capacity(value.len());
+        let mut rest = value.as_bytes();
+
+        while let Some((&byte, tail)) = rest.split_first() {
+            let decoded = match tail {
+                [high, low, ..] if byte == b'%' => {
+                    match ((*high as char).to_digit(16), (*low as char).to_digit(16)) {
+                        (Some(high), Some(low)) => Some((high * 16 + low) as u8),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            match decoded {
+                Some(decoded) => {
+                    bytes.push(decoded);
+                    rest = &tail[2..];
+                }
+                None => {
+                    bytes.push(byte);
+                    rest = tail;
+                }
+            }
+        }
+
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    fn split_seq(value: &str, delimiter: SeqDelimiter) -> Vec<&str> {
+        if value.is_empty() {
+            return Vec::new();
+        }
+
+        let mut elements = Vec::new();
+        let mut start = 0;
+        let mut in_quotes = false;
+        let mut escaped = false;
+
+        for (i, c) in value.char_indices() {
+            if escaped {
+                escaped = false;
                 continue;
             }
 
-            // Section header
-            if line.starts_with('[') && line.ends_with(']') {
-                current_section = line[1..line.len() - 1].to_string();
-                sections.insert(current_section.clone(), HashMap::new());
-                continue;
-            }
+            let is_delimiter = match delimiter {
+                SeqDelimiter::Char(d) => c == d,
+                SeqDelimiter::Whitespace => c.is_whitespace(),
+            };
+
+            if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_quotes = !in_quotes;
+            } else if is_delimiter && !in_quotes {
+                elements.push(value[start..i].trim());
+                start = i + c.len_utf8();
+            }
+        }
+        elements.push(value[start..].trim());
+
+        if delimiter == SeqDelimiter::Whitespace {
+            // Runs of whitespace produce empty elements in between
+            elements.retain(|element| !element.is_empty());
+        }
+
+        elements.into_iter().map(Self::unquote).collect()
+    }
+
+    pub(crate) fn unquote(element: &str) -> &str {
+        let Some(inner) = element
+            .strip_prefix('"')
+            .and_then(|rest| rest.strip_suffix('"'))
+        else {
+            return element;
+        };
+        // The closing quote must not itself be escaped
+        let backslashes = inner.chars().rev().take_while(|&c| c == '\\').count();
+
+        if backslashes % 2 == 0 { inner } else { element }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_bool<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::unsupported_root("bool"))
+    }
+
+    fn deserialize_i8<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::unsupported_root("i8"))
+    }
+
+    fn deserialize_i16<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::unsupported_root("i16"))
+    }
+
+    fn deserialize_i32<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::unsupported_root("i32"))
+    }
+
+    fn deserialize_i64<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::unsupported_root("i64"))
+    }
+
+    fn deserialize_u8<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::unsupported_root("u8"))
+    }
+
+    fn deserialize_u16<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::unsupported_root("u16"))
+    }
+
+    fn deserialize_u32<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::unsupported_root("u32"))
+    }
+
+    fn deserialize_u64<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::unsupported_root("u64"))
+    }
+
+    fn deserialize_i128<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::unsupported_root("i128"))
+    }
 
-            // Key-value pair
-            if let Some(eq_pos) = line.find('=') {
-                let key = line[..eq_pos].trim().to_string();
-                let value = Self::unescape_value(line[eq_pos + 1..].trim());
+    fn deserialize_u128<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::unsupported_root("u128"))
+    }
 
-                if let Some(section) = sections.get_mut(&current_section) {
-                    section.insert(key, value);
-                }
-            }
-        }
+    fn deserialize_f32<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::unsupported_root("f32"))
+    }
 
-        Ok(Deserializer { sections })
+    fn deserialize_f64<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::unsupported_root("f64"))
     }
 
-    fn unescape_value(value: &str) -> String {
-        value
-            .replace("\\\\", "\\")
-            .replace("\\n", "\n")
-            .replace("\\r", "\r")
-            .replace("\\t", "\t")
-            .replace("\\\"", "\"")
-            .replace("\\;", ";")
-            .replace("\\#", "#")
+    fn deserialize_char<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::unsupported_root("char"))
     }
-}
 
-impl<'de> de::Deserializer<'de> for &mut Deserializer {
-    type Error = Error;
+    fn deserialize_str<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::unsupported_root("str"))
+    }
 
-    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_string<V>(self, _visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_map(visitor)
+        Err(Error::unsupported_root("str"))
     }
 
-    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_bool(true)
+        Err(Error::unsupported_root("bytes"))
     }
 
-    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_byte_buf<V>(self, _visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_i8(0)
+        Err(Error::unsupported_root("bytes"))
     }
 
-    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_i16(0)
+        visitor.visit_some(self)
     }
 
-    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_unit<V>(self, _visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_i32(0)
+        Err(Error::unsupported_root("unit"))
     }
 
-    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_unit_struct<V>(self, _name: &'static str, _visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_i64(0)
+        Err(Error::unsupported_root("unit struct"))
     }
 
-    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_u8(0)
+        visitor.visit_newtype_struct(self)
     }
 
-    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_seq<V>(self, _visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_u16(0)
+        Err(Error::unsupported_root("sequence"))
     }
 
-    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_tuple<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_u32(0)
+        Err(Error::unsupported_root("tuple"))
     }
 
-    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        _visitor: V,
+    ) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_u64(0)
+        Err(Error::unsupported_root("tuple struct"))
     }
 
-    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_f32(0.0)
+        if let Some(root_key) = self.options.root_map_key.clone() {
+            return visitor.visit_map(StructAccess::sections(self, root_key));
+        }
+
+        // Like a root struct, which is deserialized as a map when it has flattened fields
+        visitor.visit_map(StructAccess::new(self, "", &[]))
     }
 
-    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_f64(0.0)
+        // The root struct reads the keys before the first section, its fields that are structs
+        // read the sections named after them. Only if none of its fields are found there is the
+        // section named after the struct, as serde's `rename` spells it, read instead.
+        let section = self.name(name);
+        let fields_in = |section: &str| fields.iter().any(|field| self.has_field(section, field));
+        if !fields.contains(&name) && !fields_in("") && fields_in(&section) {
+            return SectionDeserializer::new(self, &section)
+                .deserialize_struct(name, fields, visitor);
+        }
+        visitor.visit_map(StructAccess::new(self, "", fields))
     }
 
-    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_char('a')
+        visitor.visit_enum(SectionEnumAccess::new(self, ""))
     }
 
-    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_identifier<V>(self, _visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_borrowed_str("")
+        Err(Error::unsupported_root("identifier"))
     }
 
-    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    // Sections and keys are looked up by name rather than consumed in order, so an ignored
+    // section or key needs no skipping. Unknown ones are reported by the deserializers of the
+    // section or entry ignored.
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_string(String::new())
+        visitor.visit_unit()
     }
+}
 
-    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+// Enum to track field source
+enum FieldSource<'de> {
+    Value(Vec<RawValue<'de>>),
+    Section(String),
+    // The root keys only, without the sections
+    Root,
+}
+
+// Struct access over the keys of a section and its subsections, the root section included
+struct StructAccess<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    section: String,
+    fields: Vec<(String, FieldSource<'de>)>,
+    index: usize,
+}
+
+impl<'a, 'de> StructAccess<'a, 'de> {
+    fn new(de: &'a mut Deserializer<'de>, section: &str, names: &[&str]) -> Self {
+        Self::lines(de, section, names, 0..usize::MAX)
+    }
+
+    // Access to the keys of a section assigned within the given lines, such as those following
+    // one of the headers of a repeated section
+    fn lines(
+        de: &'a mut Deserializer<'de>,
+        section: &str,
+        names: &[&str],
+        lines: Range<usize>,
+    ) -> Self {
+        let mut fields = Vec::new();
+        let prefix = if section.is_empty() {
+            String::new()
+        } else {
+            format!("{section}.")
+        };
+
+        // Get the section's own fields
+        if let Some(own_section) = de.section(section) {
+            for (key, values) in &own_section.entries {
+                let values: Vec<_> = values
+                    .iter()
+                    .filter(|value| lines.contains(&value.line))
+                    .cloned()
+                    .collect();
+                if values.is_empty() {
+                    continue;
+                }
+
+                let path = format!("{prefix}{key}");
+                // Check if there's also a section with this name
+                if de.has_section(&path) {
+                    // Prefer section over plain field for self-referential structs
+                    fields.push((key.clone(), FieldSource::Section(path)));
+                } else {
+                    fields.push((key.clone(), FieldSource::Value(values)));
+                }
+            }
+        }
+
+        // Add subsections that don't have corresponding fields
+        Self::push_subsections(de, section, &prefix, &mut fields);
+
+        // Stored names are lowercased, yield the field names they match instead
+        if de.options.case_insensitive {
+            for (key, _) in &mut fields {
+                if let Some(name) = names.iter().find(|name| name.to_lowercase() == *key) {
+                    *key = name.to_string();
+                }
+            }
+        }
+
+        StructAccess {
+            de,
+            section: section.to_string(),
+            fields,
+            index: 0,
+        }
+    }
+
+    // Access to the top-level sections, with the root keys as a section named `root_key`
+    fn sections(de: &'a mut Deserializer<'de>, root_key: String) -> Self {
+        let mut fields = Vec::new();
+        if de.section("").is_some_and(|root| !root.entries.is_empty()) {
+            fields.push((root_key, FieldSource::Root));
+        }
+        Self::push_subsections(de, "", "", &mut fields);
+
+        StructAccess {
+            de,
+            section: String::new(),
+            fields,
+            index: 0,
+        }
+    }
+
+    // `[a.b.c]` implies `b` within `[a]`, see `Deserializer::link_section`
+    fn push_subsections(
+        de: &Deserializer<'de>,
+        section: &str,
+        prefix: &str,
+        fields: &mut Vec<(String, FieldSource<'de>)>,
+    ) {
+        for child in de.subsections.get(section).into_iter().flatten() {
+            if !fields
+                .iter()
+                .any(|(key, source)| key == child && !matches!(source, FieldSource::Root))
+            {
+                let path = format!("{prefix}{child}");
+                fields.push((child.clone(), FieldSource::Section(path)));
+            }
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for StructAccess<'_, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        if self.index < self.fields.len() {
+            let (key, _) = &self.fields[self.index];
+            self.index += 1;
+            seed.deserialize(key.as_str().into_deserializer()).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        match &self.fields[self.index - 1].1 {
+            FieldSource::Section(path) => {
+                if self.de.depth >= self.de.options.max_depth {
+                    return Err(too_deep(self.de.options.max_depth));
+                }
+                self.de.depth += 1;
+                let result = seed.deserialize(&mut SectionDeserializer::new(self.de, path));
+                self.de.depth -= 1;
+                result
+            }
+            _ => self.next_entry_value(seed),
+        }
+    }
+}
+
+impl<'de> StructAccess<'_, 'de> {
+    // The value of a key or the root keys. Kept apart from `next_value_seed`, which nested
+    // sections recurse through, so its locals don't add to the stack of each level.
+    fn next_entry_value<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let (key, source) = &self.fields[self.index - 1];
+        match source {
+            FieldSource::Value(values) => seed
+                .deserialize(EntryDeserializer::new(&self.section, key, values, self.de))
+                .map_err(|e| e.at_field(&self.section, key)),
+            FieldSource::Section(_) => unreachable!("sections are read by `next_value_seed`"),
+            FieldSource::Root => {
+                let fields = self
+                    .de
+                    .section("")
+                    .map(|root| &root.entries)
+                    .into_iter()
+                    .flatten()
+                    .map(|(key, values)| (key.clone(), FieldSource::Value(values.clone())))
+                    .collect();
+                seed.deserialize(MapAccessDeserializer::new(StructAccess {
+                    de: self.de,
+                    section: String::new(),
+                    fields,
+                    index: 0,
+                }))
+            }
+        }
+    }
+}
+
+// Sections nested deeper than the limit, which would be read with as many levels of recursion
+pub(crate) fn too_deep(max_depth: usize) -> Error {
+    Error::unsupported(&format!("sections nested deeper than {max_depth} levels"))
+}
+
+// Section deserializer for nested structs
+struct SectionDeserializer<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    section: String,
+}
+
+impl<'a, 'de> SectionDeserializer<'a, 'de> {
+    fn new(de: &'a mut Deserializer<'de>, section: &str) -> Self {
+        SectionDeserializer {
+            de,
+            section: section.to_string(),
+        }
+    }
+}
+
+impl<'de, 'a> de::Deserializer<'de> for &'a mut SectionDeserializer<'a, 'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_bytes(b"")
+        self.deserialize_struct("", &[], visitor)
     }
 
-    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_byte_buf(Vec::new())
+        visitor
+            .visit_map(StructAccess::new(self.de, &self.section, fields))
+            .map_err(|e| e.with_section(&self.section))
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        // Section exists, so it's Some
         visitor.visit_some(self)
     }
 
-    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_unit()
+        visitor.visit_newtype_struct(self)
     }
 
-    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    // Each header of a repeated section starts another element
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        let headers = self
+            .de
+            .section(&self.section)
+            .map(|section| section.headers.clone())
+            .unwrap_or_default();
+        visitor
+            .visit_seq(RepeatedSectionAccess {
+                de: self.de,
+                section: &self.section,
+                headers,
+                index: 0,
+            })
+            .map_err(|e| e.with_section(&self.section))
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor
+            .visit_enum(SectionEnumAccess::new(self.de, &self.section))
+            .map_err(|e| e.with_section(&self.section))
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let (section, key) = self.section.rsplit_once('.').unwrap_or(("", &self.section));
+        if self.de.options.deny_unknown_fields {
+            return Err(Error::UnknownField {
+                key: key.to_string(),
+                section: section.to_string(),
+            });
+        }
+
+        // A section only implied by its subsections starts at the first of them
+        let nested = format!("{}.", self.section);
+        let line = self
+            .de
+            .sections
+            .iter()
+            .filter(|(name, _)| *name == self.section || name.starts_with(&nested))
+            .filter_map(|(_, section)| section.headers.first().copied())
+            .min()
+            .unwrap_or(0);
+        self.de.warnings.push(Warning::UnknownField {
+            key: key.to_string(),
+            section: section.to_string(),
+            line,
+        });
         visitor.visit_unit()
     }
 
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    // Forward all other deserialize methods to deserialize_any
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct tuple
+        tuple_struct map identifier
+    }
+}
+
+// Elements of a sequence read from a section, one for each of its headers
+struct RepeatedSectionAccess<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    section: &'a str,
+    headers: Vec<usize>,
+    index: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for RepeatedSectionAccess<'_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        // A section written once, or implied by a subsection, is a single element
+        let lines = match self.headers.len() {
+            0 | 1 if self.index == 0 => 0..usize::MAX,
+            len if self.index < len => {
+                let end = self.headers.get(self.index + 1).copied();
+                self.headers[self.index]..end.unwrap_or(usize::MAX)
+            }
+            _ => return Ok(None),
+        };
+        self.index += 1;
+
+        let access = StructAccess::lines(self.de, self.section, &[], lines);
+        seed.deserialize(MapAccessDeserializer::new(access))
+            .map(Some)
+    }
+}
+
+// Access to an enum read from a section, whose tag key names the variant
+struct SectionEnumAccess<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    section: String,
+    tag: String,
+}
+
+impl<'a, 'de> SectionEnumAccess<'a, 'de> {
+    fn new(de: &'a mut Deserializer<'de>, section: &str) -> Self {
+        // Stored keys are lowercased when matching regardless of case
+        let tag = if de.options.case_insensitive {
+            de.options.enum_tag.to_lowercase()
+        } else {
+            de.options.enum_tag.clone()
+        };
+        SectionEnumAccess {
+            de,
+            section: section.to_string(),
+            tag,
+        }
+    }
+
+    // The keys of the section other than the tag
+    fn fields(self, names: &[&str]) -> StructAccess<'a, 'de> {
+        let mut access = StructAccess::new(self.de, &self.section, names);
+        access.fields.retain(|(key, _)| *key != self.tag);
+        access
+    }
+}
+
+impl<'a, 'de> de::EnumAccess<'de> for SectionEnumAccess<'a, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let values = self
+            .de
+            .section(&self.section)
+            .and_then(|section| section.entries.iter().find(|(key, _)| *key == self.tag))
+            .map(|(_, values)| values.clone())
+            .unwrap_or_default();
+        let variant = seed.deserialize(EntryDeserializer::new(
+            &self.section,
+            &self.tag,
+            &values,
+            self.de,
+        ))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for SectionEnumAccess<'_, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
     where
-        V: de::Visitor<'de>,
+        T: de::DeserializeSeed<'de>,
     {
-        visitor.visit_newtype_struct(self)
+        seed.deserialize(MapAccessDeserializer::new(self.fields(&[])))
     }
 
-    fn deserialize_seq<V>(self, _visitor: V) -> Result<V::Value>
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Error::UnsupportedFeature("sequences".to_string()))
+        Err(Error::unsupported("tuple variants"))
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Error::UnsupportedFeature("tuples".to_string()))
+        visitor.visit_map(self.fields(fields))
     }
+}
 
-    fn deserialize_tuple_struct<V>(
-        self,
-        _name: &'static str,
-        _len: usize,
-        _visitor: V,
-    ) -> Result<V::Value>
-    where
-        V: de::Visitor<'de>,
-    {
-        Err(Error::UnsupportedFeature("tuple structs".to_string()))
+// Deserializer for all values assigned to a key
+struct EntryDeserializer<'a, 'de> {
+    section: &'a str,
+    key: &'a str,
+    values: &'a [RawValue<'de>],
+    options: &'a DeserializeOptions,
+    // Where the key is reported if no field reads it
+    warnings: &'a mut Vec<Warning>,
+}
+
+impl<'a, 'de> EntryDeserializer<'a, 'de> {
+    fn new(
+        section: &'a str,
+        key: &'a str,
+        values: &'a [RawValue<'de>],
+        de: &'a mut Deserializer<'de>,
+    ) -> Self {
+        EntryDeserializer {
+            section,
+            key,
+            values,
+            options: &de.options,
+            warnings: &mut de.warnings,
+        }
     }
 
-    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    // Whether the key may be written bare, see `DeserializeOptions::flag_fields`
+    fn is_flag(&self) -> bool {
+        let Some(flags) = &self.options.flag_fields else {
+            return true;
+        };
+        let path = match self.section {
+            "" => self.key.to_string(),
+            section => format!("{section}.{}", self.key),
+        };
+        flags
+            .iter()
+            .any(|flag| match self.options.case_insensitive {
+                true => flag.eq_ignore_ascii_case(&path),
+                false => *flag == path,
+            })
+    }
+
+    // The value a single-value field takes, which is the last one assigned
+    fn value(&self) -> Result<&'a RawValue<'de>> {
+        if let [_, duplicate, ..] = self.values
+            && self.options.duplicate_keys == DuplicateKeys::Error
+        {
+            return Err(duplicate.locate(Error::DuplicateKey {
+                key: self.key.to_string(),
+                section: self.section.to_string(),
+            }));
+        }
+        if let Some(value) = self.values.last()
+            && value.bare
+            && !self.is_flag()
+        {
+            return Err(value.locate(Error::MissingValue {
+                key: self.key.to_string(),
+                section: self.section.to_string(),
+            }));
+        }
+
+        self.values.last().ok_or_else(|| Error::MissingField {
+            field: self.key.to_string(),
+            section: self.section.to_string(),
+        })
+    }
+}
+
+// Forwards single-value methods to the deserializer of the key's value
+macro_rules! forward_to_value {
+    ($($method:ident($($arg:ident: $ty:ty),*))*) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value>
+            where
+                V: de::Visitor<'de>,
+            {
+                let value = self.value()?;
+                ValueDeserializer::borrowed(&value.text, self.options)
+                    .and_then(|de| de.$method($($arg,)* visitor))
+                    .map_err(|e| value.locate(e))
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for EntryDeserializer<'_, 'de> {
+    type Error = Error;
+
+    forward_to_value! {
+        deserialize_any() deserialize_i8() deserialize_i16()
+        deserialize_i32() deserialize_i64() deserialize_u8() deserialize_u16()
+        deserialize_u32() deserialize_u64() deserialize_i128() deserialize_u128()
+        deserialize_f32() deserialize_f64()
+        deserialize_char() deserialize_str() deserialize_string() deserialize_bytes()
+        deserialize_byte_buf() deserialize_unit() deserialize_unit_struct(name: &'static str)
+        deserialize_tuple(len: usize)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_map()
+        deserialize_struct(name: &'static str, fields: &'static [&'static str])
+        deserialize_enum(name: &'static str, variants: &'static [&'static str])
+        deserialize_identifier()
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_map(MapAccess::new(self))
+        let value = self.value()?;
+        if value.bare {
+            return visitor.visit_bool(true);
+        }
+        ValueDeserializer::borrowed(&value.text, self.options)
+            .and_then(|de| de.deserialize_bool(visitor))
+            .map_err(|e| value.locate(e))
     }
 
-    fn deserialize_struct<V>(
-        self,
-        name: &'static str,
-        _fields: &'static [&'static str],
-        visitor: V,
-    ) -> Result<V::Value>
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        // For root struct or when the struct name exists as a section
-        if name.is_empty() || self.sections.contains_key(name) {
-            if name.is_empty() {
-                // Root struct - deserialize the whole INI file
-                visitor.visit_map(RootStructAccess::new(self))
-            } else {
-                // Named section exists
-                visitor.visit_map(StructAccess::new(self, name))
-            }
-        } else {
-            // Check if any section exists (for renamed structs)
-            // This handles cases where the struct might be renamed via serde
-            if self.sections.len() > 1
-                || (self.sections.len() == 1 && !self.sections.contains_key(""))
-            {
-                // We have sections, assume root struct
-                visitor.visit_map(RootStructAccess::new(self))
-            } else {
-                // No sections or only root section
-                visitor.visit_map(StructAccess::new(self, ""))
-            }
+        // A bare key has no value either, but stands for `true`
+        if self.options.empty_as_none
+            && let Some(value) = self.values.last()
+            && value.text.is_empty()
+            && !value.bare
+        {
+            return visitor.visit_none();
         }
+        visitor.visit_some(self)
     }
 
-    fn deserialize_enum<V>(
-        self,
-        _name: &'static str,
-        _variants: &'static [&'static str],
-        _visitor: V,
-    ) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Error::UnsupportedFeature("enums".to_string()))
+        visitor.visit_newtype_struct(self)
     }
 
-    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_str("")
+        match self.values {
+            [value] => visitor
+                .visit_seq(SeqAccess::new(&value.text, self.options))
+                .map_err(|e| value.locate(e)),
+            // A repeated key yields one element per occurrence
+            values => visitor.visit_seq(SeqAccess::repeated(values, self.options)),
+        }
     }
 
+    // Serde ignores keys that don't match a field of the target struct
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        if self.options.deny_unknown_fields {
+            let error = Error::UnknownField {
+                key: self.key.to_string(),
+                section: self.section.to_string(),
+            };
+            return Err(match self.values.first() {
+                Some(value) => value.locate(error),
+                None => error.at_line(0),
+            });
+        }
+
+        self.warnings.push(Warning::UnknownField {
+            key: self.key.to_string(),
+            section: self.section.to_string(),
+            line: self.values.first().map_or(0, |value| value.line),
+        });
         visitor.visit_unit()
     }
 }
 
-struct MapAccess<'a> {
-    de: &'a mut Deserializer,
-    sections: Vec<String>,
-    index: usize,
+pub(crate) struct ValueDeserializer<'a, 'de> {
+    raw: &'a str,
+    // Borrowed from the input where the raw value is and reading it changes nothing
+    value: Cow<'de, str>,
+    options: &'a DeserializeOptions,
 }
 
-impl<'a> MapAccess<'a> {
-    fn new(de: &'a mut Deserializer) -> Self {
-        let sections: Vec<String> = de.sections.keys().cloned().collect();
-        MapAccess {
-            de,
-            sections,
-            index: 0,
-        }
+impl<'a, 'de> ValueDeserializer<'a, 'de> {
+    pub(crate) fn new(raw: &'a str, options: &'a DeserializeOptions) -> Result<Self> {
+        Ok(ValueDeserializer {
+            raw,
+            value: Cow::Owned(Self::read(raw, options)?.into_owned()),
+            options,
+        })
     }
-}
-
-// Enum to track field source
-enum FieldSource {
-    Root(String),
-    Section,
-}
 
-// Root struct access - handles both root fields and sections
-struct RootStructAccess<'a> {
-    de: &'a mut Deserializer,
-    fields: Vec<(String, FieldSource)>,
-    index: usize,
-}
+    // Like `new`, borrowing the value from the input where possible
+    fn borrowed(raw: &'a Cow<'de, str>, options: &'a DeserializeOptions) -> Result<Self> {
+        let value = match raw {
+            Cow::Borrowed(raw) => Self::read(raw, options)?,
+            Cow::Owned(raw) => Cow::Owned(Self::read(raw, options)?.into_owned()),
+        };
+        Ok(ValueDeserializer {
+            raw,
+            value,
+            options,
+        })
+    }
+
+    // The value a raw value stands for, which is only copied if reading it changes anything
+    pub(crate) fn read<'s>(raw: &'s str, options: &DeserializeOptions) -> Result<Cow<'s, str>> {
+        // Quotes keep leading and trailing whitespace, which is trimmed otherwise
+        let value = Deserializer::unquote(raw);
+        let value = if options.unescape && value.contains(options.escape_mode.escape_char()) {
+            Cow::Owned(Deserializer::unescape_value(value, options.escape_mode))
+        } else {
+            Cow::Borrowed(value)
+        };
+        #[cfg(feature = "interpolate")]
+        let value = match &options.variables {
+            Some(variables) if value.contains('$') => {
+                Cow::Owned(crate::interpolate::interpolate(&value, variables)?)
+            }
+            _ => value,
+        };
+        Ok(value)
+    }
 
-impl<'a> RootStructAccess<'a> {
-    fn new(de: &'a mut Deserializer) -> Self {
-        let mut fields = Vec::new();
+    fn parse_int<T: TryFrom<i128> + TryFrom<u128>>(&self, typ: &str) -> Result<T> {
+        Self::integer(&self.value).ok_or_else(|| Error::InvalidValue {
+            typ: typ.to_string(),
+            value: self.value.to_string(),
+        })
+    }
 
-        // Get root section fields
-        if let Some(root_section) = de.sections.get("") {
-            for (key, value) in root_section {
-                // Check if there's also a section with this name
-                if de.sections.contains_key(key) {
-                    // Prefer section over root field for self-referential structs
-                    fields.push((key.clone(), FieldSource::Section));
-                } else {
-                    fields.push((key.clone(), FieldSource::Root(value.clone())));
-                }
-            }
+    // Parse an integer, allowing a `0x`, `0o` or `0b` radix prefix and single `_` between digits
+    fn integer<T: TryFrom<i128> + TryFrom<u128>>(value: &str) -> Option<T> {
+        let (negative, digits) = match value.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, value.strip_prefix('+').unwrap_or(value)),
+        };
+        let (radix, digits) = match digits.get(..2) {
+            Some("0x" | "0X") => (16, &digits[2..]),
+            Some("0o" | "0O") => (8, &digits[2..]),
+            Some("0b" | "0B") => (2, &digits[2..]),
+            _ => (10, digits),
+        };
+        // The sign was taken already, `from_str_radix` would accept another one
+        if digits.starts_with(['+', '-', '_']) || digits.ends_with('_') || digits.contains("__") {
+            return None;
         }
 
-        // Add sections that don't have corresponding root fields
-        for section_name in de.sections.keys() {
-            if !section_name.is_empty() {
-                // Check if we already added this as a field
-                if !fields.iter().any(|(name, _)| name == section_name) {
-                    fields.push((section_name.clone(), FieldSource::Section));
-                }
-            }
+        // Parsed unsigned, so the full range of `u128` and `i128` can be read
+        let magnitude = u128::from_str_radix(&digits.replace('_', ""), radix).ok()?;
+        if negative {
+            let value = 0i128.checked_sub_unsigned(magnitude)?;
+            T::try_from(value).ok()
+        } else {
+            T::try_from(magnitude).ok()
         }
+    }
 
-        RootStructAccess {
-            de,
-            fields,
-            index: 0,
-        }
+    // Parse a float, accepting the spellings of infinity and NaN used by other tools
+    fn parse_float<T: FromStr>(&self, typ: &str) -> Result<T> {
+        let (sign, unsigned) = match self.value.strip_prefix(['+', '-']) {
+            Some(unsigned) => (&self.value[..1], unsigned),
+            None => ("", &*self.value),
+        };
+        let text = match unsigned.to_lowercase().as_str() {
+            "inf" | "infinity" | ".inf" => format!("{sign}inf"),
+            "nan" | ".nan" => "nan".to_string(),
+            _ => self.value.to_string(),
+        };
+
+        T::from_str(&text).map_err(|_| Error::InvalidValue {
+            typ: typ.to_string(),
+            value: self.value.to_string(),
+        })
     }
 }
 
-impl<'de> de::MapAccess<'de> for RootStructAccess<'_> {
-    type Error = Error;
+// Sequence access over the elements of a delimited value or a repeated key
+struct SeqAccess<'a> {
+    // Elements of a repeated key each come with their own line
+    elements: std::vec::IntoIter<(&'a str, Option<&'a RawValue<'a>>)>,
+    options: &'a DeserializeOptions,
+}
 
-    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
-    where
-        K: de::DeserializeSeed<'de>,
-    {
-        if self.index < self.fields.len() {
-            let (key, _) = &self.fields[self.index];
-            self.index += 1;
-            seed.deserialize(key.as_str().into_deserializer()).map(Some)
-        } else {
-            Ok(None)
+impl<'a> SeqAccess<'a> {
+    fn new(value: &'a str, options: &'a DeserializeOptions) -> Self {
+        SeqAccess {
+            elements: Deserializer::split_seq(value, options.seq_delimiter)
+                .into_iter()
+                .map(|element| (element, None))
+                .collect::<Vec<_>>()
+                .into_iter(),
+            options,
         }
     }
 
-    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
-    where
-        V: de::DeserializeSeed<'de>,
-    {
-        let (key, source) = &self.fields[self.index - 1];
-        match source {
-            FieldSource::Root(value) => seed.deserialize(ValueDeserializer::new(value)),
-            FieldSource::Section => seed.deserialize(&mut SectionDeserializer::new(self.de, key)),
+    fn repeated(values: &'a [RawValue<'a>], options: &'a DeserializeOptions) -> Self {
+        SeqAccess {
+            elements: values
+                .iter()
+                .map(|value| (Deserializer::unquote(&value.text), Some(value)))
+                .collect::<Vec<_>>()
+                .into_iter(),
+            options,
         }
     }
 }
 
-impl<'de> de::MapAccess<'de> for MapAccess<'_> {
+impl<'de> de::SeqAccess<'de> for SeqAccess<'_> {
     type Error = Error;
 
-    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
     where
-        K: de::DeserializeSeed<'de>,
+        T: de::DeserializeSeed<'de>,
     {
-        if self.index < self.sections.len() {
-            let key = &self.sections[self.index];
-            self.index += 1;
-            seed.deserialize(key.as_str().into_deserializer()).map(Some)
-        } else {
-            Ok(None)
+        match self.elements.next() {
+            Some((element, value)) => seed
+                .deserialize(ValueDeserializer::new(element, self.options)?)
+                .map(Some)
+                .map_err(|e| match value {
+                    Some(value) => value.locate(e),
+                    None => e,
+                }),
+            None => Ok(None),
         }
     }
 
-    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
-    where
-        V: de::DeserializeSeed<'de>,
-    {
-        let section = &self.sections[self.index - 1];
-        seed.deserialize(&mut SectionDeserializer::new(self.de, section))
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.elements.len())
     }
 }
 
-struct StructAccess {
-    fields: Vec<(String, String)>,
-    index: usize,
+// Map access over the `key=value` elements of an inline map value
+struct InlineMapAccess<'a> {
+    entries: std::vec::IntoIter<(Cow<'a, str>, &'a str)>,
+    value: Option<&'a str>,
+    options: &'a DeserializeOptions,
 }
 
-impl StructAccess {
-    fn new(de: &mut Deserializer, section: &str) -> Self {
-        let fields = if let Some(section_map) = de.sections.get(section) {
-            section_map
-                .iter()
-                .map(|(k, v)| (k.clone(), v.clone()))
-                .collect()
-        } else {
-            Vec::new()
-        };
+impl<'a> InlineMapAccess<'a> {
+    fn new(value: &'a str, options: &'a DeserializeOptions) -> Result<Self> {
+        let entries = Deserializer::split_seq(value, options.seq_delimiter)
+            .into_iter()
+            .map(|entry| match Self::split_entry(entry, options) {
+                Some((key, value)) => Ok((Self::unescape_key(key.trim(), options), value.trim())),
+                None => Err(Error::InvalidValue {
+                    typ: "map entry".to_string(),
+                    value: entry.to_string(),
+                }),
+            })
+            .collect::<Result<Vec<_>>>()?;
 
-        StructAccess { fields, index: 0 }
+        Ok(InlineMapAccess {
+            entries: entries.into_iter(),
+            value: None,
+            options,
+        })
     }
-}
-
-// Section deserializer for nested structs
-struct SectionDeserializer<'a> {
-    de: &'a mut Deserializer,
-    section: String,
-}
 
-impl<'a> SectionDeserializer<'a> {
-    fn new(de: &'a mut Deserializer, section: &str) -> Self {
-        SectionDeserializer {
-            de,
-            section: section.to_string(),
+    // Split an entry at the first `=` that isn't escaped as `\=`, which belongs to the key
+    fn split_entry(entry: &'a str, options: &DeserializeOptions) -> Option<(&'a str, &'a str)> {
+        if !options.unescape || options.escape_mode == EscapeMode::Percent {
+            return entry.split_once('=');
         }
-    }
-}
-
-impl<'de, 'a> de::Deserializer<'de> for &'a mut SectionDeserializer<'a> {
-    type Error = Error;
-
-    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
-    where
-        V: de::Visitor<'de>,
-    {
-        self.deserialize_struct("", &[], visitor)
-    }
-
-    fn deserialize_struct<V>(
-        self,
-        _name: &'static str,
-        _fields: &'static [&'static str],
-        visitor: V,
-    ) -> Result<V::Value>
-    where
-        V: de::Visitor<'de>,
-    {
-        visitor.visit_map(StructAccess::new(self.de, &self.section))
-    }
-
-    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
-    where
-        V: de::Visitor<'de>,
-    {
-        // Section exists, so it's Some
-        visitor.visit_some(self)
+        let mut escaped = false;
+        for (i, c) in entry.char_indices() {
+            if c == '=' && !escaped {
+                return Some((&entry[..i], &entry[i + 1..]));
+            }
+            escaped = c == '\\' && !escaped;
+        }
+        None
     }
 
-    // Forward all other deserialize methods to deserialize_any
-    serde::forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string
-        bytes byte_buf unit unit_struct newtype_struct seq tuple
-        tuple_struct map enum identifier ignored_any
+    // Replace `\=` within a key, leaving the other escape sequences to be read like values
+    fn unescape_key(key: &'a str, options: &DeserializeOptions) -> Cow<'a, str> {
+        if !options.unescape || options.escape_mode == EscapeMode::Percent || !key.contains("\\=") {
+            return Cow::Borrowed(key);
+        }
+        let mut output = String::with_capacity(key.len());
+        let mut chars = key.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                output.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('=') => output.push('='),
+                Some(next) => {
+                    output.push('\\');
+                    output.push(next);
+                }
+                None => output.push('\\'),
+            }
+        }
+        Cow::Owned(output)
     }
 }
 
-impl<'de> de::MapAccess<'de> for StructAccess {
+impl<'de> de::MapAccess<'de> for InlineMapAccess<'_> {
     type Error = Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
     where
         K: de::DeserializeSeed<'de>,
     {
-        if self.index < self.fields.len() {
-            let (key, _) = &self.fields[self.index];
-            self.index += 1;
-            seed.deserialize(key.as_str().into_deserializer()).map(Some)
-        } else {
-            Ok(None)
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(ValueDeserializer::new(&key, self.options)?)
+                    .map(Some)
+            }
+            None => Ok(None),
         }
     }
 
@@ -520,43 +2278,58 @@ impl<'de> de::MapAccess<'de> for StructAccess {
     where
         V: de::DeserializeSeed<'de>,
     {
-        let (_, value) = &self.fields[self.index - 1];
-        seed.deserialize(ValueDeserializer::new(value))
+        let value = self.value.take().unwrap_or_default();
+        seed.deserialize(ValueDeserializer::new(value, self.options)?)
     }
-}
 
-struct ValueDeserializer {
-    value: String,
-}
-
-impl ValueDeserializer {
-    fn new(value: &str) -> Self {
-        ValueDeserializer {
-            value: value.to_string(),
-        }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
     }
 }
 
-impl<'de> de::Deserializer<'de> for ValueDeserializer {
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'_, 'de> {
     type Error = Error;
 
+    // Serde buffers values of flattened structs without knowing their type, so booleans and
+    // numbers can only be recognized by their spelling, integers the same ways typed fields accept
+    // them. Quoted values are always strings.
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_str(visitor)
+        if !self.options.infer_types || self.raw.starts_with('"') {
+            return self.deserialize_string(visitor);
+        }
+
+        let value = &*self.value;
+        if let Ok(v) = value.parse::<bool>() {
+            visitor.visit_bool(v)
+        } else if let Some(v) = Self::integer::<i64>(value) {
+            visitor.visit_i64(v)
+        } else if let Some(v) = Self::integer::<u64>(value) {
+            visitor.visit_u64(v)
+        } else if let Some(v) = value
+            .parse::<f64>()
+            .ok()
+            .filter(|_| value.contains(|c: char| c.is_ascii_digit()))
+        {
+            visitor.visit_f64(v)
+        } else {
+            self.deserialize_string(visitor)
+        }
     }
 
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        match self.value.as_str() {
-            "true" => visitor.visit_bool(true),
-            "false" => visitor.visit_bool(false),
+        // Any spelling of `BoolStyle` is accepted
+        match self.value.to_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => visitor.visit_bool(true),
+            "false" | "no" | "off" | "0" => visitor.visit_bool(false),
             _ => Err(Error::InvalidValue {
                 typ: "bool".to_string(),
-                value: self.value,
+                value: self.value.into_owned(),
             }),
         }
     }
@@ -565,113 +2338,98 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer {
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_i8(i8::from_str(&self.value).map_err(|_| Error::InvalidValue {
-            typ: "i8".to_string(),
-            value: self.value.clone(),
-        })?)
+        visitor.visit_i8(self.parse_int("i8")?)
     }
 
     fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_i16(i16::from_str(&self.value).map_err(|_| Error::InvalidValue {
-            typ: "i16".to_string(),
-            value: self.value.clone(),
-        })?)
+        visitor.visit_i16(self.parse_int("i16")?)
     }
 
     fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_i32(i32::from_str(&self.value).map_err(|_| Error::InvalidValue {
-            typ: "i32".to_string(),
-            value: self.value.clone(),
-        })?)
+        visitor.visit_i32(self.parse_int("i32")?)
     }
 
     fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_i64(i64::from_str(&self.value).map_err(|_| Error::InvalidValue {
-            typ: "i64".to_string(),
-            value: self.value.clone(),
-        })?)
+        visitor.visit_i64(self.parse_int("i64")?)
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_u8(u8::from_str(&self.value).map_err(|_| Error::InvalidValue {
-            typ: "u8".to_string(),
-            value: self.value.clone(),
-        })?)
+        visitor.visit_u8(self.parse_int("u8")?)
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_u16(u16::from_str(&self.value).map_err(|_| Error::InvalidValue {
-            typ: "u16".to_string(),
-            value: self.value.clone(),
-        })?)
+        visitor.visit_u16(self.parse_int("u16")?)
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_u32(u32::from_str(&self.value).map_err(|_| Error::InvalidValue {
-            typ: "u32".to_string(),
-            value: self.value.clone(),
-        })?)
+        visitor.visit_u32(self.parse_int("u32")?)
     }
 
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_u64(u64::from_str(&self.value).map_err(|_| Error::InvalidValue {
-            typ: "u64".to_string(),
-            value: self.value.clone(),
-        })?)
+        visitor.visit_u64(self.parse_int("u64")?)
+    }
+
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_i128(self.parse_int("i128")?)
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_u128(self.parse_int("u128")?)
     }
 
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_f32(f32::from_str(&self.value).map_err(|_| Error::InvalidValue {
-            typ: "f32".to_string(),
-            value: self.value.clone(),
-        })?)
+        visitor.visit_f32(self.parse_float("f32")?)
     }
 
     fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_f64(f64::from_str(&self.value).map_err(|_| Error::InvalidValue {
-            typ: "f64".to_string(),
-            value: self.value.clone(),
-        })?)
+        visitor.visit_f64(self.parse_float("f64")?)
     }
 
     fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        if self.value.len() == 1 {
-            visitor.visit_char(self.value.chars().next().unwrap())
-        } else {
-            Err(Error::InvalidValue {
+        // The value is unescaped already, so `\n` is a single character
+        let mut chars = self.value.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(Error::InvalidValue {
                 typ: "char".to_string(),
-                value: self.value,
-            })
+                value: self.value.into_owned(),
+            }),
         }
     }
 
@@ -679,28 +2437,47 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer {
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_string(self.value)
+        match self.value {
+            Cow::Borrowed(value) => visitor.visit_borrowed_str(value),
+            Cow::Owned(value) => visitor.visit_string(value),
+        }
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_string(self.value)
+        visitor.visit_string(self.value.into_owned())
     }
 
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_bytes(self.value.as_bytes())
+        self.deserialize_byte_buf(visitor)
+    }
+
+    #[cfg(feature = "base64")]
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        use base64::Engine;
+        match base64::engine::general_purpose::STANDARD.decode(self.value.as_bytes()) {
+            Ok(bytes) => visitor.visit_byte_buf(bytes),
+            Err(_) => Err(Error::InvalidValue {
+                typ: "base64".to_string(),
+                value: self.value.into_owned(),
+            }),
+        }
     }
 
+    #[cfg(not(feature = "base64"))]
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_byte_buf(self.value.into_bytes())
+        visitor.visit_byte_buf(self.value.into_owned().into_bytes())
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
@@ -731,37 +2508,45 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer {
         visitor.visit_newtype_struct(self)
     }
 
-    fn deserialize_seq<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Error::UnsupportedFeature("sequences".to_string()))
+        visitor.visit_seq(SeqAccess::new(self.raw, self.options))
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    // Tuples and arrays are split like sequences, but need exactly as many elements
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Error::UnsupportedFeature("tuples".to_string()))
+        let access = SeqAccess::new(self.raw, self.options);
+        if access.elements.len() != len {
+            return Err(Error::InvalidValue {
+                typ: format!("tuple of {len} elements"),
+                value: self.value.into_owned(),
+            });
+        }
+        visitor.visit_seq(access)
     }
 
     fn deserialize_tuple_struct<V>(
         self,
         _name: &'static str,
-        _len: usize,
-        _visitor: V,
+        len: usize,
+        visitor: V,
     ) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Error::UnsupportedFeature("tuple structs".to_string()))
+        self.deserialize_tuple(len, visitor)
     }
 
-    fn deserialize_map<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Error::UnsupportedFeature("maps in values".to_string()))
+        visitor.visit_map(InlineMapAccess::new(self.raw, self.options)?)
     }
 
     fn deserialize_struct<V>(
@@ -773,19 +2558,19 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer {
     where
         V: de::Visitor<'de>,
     {
-        Err(Error::UnsupportedFeature("structs in values".to_string()))
+        Err(Error::unsupported("structs in values"))
     }
 
     fn deserialize_enum<V>(
         self,
         _name: &'static str,
         _variants: &'static [&'static str],
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        Err(Error::UnsupportedFeature("enums".to_string()))
+        visitor.visit_enum(self)
     }
 
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
@@ -802,3 +2587,48 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer {
         visitor.visit_unit()
     }
 }
+
+// An enum written as a value, by the name of its variant
+impl<'de> de::EnumAccess<'de> for ValueDeserializer<'_, 'de> {
+    type Error = Error;
+    type Variant = UnitVariantAccess;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, UnitVariantAccess)>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        Ok((seed.deserialize(self)?, UnitVariantAccess))
+    }
+}
+
+// Only unit variants can be written as a value, those with data need a section
+pub(crate) struct UnitVariantAccess;
+
+impl<'de> de::VariantAccess<'de> for UnitVariantAccess {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, _seed: T) -> Result<T::Value>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        Err(Error::unsupported("enum variants"))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::unsupported("tuple variants"))
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::unsupported("struct variants"))
+    }
+}
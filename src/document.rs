@@ -0,0 +1,276 @@
+use crate::de::{BOM, Deserializer};
+use crate::ser::value_to_string;
+use crate::{DeserializeOptions, LineEnding, SerializeOptions, error::Result};
+use serde::Serialize;
+use std::fmt;
+
+/// An INI file that can be edited in place, keeping its comments, blank lines and formatting.
+///
+/// Values are read and written by section and key, with `""` naming the root section. Lines that
+/// aren't changed are written back exactly as they were parsed, using the line ending (`\n` or
+/// `\r\n`) of the first line.
+///
+/// ```rust
+/// use serini::IniDocument;
+///
+/// let mut doc = IniDocument::parse("; database settings\n[database]\nport = 5432 ; default\n");
+/// assert_eq!(doc.get("database", "port").as_deref(), Some("5432"));
+///
+/// doc.set("database", "port", &6543)?;
+/// assert_eq!(doc.to_string(), "; database settings\n[database]\nport = 6543 ; default\n");
+/// # Ok::<(), serini::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct IniDocument {
+    lines: Vec<Line>,
+    options: DeserializeOptions,
+    bom: bool,
+    trailing_newline: bool,
+    // Line break of the first line, written after every line
+    line_ending: LineEnding,
+}
+
+#[derive(Debug, Clone)]
+enum Line {
+    // Blank lines, comments and anything else that isn't a section or entry, kept verbatim
+    Other(String),
+    Section {
+        text: String,
+        name: String,
+    },
+    // A key/value line, the text spans several lines if it is continued with a backslash
+    Entry {
+        text: String,
+        section: String,
+        key: String,
+    },
+}
+
+impl Line {
+    fn text(&self) -> &str {
+        match self {
+            Line::Other(text) | Line::Section { text, .. } | Line::Entry { text, .. } => text,
+        }
+    }
+
+    fn is_entry(&self, name: &str) -> bool {
+        matches!(self, Line::Entry { section, .. } if section == name)
+    }
+
+    fn is_key(&self, name: &str, key: &str) -> bool {
+        matches!(self, Line::Entry { section, key: k, .. } if section == name && k == key)
+    }
+
+    fn is_section(&self, name: &str) -> bool {
+        matches!(self, Line::Section { name: section, .. } if section == name)
+    }
+}
+
+impl IniDocument {
+    /// Parses an INI string with the default options.
+    pub fn parse(s: &str) -> Self {
+        IniDocument::parse_with(s, &DeserializeOptions::default())
+    }
+
+    /// Parses an INI string, reading values with the given options.
+    pub fn parse_with(s: &str, options: &DeserializeOptions) -> Self {
+        let bom = s.starts_with(BOM);
+        let s = s.strip_prefix(BOM).unwrap_or(s);
+
+        let mut lines = Vec::new();
+        let mut section = String::new();
+        // Physical lines of an entry continued with a trailing backslash
+        let mut continued: Option<Vec<&str>> = None;
+
+        for line in s.lines() {
+            let logical = match continued.take() {
+                Some(mut parts) => {
+                    parts.push(line);
+                    parts
+                }
+                None => vec![line],
+            };
+
+            if Deserializer::strip_continuation(line, options.inline_comments).is_some() {
+                continued = Some(logical);
+            } else {
+                lines.push(Self::parse_line(&logical, &mut section, options));
+            }
+        }
+        if let Some(logical) = continued {
+            lines.push(Self::parse_line(&logical, &mut section, options));
+        }
+
+        IniDocument {
+            lines,
+            options: options.clone(),
+            bom,
+            trailing_newline: s.is_empty() || s.ends_with('\n'),
+            line_ending: match s.find('\n') {
+                Some(end) if s[..end].ends_with('\r') => LineEnding::Crlf,
+                _ => LineEnding::Lf,
+            },
+        }
+    }
+
+    fn parse_line(parts: &[&str], section: &mut String, options: &DeserializeOptions) -> Line {
+        let text = parts.join("\n");
+        let trimmed = parts[0].trim();
+
+        if trimmed.is_empty() || trimmed.starts_with([';', '#']) {
+            Line::Other(text)
+        } else if parts.len() == 1
+            && let Some(name) = Deserializer::section_header(trimmed, options)
+        {
+            *section = name.to_string();
+            Line::Section {
+                text,
+                name: section.clone(),
+            }
+        } else if let Some((key, _)) = Deserializer::split_key(trimmed, options) {
+            Line::Entry {
+                key: Deserializer::unescape_key(key.trim(), options).into_owned(),
+                section: section.clone(),
+                text,
+            }
+        } else {
+            Line::Other(text)
+        }
+    }
+
+    /// Returns the unescaped value of a key, the last one if the key is repeated.
+    pub fn get(&self, section: &str, key: &str) -> Option<String> {
+        let index = self.find(section, key)?;
+        let (_, value, _) = self.split_entry(index);
+        let value = Deserializer::unquote(&value);
+
+        Some(if self.options.unescape {
+            Deserializer::unescape_value(value, self.options.escape_mode)
+        } else {
+            value.to_string()
+        })
+    }
+
+    /// Sets the value of a key, keeping the layout of its line and any trailing comment.
+    ///
+    /// A new key is added after the last key of its section, and a new section at the end of
+    /// the document. Setting a `None` value removes the key.
+    pub fn set<T>(&mut self, section: &str, key: &str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let options = SerializeOptions {
+            delimiter: self.options.delimiter,
+            inline_comments: self.options.inline_comments,
+            escape: self.options.unescape,
+            escape_mode: self.options.escape_mode,
+            ..Default::default()
+        };
+        let Some(value) = value_to_string(value, &options)? else {
+            self.remove(section, key);
+            return Ok(());
+        };
+
+        if let Some(index) = self.find(section, key) {
+            let (before, _, comment) = self.split_entry(index);
+            let text = format!("{before}{value}{comment}");
+            self.lines[index] = Line::Entry {
+                text,
+                section: section.to_string(),
+                key: key.to_string(),
+            };
+            return Ok(());
+        }
+
+        let line = Line::Entry {
+            text: format!(
+                "{} {} {value}",
+                options.escape_key(key)?,
+                self.options.delimiter
+            ),
+            section: section.to_string(),
+            key: key.to_string(),
+        };
+        let last_entry = self.lines.iter().rposition(|line| line.is_entry(section));
+        let header = self.lines.iter().rposition(|line| line.is_section(section));
+        match (last_entry, header) {
+            (Some(index), _) | (None, Some(index)) => self.lines.insert(index + 1, line),
+            // Root keys have to come before the first section header
+            (None, None) if section.is_empty() => {
+                let index = self
+                    .lines
+                    .iter()
+                    .position(|line| matches!(line, Line::Section { .. }))
+                    .unwrap_or(self.lines.len());
+                self.lines.insert(index, line);
+            }
+            (None, None) => {
+                self.lines.push(Line::Section {
+                    text: format!("[{section}]"),
+                    name: section.to_string(),
+                });
+                self.lines.push(line);
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes every occurrence of a key, returning whether there was any.
+    pub fn remove(&mut self, section: &str, key: &str) -> bool {
+        let len = self.lines.len();
+        self.lines.retain(|line| !line.is_key(section, key));
+        self.lines.len() != len
+    }
+
+    fn find(&self, section: &str, key: &str) -> Option<usize> {
+        self.lines
+            .iter()
+            .rposition(|line| line.is_key(section, key))
+    }
+
+    // Split an entry into the text up to its value, the raw value and the trailing comment
+    fn split_entry(&self, index: usize) -> (String, String, String) {
+        let text = self.lines[index].text();
+        let (key, rest) = Deserializer::split_key(text, &self.options).unwrap_or((text, ""));
+
+        // Continued lines are joined the same way they are when deserializing
+        let mut joined = String::new();
+        for (i, part) in rest.split('\n').enumerate() {
+            let part = if i == 0 { part } else { part.trim() };
+            let rule = self.options.inline_comments;
+            joined.push_str(Deserializer::strip_continuation(part, rule).unwrap_or(part));
+        }
+
+        let value = joined.trim_start();
+        let before = format!(
+            "{key}{}{}",
+            self.options.delimiter,
+            &joined[..joined.len() - value.len()]
+        );
+        let uncommented =
+            Deserializer::strip_inline_comment(value, self.options.inline_comments).trim_end();
+        let comment = value[uncommented.len()..].to_string();
+
+        (before, uncommented.to_string(), comment)
+    }
+}
+
+impl fmt::Display for IniDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.bom {
+            write!(f, "{BOM}")?;
+        }
+        // Continued lines are kept joined by `\n`
+        let eol = self.line_ending.as_str();
+        for (i, line) in self.lines.iter().enumerate() {
+            if i > 0 {
+                f.write_str(eol)?;
+            }
+            f.write_str(&line.text().replace('\n', eol))?;
+        }
+        if self.trailing_newline && !self.lines.is_empty() {
+            f.write_str(eol)?;
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,5 @@
 use serde::{de, ser};
-use std::fmt;
+use std::{fmt, io, ops::Range, str};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -16,16 +16,142 @@ pub enum Error {
     #[error("invalid value for {typ}: {value}")]
     InvalidValue { typ: String, value: String },
 
-    #[error("unsupported feature: {0}")]
+    #[error("value can't be written without escaping: {value:?}")]
+    UnrepresentableValue { value: String },
+
+    #[error("unsupported feature '{0}'")]
     UnsupportedFeature(String),
 
-    #[error("missing field: {0}")]
-    MissingField(String),
+    #[error("unsupported root type: {typ}, expected a struct or map")]
+    UnsupportedRoot { typ: String },
+
+    #[error("missing field: {field}{}", in_section(section))]
+    MissingField { field: String, section: String },
+
+    #[error("duplicate key: {key}{}", in_section(section))]
+    DuplicateKey { key: String, section: String },
+
+    #[error("duplicate section: [{name}]")]
+    DuplicateSection { name: String },
+
+    #[error("unknown field: {key}{}", in_section(section))]
+    UnknownField { key: String, section: String },
+
+    #[error("missing value: {key}{}", in_section(section))]
+    MissingValue { key: String, section: String },
+
+    #[error("{source}{}{}", at_field(field), in_section(section))]
+    AtField {
+        /// The field the value belongs to, empty if the whole section can't be represented.
+        field: String,
+        /// Path of the section holding the field, empty for the root.
+        section: String,
+        source: Box<Error>,
+    },
+
+    #[error("line {line}: {source}")]
+    AtLine {
+        line: usize,
+        /// Byte range of the offending value within the input, unless it spans several lines.
+        span: Option<Range<usize>>,
+        source: Box<Error>,
+    },
+
+    #[cfg(feature = "interpolate")]
+    #[error("undefined variable: ${{{name}}}")]
+    UndefinedVariable { name: String },
+
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("invalid utf-8: {0}")]
+    InvalidUtf8(#[from] str::Utf8Error),
 
     #[error("custom error: {0}")]
     Custom(String),
 }
 
+impl Error {
+    // Only structs and maps can be represented as an INI document
+    pub(crate) fn unsupported_root(typ: &str) -> Self {
+        Error::UnsupportedRoot {
+            typ: typ.to_string(),
+        }
+    }
+
+    // Wrapped in `AtField` where the value is written or read
+    pub(crate) fn unsupported(feature: &str) -> Self {
+        Error::UnsupportedFeature(feature.to_string())
+    }
+
+    // Attach the section a missing field was expected in, unless a nested struct already did
+    pub(crate) fn with_section(self, name: &str) -> Self {
+        match self {
+            Error::MissingField { field, section } if section.is_empty() => Error::MissingField {
+                field,
+                section: name.to_string(),
+            },
+            Error::UnsupportedFeature(_) => Error::AtField {
+                field: String::new(),
+                section: name.to_string(),
+                source: Box::new(self),
+            },
+            Error::AtLine { line, span, source } => Error::AtLine {
+                line,
+                span,
+                source: Box::new(source.with_section(name)),
+            },
+            _ => self,
+        }
+    }
+
+    // Attach the field an unsupported value was found at, unless a nested field already did
+    pub(crate) fn at_field(self, section_name: &str, field_name: &str) -> Self {
+        match self {
+            Error::UnsupportedFeature(_) => Error::AtField {
+                field: field_name.to_string(),
+                section: section_name.to_string(),
+                source: Box::new(self),
+            },
+            Error::AtLine { line, span, source } => Error::AtLine {
+                line,
+                span,
+                source: Box::new(source.at_field(section_name, field_name)),
+            },
+            _ => self,
+        }
+    }
+
+    /// Byte range of the value within the input that caused the error, e.g. to select it in an
+    /// editor. Ranges of values read by [`from_strs`](crate::from_strs) refer to the source the
+    /// value came from.
+    ///
+    /// `None` for errors that aren't caused by a value, and for values continued across lines.
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self {
+            Error::AtLine { span, .. } => span.clone(),
+            _ => None,
+        }
+    }
+
+    // Attach the line the error occurred on, unless it already has one
+    pub(crate) fn at_line(self, line: usize) -> Self {
+        self.at_span(line, None)
+    }
+
+    // Attach the line and byte range of the value causing the error, unless it already has them
+    pub(crate) fn at_span(self, line: usize, span: Option<Range<usize>>) -> Self {
+        match self {
+            Error::AtLine { .. } => self,
+            _ => Error::AtLine {
+                line,
+                span,
+                source: Box::new(self),
+            },
+        }
+    }
+}
+
 impl ser::Error for Error {
     fn custom<T: fmt::Display>(msg: T) -> Self {
         Error::Custom(msg.to_string())
@@ -36,6 +162,31 @@ impl de::Error for Error {
     fn custom<T: fmt::Display>(msg: T) -> Self {
         Error::Custom(msg.to_string())
     }
+
+    fn missing_field(field: &'static str) -> Self {
+        // The section is filled in by the struct the field belongs to
+        Error::MissingField {
+            field: field.to_string(),
+            section: String::new(),
+        }
+    }
+}
+
+fn at_field(field: &str) -> String {
+    if field.is_empty() {
+        String::new()
+    } else {
+        format!(" at field '{field}'")
+    }
+}
+
+// Root keys have no section to name
+pub(crate) fn in_section(section: &str) -> String {
+    if section.is_empty() {
+        String::new()
+    } else {
+        format!(" in section [{section}]")
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
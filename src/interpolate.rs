@@ -0,0 +1,68 @@
+use crate::{Error, error::Result};
+use std::collections::HashMap;
+
+/// Source of the variables referenced as `${NAME}` within values.
+///
+/// References are expanded after escape sequences are replaced. `$$` stands for a literal `$`,
+/// and `${NAME:-fallback}` falls back to the given text when the variable is unset or empty.
+/// Referencing a variable that isn't set fails with [`Error::UndefinedVariable`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Variables {
+    /// The environment of the process.
+    Env,
+    /// The given names and values.
+    Map(HashMap<String, String>),
+}
+
+impl Variables {
+    fn get(&self, name: &str) -> Option<String> {
+        match self {
+            Variables::Env => std::env::var(name).ok(),
+            Variables::Map(map) => map.get(name).cloned(),
+        }
+    }
+}
+
+// Expand the variable references within a value
+pub(crate) fn interpolate(value: &str, variables: &Variables) -> Result<String> {
+    let mut output = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(after) = rest.strip_prefix("$$") {
+            output.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after.find('}').ok_or_else(|| Error::InvalidValue {
+                typ: "variable reference".to_string(),
+                value: value.to_string(),
+            })?;
+            let (name, fallback) = match after[..end].split_once(":-") {
+                Some((name, fallback)) => (name, Some(fallback)),
+                None => (&after[..end], None),
+            };
+
+            match (variables.get(name), fallback) {
+                (Some(value), Some(fallback)) if value.is_empty() => output.push_str(fallback),
+                (Some(value), _) => output.push_str(&value),
+                (None, Some(fallback)) => output.push_str(fallback),
+                (None, None) => {
+                    return Err(Error::UndefinedVariable {
+                        name: name.to_string(),
+                    });
+                }
+            }
+            rest = &after[end + 1..];
+        } else {
+            // A `$` that doesn't start a reference is kept as written
+            output.push('$');
+            rest = &rest[1..];
+        }
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
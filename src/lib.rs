@@ -151,6 +151,23 @@
 //! max_connections = 100
 //! ```
 //!
+//! Structs nested deeper than one level are written with a dotted section name, e.g. a `tls`
+//! struct within `http` becomes `[http.tls]`, and read back into the same place.
+//!
+//! The top-level struct reads the keys before the first header, however many sections the file
+//! has. Only if none of its fields are found there, neither as keys nor as sections, does it
+//! read the section named after the struct instead, so `[Config]\nname = x` can be read into a
+//! `struct Config`. The name is the one serde uses, i.e. after `#[serde(rename)]`, and a struct
+//! with a field of the same name always reads the root.
+//!
+//! Root fields are written before the first section header. For parsers expecting every key
+//! within a section, set [`SerializeOptions::root_section`] to write them under a header such as
+//! `[DEFAULT]`, and [`DeserializeOptions::root_section`] to read that section back as the root.
+//!
+//! A file without a fixed schema can be read into a map of sections, such as
+//! `HashMap<String, HashMap<String, String>>`, by setting [`DeserializeOptions::root_map_key`] to
+//! the key that holds the root keys.
+//!
 //! ## Option Handling
 //!
 //! `Option<T>` fields are handled specially:
@@ -188,9 +205,63 @@
 //! ; age =
 //! ```
 //!
+//! The comment character can be changed to `#` with [`SerializeOptions::comment_char`], or the
+//! line left out entirely by setting [`SerializeOptions::option_style`] to [`OptionStyle::Omit`].
+//! [`OptionStyle::EmptyValue`] writes the key with an empty value (`age = `) instead, which
+//! [`DeserializeOptions::empty_as_none`] reads back as `None`.
+//!
+//! An empty value is otherwise read into the `Option`, as `Some("")` for an `Option<String>`.
+//! With [`DeserializeOptions::empty_as_none`] only a quoted `""` is `Some("")`, which is how
+//! [`OptionStyle::EmptyValue`] writes empty strings, so that `None` and `Some("")` both survive a
+//! round trip.
+//!
+//! When reading, a missing key or section leaves an `Option` as `None` and a field marked
+//! `#[serde(default)]` at its default. Any other missing field fails with [`Error::MissingField`],
+//! naming the field and the section it was expected in.
+//!
 //! ## Escape Sequences
 //!
-//! Special characters in values are automatically escaped:
+//! Special characters in values are automatically escaped. When reading, an unescaped `;` or `#`
+//! following whitespace starts a trailing comment (`port = 8080 ; http`), while `url = http://x#y`
+//! is read whole. [`DeserializeOptions::inline_comments`] turns inline comments off, or starts
+//! them at any `;` or `#`. The serializer escapes every `;` and `#` by default, so the output is
+//! read alike by any of the rules. Setting [`SerializeOptions::inline_comments`] to the rule the
+//! file is read with writes them without escaping where they can't start a comment.
+//!
+//! Values that are encoded already, like base64 or percent-encoded URLs, can be written and read
+//! verbatim by turning off both [`SerializeOptions::escape`] and [`DeserializeOptions::unescape`].
+//! The two have to agree, otherwise backslashes are doubled or dropped. A value containing a line
+//! break, or ending in a backslash that would continue the line, can't be written verbatim and
+//! fails with [`Error::UnrepresentableValue`].
+//!
+//! [`EscapeMode::Percent`] writes percent-encoded bytes like `%0A` and `%3B` instead of the
+//! backslash escapes in the table below, for tools that expect them. It is set as
+//! [`SerializeOptions::escape_mode`] and [`DeserializeOptions::escape_mode`], which have to agree
+//! just like escaping itself.
+//!
+//! Values are trimmed when read, so values with leading or trailing whitespace are written in
+//! double quotes (`name = "  padded  "`), which are removed again when reading. Keys are trimmed
+//! as well, so `port=8080` and `port\t=\t8080` are read alike, while whitespace within a key such
+//! as `display name` is kept as written.
+//!
+//! Only the first delimiter ends the key, so a value may contain more of them without escaping:
+//! `dsn = host=db user=app` reads `dsn` as `host=db user=app`. A `;` or `#` within such a value
+//! starts a comment if it follows whitespace, unless escaped, quoted as in `note = "a ; b"` or
+//! allowed by the inline comment rule.
+//!
+//! Keys are escaped too: a map key `a=b` is written as `a\=b = 1` and read back whole. Line breaks
+//! and backslashes within keys are escaped like those of values, a leading `;`, `#` or `[` is
+//! escaped so the line isn't read as a comment or section header, and keys with whitespace at
+//! either end are quoted. With [`SerializeOptions::escape`] off, a key with a delimiter is an
+//! [`Error::UnsupportedFeature`], and one that would need any other escaping an
+//! [`Error::UnrepresentableValue`].
+//!
+//! A line ending in an unescaped `\` is continued on the next line, so long values can be split
+//! across several lines. A `\` ending an inline comment is part of the comment.
+//!
+//! With the `interpolate` feature, setting `DeserializeOptions::variables` expands references
+//! like `${DB_PASSWORD}` within values from the environment or a map, see `Variables`.
+//!
 //!
 //! | Character | Escaped |
 //! |-----------|---------|
@@ -228,29 +299,182 @@
 //! # }
 //! ```
 //!
+//! ## Sequences
+//!
+//! Sequences of scalars are written as a single value, joined by
+//! [`SerializeOptions::seq_separator`] (`", "` by default). Elements that contain the separator
+//! or surrounding whitespace are wrapped in double quotes so they survive being split again by
+//! [`DeserializeOptions::seq_delimiter`].
+//!
+//! ```rust
+//! use serde::{Deserialize, Serialize};
+//! use serini::{from_str, to_string};
+//!
+//! #[derive(Debug, Serialize, Deserialize, PartialEq)]
+//! struct Server {
+//!     allowed_hosts: Vec<String>,
+//!     ports: Vec<u16>,
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let server = Server {
+//!     allowed_hosts: vec!["a.com".to_string(), "b.com".to_string()],
+//!     ports: vec![80, 443],
+//! };
+//!
+//! let ini = to_string(&server)?;
+//! // allowed_hosts = a.com, b.com
+//! // ports = 80, 443
+//!
+//! let parsed: Server = from_str(&ini)?;
+//! assert_eq!(server, parsed);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! When deserializing, a key that is repeated within a section is collected into a sequence with
+//! one element per occurrence, e.g. `include = a` followed by `include = b`. A repeated key read
+//! into a single value takes the last occurrence, unless [`DeserializeOptions::duplicate_keys`]
+//! is set to [`DuplicateKeys::Error`].
+//!
+//! A section header that appears more than once continues the same section. Set
+//! [`DeserializeOptions::duplicate_sections`] to [`DuplicateSections::Error`] to reject it
+//! instead.
+//!
+//! ## Options
+//!
+//! [`to_string_with`] and [`from_str_with`] take a [`SerializeOptions`] or
+//! [`DeserializeOptions`] to change the format, such as the character between keys and values.
+//! A [`ser::Serializer`] can also be created with [`ser::Serializer::with_options`]:
+//!
+//! ```rust
+//! use serde::{Deserialize, Serialize};
+//! use serini::{DeserializeOptions, SerializeOptions, from_str_with, to_string_with};
+//!
+//! #[derive(Serialize, Deserialize, Debug, PartialEq)]
+//! struct Config {
+//!     host: String,
+//!     url: String,
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let config = Config {
+//!     host: "localhost".to_string(),
+//!     url: "http://localhost:8080".to_string(),
+//! };
+//!
+//! let ini = to_string_with(&config, &SerializeOptions { delimiter: ':', ..Default::default() })?;
+//! // host : localhost
+//! // url : http://localhost:8080
+//!
+//! let options = DeserializeOptions { delimiter: ':', ..Default::default() };
+//! let parsed: Config = from_str_with(&ini, &options)?;
+//! assert_eq!(config, parsed);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A serializer writing many values can be reused with [`ser::Serializer::reset`], keeping its
+//! buffers allocated.
+//!
+//! The layout of the output is byte-exact: sections follow the lines before them directly and the
+//! document ends with a single line break. Set [`SerializeOptions::blank_line_between_sections`]
+//! to separate sections with a blank line, and [`SerializeOptions::trailing_newline`] to `false`
+//! to leave the last line unterminated. Lines end with `\n`, [`SerializeOptions::line_ending`]
+//! switches to `\r\n` for Windows tools. Both are accepted when reading. Sections are written in
+//! the order of their fields, [`SerializeOptions::section_order`] sorts them by name or puts the
+//! listed ones first.
+//!
+//! The output only depends on the value and the options, so the same value is always written
+//! byte for byte the same. Entries of a `HashMap` come in a different order in every process
+//! though. Use a `BTreeMap` or an `IndexMap` for output that is checked into version control, or
+//! [`SectionOrder::Alphabetical`] where the map only holds sections.
+//!
+//! Lines holding only a key, such as a `verbose` flag, are ignored unless
+//! [`DeserializeOptions::bare_keys`] is set, reading them as an empty value or `true` for a `bool`.
+//! [`SerializeOptions::bare_flags`] writes `true` booleans that way.
+//! [`DeserializeOptions::flag_fields`] limits bare keys to the listed fields, failing with
+//! [`Error::MissingValue`] for others.
+//!
+//! Keys and sections without a matching field are ignored, unless
+//! [`DeserializeOptions::deny_unknown_fields`] is set to reject them with [`Error::UnknownField`].
+//! Names are case-sensitive by default, [`DeserializeOptions::case_insensitive`] matches them
+//! regardless of case, comparing against the field names after any serde `rename`. Names given
+//! with `#[serde(alias = "...")]` are accepted for keys and sections as well.
+//!
+//! [`SerializeOptions::key_transform`] respells all keys and section names when writing, e.g.
+//! as `MAX_CONNECTIONS` for legacy tools expecting uppercase keys. Reading doesn't undo it:
+//! uppercase or lowercase keys are read back with `case_insensitive`, kebab-case or snake_case
+//! keys need fields renamed or aliased to match.
+//!
 //! ## Supported Types
 //!
 //! The following types are supported for serialization and deserialization:
 //!
-//! - **Integers**: `i8`, `i16`, `i32`, `i64`, `u8`, `u16`, `u32`, `u64`
-//! - **Floats**: `f32`, `f64`
-//! - **Boolean**: `bool` (serialized as `true`/`false`)
-//! - **String**: `String`, `&str`
+//! - **Integers**: `i8`, `i16`, `i32`, `i64`, `i128`, `u8`, `u16`, `u32`, `u64`, `u128` (read
+//!   with optional `_` separators between digits and `0x`, `0o` or `0b` prefixes, e.g. `1_000`
+//!   or `0xFF`)
+//! - **Floats**: `f32`, `f64` (written in fixed-point notation, optionally rounded with
+//!   [`SerializeOptions::float_decimals`]; infinity and NaN are written as `inf`, `-inf` and
+//!   `nan`, and read regardless of case, also as `infinity` or `.inf`)
+//! - **Boolean**: `bool` (serialized as `true`/`false`, or another [`BoolStyle`]; `yes`/`no`,
+//!   `on`/`off` and `1`/`0` are read as well, regardless of case)
+//! - **String**: `String`, `&str`, `Cow<str>` (`&str` and `#[serde(borrow)] Cow<str>` borrow from
+//!   the input of [`from_str`] without copying. A `&str` field fails for values that have to be
+//!   unescaped or span several lines, `Cow<str>` holds a copy of those)
 //! - **Option**: `Option<T>` where `T` is a supported type
+//! - **Sequences**: `Vec<T>` and other sequences of scalars, as separator-joined values
+//! - **Repeated sections**: Sequences of structs or maps, written as one section per element
+//!   under the same header, e.g. `[servers]` for each element of `servers: Vec<Server>`. The
+//!   elements can't hold sections themselves
+//! - **Timestamps**: chrono's `DateTime` as RFC 3339 strings with the `chrono` feature, see the
+//!   `timestamp` module
+//! - **Bytes**: Written as base64 with the `base64` feature, or as lossy UTF-8 otherwise. Serde
+//!   only treats fields as bytes with a wrapper like `serde_bytes`, a plain `Vec<u8>` is a sequence
+//! - **Tuples**: Tuples, tuple structs and arrays of scalars, written like sequences and read
+//!   back only with the expected number of elements
 //! - **Structs**: Custom structs with named fields
+//! - **Maps**: Maps with scalar keys, written as a section with one line per entry, or inline as
+//!   `key = k1=v1, k2=v2` with [`SerializeOptions::inline_maps`]
+//! - **Newtype structs**: Serialized as the value they wrap, like `#[serde(transparent)]`
+//!   wrappers. A wrapped string is escaped like any other, a wrapped struct or map is a section
+//! - **Flattened structs**: Fields of a `#[serde(flatten)]` struct are written and read as if
+//!   they belonged to the parent. Fields other than strings rely on
+//!   [`DeserializeOptions::infer_types`]
+//! - **Unit enums**: Fieldless variants, written and read as the variant name. A value naming a
+//!   variant with data fails with [`Error::UnsupportedFeature`]
+//! - **Tagged enums**: Internally tagged enums (`#[serde(tag = "type")]`) with struct variants,
+//!   written as a section whose tag key selects the variant
+//! - **Sectioned enums**: Other enums are read from a section whose
+//!   [`DeserializeOptions::enum_tag`] key, `type` by default, names the variant. The remaining
+//!   keys fill the fields of a struct variant or the value of a newtype variant (reading only)
+//!
+//! Keys are kept in the order they appear in the file, so a section deserialized into an
+//! order-preserving map (such as `IndexMap` with the `indexmap` feature) lists its entries in
+//! file order.
 //!
 //! ## Limitations
 //!
 //! The following serde types are **not** supported:
 //!
-//! - Sequences (Vec, arrays, etc.)
-//! - Tuples and tuple structs
-//! - Enums with variants
-//! - Maps (HashMap, BTreeMap, etc.)
+//! - Nested sequences, including tuples within sequences, and sections within repeated sections
+//! - Strings within flattened structs that look like a boolean or number, unless quoted or read
+//!   with [`DeserializeOptions::infer_types`] turned off, which fields of other types need
+//! - Enum variants with data, except internally tagged struct variants and variants read from a
+//!   section
 //! - Unit structs
 //!
 //! Attempting to serialize or deserialize these types will result in an error.
 //!
+//! The top-level value passed to [`to_string`] or read by [`from_str`] must be a struct or map,
+//! anything else is rejected with [`Error::UnsupportedRoot`].
+//!
+//! Serde recurses through the nested structs of a value, so sections may be nested at most
+//! `max_depth` levels deep, 128 by default. Deeper values fail with [`Error::UnsupportedFeature`]
+//! instead of overflowing the stack. Raise [`SerializeOptions::max_depth`] and
+//! [`DeserializeOptions::max_depth`] only when running on a larger stack, as each level takes a
+//! few KiB of it in unoptimized builds.
+//!
 //! ## Error Handling
 //!
 //! This crate uses an`Error` type using [thiserror] to provide granular error variants:
@@ -269,14 +493,24 @@
 //!
 //! match from_str::<Config>(ini) {
 //!     Ok(_) => println!("Parsed successfully"),
-//!     Err(Error::InvalidValue { typ, value }) => {
-//!         println!("Invalid {} value: {}", typ, value);
-//!     }
+//!     Err(Error::AtLine { line, source, .. }) => match *source {
+//!         Error::InvalidValue { typ, value } => {
+//!             println!("Invalid {} value on line {}: {}", typ, line, value);
+//!         }
+//!         e => println!("Error on line {}: {}", line, e),
+//!     },
 //!     Err(e) => println!("Error: {}", e),
 //! }
 //! # }
 //! ```
 //!
+//! Errors caused by a value are wrapped in [`Error::AtLine`] with the line the value is on, and
+//! the byte range of the value within the input. [`Error::span`] returns the range, e.g. for an
+//! editor to select the value. Values continued across lines have no range.
+//! An [`Error::UnsupportedFeature`] caused by a value is wrapped in [`Error::AtField`], naming
+//! the field and section of the value that can't be represented, such as
+//! `unsupported feature 'nested sequences' at field 'hosts' in section [server]`.
+//!
 //! ## API Reference
 //!
 //! ### Functions
@@ -285,10 +519,68 @@
 //!
 //! Serializes a value to an INI string.
 //!
+//! #### [`to_string_pretty`]
+//!
+//! Serializes a value to an INI string, padding keys so the `=` of each section line up.
+//!
+//! #### [`to_string_described`]
+//!
+//! Serializes a value to an INI string, with the descriptions of its keys and sections given by
+//! [`IniDescribe`] written as comments above them.
+//!
+//! #### [`to_string_in_section`]
+//!
+//! Serializes a value as a single section with the given name, such as `[plugin.foo]`, to build
+//! up a file from several values.
+//!
+//! #### [`to_writer`]
+//!
+//! Serializes a value as INI into any [`std::io::Write`]. Keys of the root section are written as
+//! they are produced, while sections are buffered in memory until the whole value is serialized,
+//! as root keys following them must be written first.
+//!
 //! #### [`from_str`]
 //!
 //! Deserializes an INI string to a value.
 //!
+//! #### [`from_reader`]
+//!
+//! Deserializes a value from any [`std::io::Read`], parsing the input line by line.
+//!
+//! #### [`from_strs`]
+//!
+//! Deserializes a value from several INI strings, such as a base config and an override file.
+//! Keys of later sources replace the same keys of earlier ones, other keys and sections are kept.
+//!
+//! #### [`from_str_into`]
+//!
+//! Deserializes an INI string over an existing value, such as a config holding its defaults.
+//! Fields the string doesn't assign keep their current values. The value's type says which field
+//! each key assigns by implementing [`IniOverlay`].
+//!
+//! #### [`from_slice`]
+//!
+//! Deserializes a value from UTF-8 encoded bytes, failing with [`Error::InvalidUtf8`] otherwise.
+//!
+//! #### [`parse_raw`]
+//!
+//! Parses an INI string into its sections, keys and values as strings, without a target type.
+//!
+//! #### [`Value`]
+//!
+//! Holds a document of unknown shape, with [`to_value`] and [`from_value`] converting between it
+//! and concrete types.
+//!
+//! #### [`IniDocument`]
+//!
+//! Edits the values of an INI file in place, keeping its comments, blank lines and formatting.
+//!
+//! #### [`from_str_with_warnings`]
+//!
+//! Deserializes an INI string like [`from_str_with`], also returning a [`Warning`] for each line
+//! whose indentation or whitespace around the delimiter is inconsistent with the rest of the file,
+//! and for each key or section that matched no field and was ignored.
+//!
 //! ## Advanced Example
 //!
 //! Here's a complete example showing various features:
@@ -401,12 +693,31 @@
 //! This project is licensed under the MIT License - see the LICENSE file for details.
 
 pub mod de;
+pub mod document;
 pub mod error;
+#[cfg(feature = "interpolate")]
+pub mod interpolate;
 pub mod ser;
+#[cfg(feature = "chrono")]
+pub mod timestamp;
+pub mod value;
 
-pub use de::from_str;
+pub use de::{
+    DeserializeOptions, DuplicateKeys, DuplicateSections, EscapeMode, IniOverlay, InlineComments,
+    OverlaySeed, RawSections, SeqDelimiter, Warning, from_reader, from_reader_with, from_slice,
+    from_str, from_str_into, from_str_into_with, from_str_with, from_str_with_warnings, from_strs,
+    from_strs_with, parse_raw, parse_raw_with,
+};
+pub use document::IniDocument;
 pub use error::Error;
-pub use ser::to_string;
+#[cfg(feature = "interpolate")]
+pub use interpolate::Variables;
+pub use ser::{
+    BoolStyle, IniDescribe, KeyTransform, LineEnding, OptionStyle, SectionOrder, SerializeOptions,
+    to_string, to_string_described, to_string_described_with, to_string_in_section,
+    to_string_in_section_with, to_string_pretty, to_string_with, to_writer, to_writer_with,
+};
+pub use value::{Value, from_value, to_value};
 
 #[cfg(test)]
 mod tests {
@@ -512,6 +823,7 @@ mod tests {
         }
 
         #[test]
+        #[allow(clippy::bool_assert_comparison)]
         fn test_deserialize_nested() {
             let ini_str = r#"
     name = My App
@@ -588,6 +900,74 @@ mod tests {
             assert_eq!("speed = 2", lines[movie_idx + 1]);
         }
 
+        #[test]
+        fn test_serialize_boxed_output() {
+            let config = Config {
+                speed: 1.0,
+                anime: Some(Box::new(Config {
+                    speed: 1.5,
+                    anime: None,
+                    movie: None,
+                })),
+                movie: None,
+            };
+
+            // `movie` isn't a section, so it is commented out in both places
+            assert_eq!(
+                to_string(&config).unwrap(),
+                "speed = 1\n; movie = \n[anime]\nspeed = 1.5\n; movie = \n"
+            );
+
+            let config = Config {
+                movie: Some(Box::new(Config {
+                    speed: 2.0,
+                    anime: None,
+                    movie: None,
+                })),
+                ..config
+            };
+            assert_eq!(
+                to_string(&config).unwrap(),
+                "speed = 1\n[anime]\nspeed = 1.5\n[movie]\nspeed = 2\n"
+            );
+        }
+
+        #[test]
+        fn test_serialize_none_named_like_section() {
+            #[derive(Serialize)]
+            struct Server {
+                host: String,
+                tls: Option<String>,
+            }
+
+            #[derive(Serialize)]
+            struct Tls {
+                cert: String,
+            }
+
+            #[derive(Serialize)]
+            struct Root {
+                tls: Tls,
+                server: Server,
+            }
+
+            let root = Root {
+                tls: Tls {
+                    cert: "cert.pem".to_string(),
+                },
+                server: Server {
+                    host: "localhost".to_string(),
+                    tls: None,
+                },
+            };
+
+            // The `tls` of the server is a plain value, unlike the root section of that name
+            assert_eq!(
+                to_string(&root).unwrap(),
+                "[tls]\ncert = cert.pem\n[server]\nhost = localhost\n; tls = \n"
+            );
+        }
+
         #[test]
         fn test_deserialize_boxed() {
             let ini_str = r#"
@@ -612,26 +992,5678 @@ mod tests {
             assert!(movie.anime.is_none());
             assert!(movie.movie.is_none());
         }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Node {
+            depth: usize,
+            child: Option<Box<Node>>,
+        }
+
+        fn nested(levels: usize) -> Node {
+            let mut node = Node {
+                depth: levels,
+                child: None,
+            };
+            for depth in (1..levels).rev() {
+                node = Node {
+                    depth,
+                    child: Some(Box::new(node)),
+                };
+            }
+            node
+        }
+
+        #[test]
+        fn test_deeply_nested() {
+            // The root and as many sections as `max_depth` allows
+            const DEPTH: usize = 129;
+
+            let node = nested(DEPTH);
+            let ini = to_string(&node).unwrap();
+            assert_eq!(ini.matches('[').count(), DEPTH - 1);
+            let deepest = vec!["child"; DEPTH - 1].join(".");
+            assert!(ini.ends_with(&format!("[{deepest}]\ndepth = {DEPTH}\n")));
+
+            let read: Node = from_str(&ini).unwrap();
+            assert_eq!(read, node);
+            assert_eq!(from_value::<Node>(to_value(&node).unwrap()).unwrap(), node);
+        }
+
+        #[test]
+        fn test_too_deeply_nested() {
+            const DEPTH: usize = 5000;
+
+            // Deeper sections are an error rather than a stack overflow
+            let node = nested(DEPTH);
+            let err = to_string(&node).unwrap_err();
+            assert!(
+                err.to_string().contains("nested deeper than 128 levels"),
+                "{err}"
+            );
+            assert!(to_value(&node).is_err());
+
+            let deepest = vec!["child"; DEPTH - 1].join(".");
+            let ini = format!("depth = 1\n[{deepest}]\ndepth = {DEPTH}\n");
+            let err = from_str::<Node>(&ini).unwrap_err();
+            assert!(
+                err.to_string().contains("nested deeper than 128 levels"),
+                "{err}"
+            );
+
+            // The limit can be raised for callers that run on a larger stack
+            let options = SerializeOptions {
+                max_depth: 4,
+                ..Default::default()
+            };
+            assert!(to_string_with(&nested(5), &options).is_ok());
+            assert!(to_string_with(&nested(6), &options).is_err());
+        }
     }
 
-    #[test]
-    fn test_escaping() {
+    mod align {
+        use super::*;
+
         #[derive(Debug, Serialize, Deserialize, PartialEq)]
-        struct EscapeTest {
-            multiline: String,
-            special_chars: String,
+        struct Config {
+            name: String,
+            short: Short,
+            long: Long,
         }
 
-        let test = EscapeTest {
-            multiline: "Line 1\nLine 2\tTabbed".to_string(),
-            special_chars: "Value with \"quotes\" and ; semicolon # hash".to_string(),
-        };
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Short {
+            a: u8,
+            bcd: u8,
+        }
 
-        let ini_str = to_string(&test).unwrap();
-        assert!(ini_str.contains(r"Line 1\nLine 2\tTabbed"));
-        assert!(ini_str.contains(r#"Value with \"quotes\" and \; semicolon \# hash"#));
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Long {
+            host: String,
+            max_connections: Option<u32>,
+            password: Option<String>,
+        }
 
-        let deserialized: EscapeTest = from_str(&ini_str).unwrap();
-        assert_eq!(test, deserialized);
+        #[test]
+        fn test_align_per_section() {
+            let config = Config {
+                name: "app".to_string(),
+                short: Short { a: 1, bcd: 2 },
+                long: Long {
+                    host: "localhost".to_string(),
+                    max_connections: Some(10),
+                    password: None,
+                },
+            };
+
+            let options = SerializeOptions {
+                align: true,
+                ..Default::default()
+            };
+            let ini = to_string_with(&config, &options).unwrap();
+            let lines: Vec<&str> = ini.lines().collect();
+
+            assert_eq!(
+                lines,
+                vec![
+                    "name = app",
+                    "[short]",
+                    "a   = 1",
+                    "bcd = 2",
+                    "[long]",
+                    "host            = localhost",
+                    "max_connections = 10",
+                    "; password      = ",
+                ]
+            );
+
+            let parsed: Config = from_str(&ini).unwrap();
+            assert_eq!(config, parsed);
+        }
+
+        #[test]
+        fn test_to_string_pretty() {
+            let config = Config {
+                name: "app".to_string(),
+                short: Short { a: 1, bcd: 2 },
+                long: Long {
+                    host: "localhost".to_string(),
+                    max_connections: None,
+                    password: Some("secret".to_string()),
+                },
+            };
+
+            let ini = to_string_pretty(&config).unwrap();
+            assert_eq!(
+                ini,
+                "name = app\n[short]\na   = 1\nbcd = 2\n[long]\nhost              = localhost\n\
+                 ; max_connections = \npassword          = secret\n"
+            );
+
+            let parsed: Config = from_str(&ini).unwrap();
+            assert_eq!(config, parsed);
+        }
+    }
+
+    mod header {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            database: Database,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Database {
+            host: String,
+        }
+
+        #[test]
+        fn test_header_padding() {
+            let config = Config {
+                name: "app".to_string(),
+                database: Database {
+                    host: "localhost".to_string(),
+                },
+            };
+
+            let options = SerializeOptions {
+                header_padding: true,
+                ..Default::default()
+            };
+            let ini = to_string_with(&config, &options).unwrap();
+            assert!(ini.lines().any(|line| line == "[ database ]"));
+
+            let parsed: Config = from_str(&ini).unwrap();
+            assert_eq!(config, parsed);
+        }
+    }
+
+    mod tagged {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            backend: Backend,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        #[serde(tag = "type", rename_all = "lowercase")]
+        enum Backend {
+            Redis { url: String },
+            File { path: String, mode: String },
+        }
+
+        #[test]
+        fn test_roundtrip_tagged_enum_section() {
+            for backend in [
+                Backend::Redis {
+                    url: "redis://localhost".to_string(),
+                },
+                Backend::File {
+                    path: "/var/cache".to_string(),
+                    mode: "rw".to_string(),
+                },
+            ] {
+                let config = Config {
+                    name: "app".to_string(),
+                    backend,
+                };
+
+                let ini = to_string(&config).unwrap();
+                assert!(ini.contains("[backend]"));
+
+                let parsed: Config = from_str(&ini).unwrap();
+                assert_eq!(config, parsed);
+            }
+        }
+
+        #[test]
+        fn test_deserialize_tagged_enum_section() {
+            let ini = r#"
+    name = app
+
+    [backend]
+    type = redis
+    url = redis://localhost
+    "#;
+
+            let config: Config = from_str(ini).unwrap();
+            assert_eq!(
+                config.backend,
+                Backend::Redis {
+                    url: "redis://localhost".to_string()
+                }
+            );
+        }
+    }
+
+    mod unit_enum {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        enum Level {
+            Low,
+            High,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            level: Level,
+            levels: Vec<Level>,
+            fallback: Option<Level>,
+        }
+
+        #[test]
+        fn test_unit_variant_roundtrip() {
+            let config = Config {
+                level: Level::High,
+                levels: vec![Level::Low, Level::High],
+                fallback: Some(Level::Low),
+            };
+
+            let ini = to_string(&config).unwrap();
+            assert_eq!(ini, "level = High\nlevels = Low, High\nfallback = Low\n");
+
+            let parsed: Config = from_str(&ini).unwrap();
+            assert_eq!(config, parsed);
+        }
+
+        #[test]
+        fn test_unknown_variant() {
+            let result = from_str::<Config>("level = Medium\nlevels = Low");
+            assert!(matches!(result, Err(Error::AtLine { line: 1, source, .. })
+                if matches!(*source, Error::Custom(ref message) if message.contains("Medium"))));
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        #[serde(rename_all = "snake_case")]
+        enum Mode {
+            Read,
+            Write,
+            ReadWrite,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct File {
+            mode: Mode,
+        }
+
+        #[test]
+        fn test_renamed_variants() {
+            for (mode, ini) in [
+                (Mode::Read, "mode = read\n"),
+                (Mode::Write, "mode = write\n"),
+                (Mode::ReadWrite, "mode = read_write\n"),
+            ] {
+                let file = File { mode };
+                assert_eq!(to_string(&file).unwrap(), ini);
+                assert_eq!(from_str::<File>(ini).unwrap(), file);
+            }
+        }
+
+        #[test]
+        fn test_variants_with_data() {
+            #[derive(Debug, Deserialize)]
+            #[allow(dead_code)]
+            enum Limit {
+                None,
+                Fixed(u32),
+                Range(u32, u32),
+                Scaled { factor: u32 },
+            }
+
+            #[derive(Debug, Deserialize)]
+            #[allow(dead_code)]
+            struct Quota {
+                limit: Limit,
+            }
+
+            assert!(from_str::<Quota>("limit = None\n").is_ok());
+            for (variant, feature) in [
+                ("Fixed", "enum variants"),
+                ("Range", "tuple variants"),
+                ("Scaled", "struct variants"),
+            ] {
+                let ini = format!("limit = {variant}\n");
+                assert_eq!(
+                    from_str::<Quota>(&ini).unwrap_err().to_string(),
+                    format!("line 1: unsupported feature '{feature}' at field 'limit'")
+                );
+            }
+        }
+    }
+
+    mod newtype {
+        use super::*;
+        use std::borrow::Cow;
+        use std::collections::{BTreeMap, HashMap};
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Hosts(Vec<String>);
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Env(HashMap<String, String>);
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            hosts: Hosts,
+            env: Env,
+        }
+
+        #[test]
+        fn test_roundtrip_newtype_wrappers() {
+            let config = Config {
+                hosts: Hosts(vec!["a.com".to_string(), "b.com".to_string()]),
+                env: Env(HashMap::from([
+                    ("HOME".to_string(), "/root".to_string()),
+                    ("SHELL".to_string(), "/bin/sh".to_string()),
+                ])),
+            };
+
+            let ini = to_string(&config).unwrap();
+            assert!(ini.contains("hosts = a.com, b.com"));
+            assert!(ini.contains("[env]"));
+            assert!(ini.contains("HOME = /root"));
+            assert!(ini.contains("SHELL = /bin/sh"));
+
+            let parsed: Config = from_str(&ini).unwrap();
+            assert_eq!(config, parsed);
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+        #[serde(transparent)]
+        struct Name(String);
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Port(u16);
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        #[serde(transparent)]
+        struct Tls(TlsSettings);
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct TlsSettings {
+            cert: Name,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Server {
+            name: Name,
+            motd: Cow<'static, str>,
+            port: Port,
+            alias: Option<Name>,
+            fallback: Option<Name>,
+            peers: Vec<Name>,
+            weights: BTreeMap<Name, u32>,
+            tls: Tls,
+        }
+
+        #[test]
+        fn test_roundtrip_str_wrappers() {
+            let server = Server {
+                name: Name("web ; #1".to_string()),
+                motd: Cow::Borrowed("  line\none  "),
+                port: Port(8080),
+                alias: Some(Name("www".to_string())),
+                fallback: None,
+                peers: vec![Name("a, b".to_string()), Name("c".to_string())],
+                weights: BTreeMap::from([(Name("a".to_string()), 1)]),
+                tls: Tls(TlsSettings {
+                    cert: Name("cert.pem".to_string()),
+                }),
+            };
+
+            let ini = to_string(&server).unwrap();
+            assert_eq!(
+                ini,
+                "name = web \\; \\#1\nmotd = \"  line\\none  \"\nport = 8080\nalias = www\n\
+                 ; fallback = \npeers = \"a, b\", c\n[weights]\na = 1\n[tls]\ncert = cert.pem\n"
+            );
+            assert_eq!(from_str::<Server>(&ini).unwrap(), server);
+        }
+    }
+
+    mod map {
+        use super::*;
+        use std::collections::{BTreeMap, HashMap};
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            env: HashMap<String, String>,
+            limits: HashMap<String, u32>,
+        }
+
+        #[test]
+        fn test_map_field_as_section() {
+            let config = Config {
+                name: "app".to_string(),
+                env: HashMap::from([("HOME".to_string(), "/root".to_string())]),
+                limits: HashMap::from([("cpu".to_string(), 2)]),
+            };
+
+            let ini = to_string(&config).unwrap();
+            assert_eq!(ini, "name = app\n[env]\nHOME = /root\n[limits]\ncpu = 2\n");
+
+            let parsed: Config = from_str(&ini).unwrap();
+            assert_eq!(config, parsed);
+        }
+
+        #[test]
+        fn test_map_field_keys_escaped() {
+            let config = Config {
+                name: "app".to_string(),
+                env: HashMap::from([
+                    (";LANG".to_string(), "C".to_string()),
+                    ("#PATH".to_string(), "/usr/bin".to_string()),
+                    ("[admin]".to_string(), "root".to_string()),
+                    ("A\nB = c".to_string(), "d".to_string()),
+                ]),
+                limits: HashMap::new(),
+            };
+
+            // None of the keys ends the section early or turns into a comment or header
+            let ini = to_string(&config).unwrap();
+            assert!(ini.contains("\\;LANG = C\n"), "{ini}");
+            assert!(ini.contains("\\#PATH = /usr/bin\n"), "{ini}");
+            assert!(ini.contains("\\[admin] = root\n"), "{ini}");
+            assert!(ini.contains("A\\nB \\= c = d\n"), "{ini}");
+
+            let parsed: Config = from_str(&ini).unwrap();
+            assert_eq!(config, parsed);
+        }
+
+        #[test]
+        fn test_deserialize_dynamic_section() {
+            let ini = "name = app\n[env]\nPATH = /usr/bin\nLANG = C\n[limits]\n";
+
+            let config: Config = from_str(ini).unwrap();
+            assert_eq!(config.env.len(), 2);
+            assert_eq!(config.env["PATH"], "/usr/bin");
+            assert_eq!(config.env["LANG"], "C");
+            assert!(config.limits.is_empty());
+        }
+
+        #[test]
+        fn test_btree_map_section_sorted() {
+            #[derive(Debug, Serialize, Deserialize, PartialEq)]
+            struct Sorted {
+                aliases: BTreeMap<String, String>,
+            }
+
+            let sorted = Sorted {
+                aliases: BTreeMap::from([
+                    ("st".to_string(), "status".to_string()),
+                    ("co".to_string(), "checkout".to_string()),
+                    ("br".to_string(), "branch".to_string()),
+                ]),
+            };
+
+            let ini = to_string(&sorted).unwrap();
+            assert_eq!(ini, "[aliases]\nbr = branch\nco = checkout\nst = status\n");
+
+            let parsed: Sorted = from_str(&ini).unwrap();
+            assert_eq!(sorted, parsed);
+        }
+    }
+
+    mod seq {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            flags: Vec<String>,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Hosts {
+            allowed_hosts: Vec<String>,
+            ports: Vec<u16>,
+            weights: Option<Vec<f64>>,
+        }
+
+        #[test]
+        fn test_serialize_comma_separated() {
+            let hosts = Hosts {
+                allowed_hosts: vec!["a.com".to_string(), "b.com".to_string()],
+                ports: vec![80, 443, 8080],
+                weights: None,
+            };
+
+            let ini = to_string(&hosts).unwrap();
+            let mut lines = ini.lines();
+            assert_eq!(lines.next(), Some("allowed_hosts = a.com, b.com"));
+            assert_eq!(lines.next(), Some("ports = 80, 443, 8080"));
+            assert_eq!(lines.next(), Some("; weights = "));
+
+            let parsed: Hosts = from_str(&ini).unwrap();
+            assert_eq!(hosts, parsed);
+        }
+
+        #[test]
+        fn test_roundtrip_quoted_elements() {
+            let config = Config {
+                flags: vec![
+                    "a, b".to_string(),
+                    " padded ".to_string(),
+                    String::new(),
+                    "say \"hi\"".to_string(),
+                ],
+            };
+
+            let ini = to_string(&config).unwrap();
+            assert_eq!(
+                ini.trim_end(),
+                r#"flags = "a, b", " padded ", "", say \"hi\""#
+            );
+
+            let parsed: Config = from_str(&ini).unwrap();
+            assert_eq!(config, parsed);
+        }
+
+        #[test]
+        fn test_roundtrip_empty() {
+            let config = Config { flags: Vec::new() };
+
+            let ini = to_string(&config).unwrap();
+            assert_eq!(ini.trim_end(), "flags =");
+
+            let parsed: Config = from_str(&ini).unwrap();
+            assert_eq!(config, parsed);
+        }
+
+        #[test]
+        fn test_roundtrip_custom_separator() {
+            let config = Config {
+                flags: vec!["read".to_string(), "write all".to_string()],
+            };
+
+            let ser_options = SerializeOptions {
+                seq_separator: " ".to_string(),
+                ..Default::default()
+            };
+            let de_options = DeserializeOptions {
+                seq_delimiter: SeqDelimiter::Whitespace,
+                ..Default::default()
+            };
+
+            let ini = to_string_with(&config, &ser_options).unwrap();
+            assert_eq!(ini.trim_end(), r#"flags = read "write all""#);
+
+            let parsed: Config = from_str_with(&ini, &de_options).unwrap();
+            assert_eq!(config, parsed);
+        }
+
+        #[test]
+        fn test_serialize_nested_unsupported() {
+            #[derive(Serialize)]
+            struct Nested {
+                matrix: Vec<Vec<u8>>,
+            }
+
+            let nested = Nested {
+                matrix: vec![vec![1, 2], vec![3]],
+            };
+            assert!(matches!(
+                to_string(&nested),
+                Err(Error::AtField { source, .. })
+                    if matches!(*source, Error::UnsupportedFeature(_))
+            ));
+        }
+
+        #[test]
+        fn test_deserialize_comma_separated() {
+            let config: Config = from_str("flags = read, write , execute").unwrap();
+            assert_eq!(config.flags, vec!["read", "write", "execute"]);
+        }
+
+        #[test]
+        fn test_deserialize_whitespace_separated() {
+            let options = DeserializeOptions {
+                seq_delimiter: SeqDelimiter::Whitespace,
+                ..Default::default()
+            };
+
+            let config: Config = from_str_with("flags = read  write\texecute", &options).unwrap();
+            assert_eq!(config.flags, vec!["read", "write", "execute"]);
+
+            let config: Config =
+                from_str_with(r#"flags = read "write all" execute"#, &options).unwrap();
+            assert_eq!(config.flags, vec!["read", "write all", "execute"]);
+        }
+
+        #[test]
+        fn test_deserialize_empty() {
+            let config: Config = from_str("flags = ").unwrap();
+            assert!(config.flags.is_empty());
+        }
+    }
+
+    mod writer {
+        use super::*;
+        use std::{collections::BTreeMap, io};
+
+        #[derive(Debug, Serialize)]
+        struct Config {
+            name: String,
+            database: Database,
+        }
+
+        #[derive(Debug, Serialize)]
+        struct Database {
+            host: String,
+            password: Option<String>,
+        }
+
+        fn config() -> Config {
+            Config {
+                name: "app".to_string(),
+                database: Database {
+                    host: "localhost".to_string(),
+                    password: None,
+                },
+            }
+        }
+
+        struct FailingWriter;
+
+        impl io::Write for FailingWriter {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk full"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn test_to_writer() {
+            let mut output = Vec::new();
+            to_writer(&mut output, &config()).unwrap();
+
+            assert_eq!(
+                String::from_utf8(output).unwrap(),
+                to_string(&config()).unwrap()
+            );
+        }
+
+        #[test]
+        fn test_to_writer_io_error() {
+            match to_writer(FailingWriter, &config()) {
+                Err(Error::Io(e)) => assert_eq!(e.to_string(), "disk full"),
+                other => panic!("expected io error, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_reused_serializer() {
+            let mut serializer = ser::Serializer::new(Vec::new());
+            config().serialize(&mut serializer).unwrap();
+            let first = serializer.get_ref().clone();
+
+            serializer.reset();
+            serializer.get_mut().clear();
+            let capacity = serializer.get_ref().capacity();
+            config().serialize(&mut serializer).unwrap();
+
+            assert_eq!(serializer.get_ref().capacity(), capacity);
+            assert_eq!(serializer.into_inner(), first);
+            assert_eq!(
+                String::from_utf8(first).unwrap(),
+                "name = app\n[database]\nhost = localhost\n; password = \n"
+            );
+        }
+
+        #[test]
+        fn test_reused_serializer_after_error() {
+            #[derive(Serialize)]
+            struct Broken {
+                name: String,
+                database: Database,
+                servers: Vec<Server>,
+            }
+
+            #[derive(Serialize)]
+            struct Server {
+                port: u16,
+                grid: Vec<Vec<u8>>,
+            }
+
+            let broken = Broken {
+                name: "broken".to_string(),
+                database: config().database,
+                servers: vec![Server {
+                    port: 80,
+                    grid: vec![vec![1]],
+                }],
+            };
+
+            let mut serializer = ser::Serializer::new(Vec::new());
+            assert!(broken.serialize(&mut serializer).is_err());
+
+            serializer.reset();
+            serializer.get_mut().clear();
+            config().serialize(&mut serializer).unwrap();
+            assert_eq!(
+                String::from_utf8(serializer.into_inner()).unwrap(),
+                to_string(&config()).unwrap()
+            );
+        }
+
+        #[test]
+        fn test_reused_serializer_sections() {
+            type Sections = BTreeMap<String, BTreeMap<String, BTreeMap<String, String>>>;
+            let section = |keys: &[&str]| -> BTreeMap<String, String> {
+                keys.iter()
+                    .map(|key| (key.to_string(), "x".to_string()))
+                    .collect()
+            };
+            let mut nested: Sections = BTreeMap::new();
+            nested
+                .entry("a".to_string())
+                .or_default()
+                .insert("b".to_string(), section(&["k"]));
+            nested
+                .entry("c".to_string())
+                .or_default()
+                .insert("d".to_string(), section(&["l", "m"]));
+            nested
+                .entry("c".to_string())
+                .or_default()
+                .insert("e".to_string(), section(&[]));
+
+            // Sections of one value are reused by the next, whatever their depth
+            let mut serializer = ser::Serializer::new(Vec::new());
+            for value in [config(), config()] {
+                serializer.reset();
+                serializer.get_mut().clear();
+                value.serialize(&mut serializer).unwrap();
+                assert_eq!(serializer.get_ref(), to_string(&value).unwrap().as_bytes());
+
+                serializer.reset();
+                serializer.get_mut().clear();
+                nested.serialize(&mut serializer).unwrap();
+                assert_eq!(serializer.get_ref(), to_string(&nested).unwrap().as_bytes());
+            }
+        }
+    }
+
+    mod reader {
+        use super::*;
+        use std::io;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            database: Database,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Database {
+            host: String,
+            port: u16,
+        }
+
+        struct FailingReader;
+
+        impl io::Read for FailingReader {
+            fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("connection reset"))
+            }
+        }
+
+        #[test]
+        fn test_from_reader() {
+            let ini = "name = app\r\n[database]\r\nhost = localhost\r\nport = 5432\r\n";
+
+            let config: Config = from_reader(ini.as_bytes()).unwrap();
+            assert_eq!(config, from_str(ini).unwrap());
+            assert_eq!(config.database.port, 5432);
+        }
+
+        #[test]
+        fn test_mixed_line_endings() {
+            let ini = "name = \"app\"\r\n[database]\nhost = local\\\r\n  host\r\nport = 5432 ; default\r\n";
+            let expected = Config {
+                name: "app".to_string(),
+                database: Database {
+                    host: "localhost".to_string(),
+                    port: 5432,
+                },
+            };
+            assert_eq!(from_str::<Config>(ini).unwrap(), expected);
+            assert_eq!(from_reader::<_, Config>(ini.as_bytes()).unwrap(), expected);
+
+            let options = DeserializeOptions {
+                unescape: false,
+                ..Default::default()
+            };
+            for (_, entries) in parse_raw_with(ini, &options).unwrap() {
+                assert!(entries.iter().all(|(_, value)| !value.contains('\r')));
+            }
+        }
+
+        #[test]
+        fn test_from_reader_io_error() {
+            match from_reader::<_, Config>(FailingReader) {
+                Err(Error::Io(e)) => assert_eq!(e.to_string(), "connection reset"),
+                other => panic!("expected io error, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_byte_order_mark() {
+            let ini = "\u{FEFF}name = app\n[database]\nhost = localhost\nport = 5432\n";
+
+            let config: Config = from_str(ini).unwrap();
+            assert_eq!(config.name, "app");
+            assert_eq!(config, from_reader(ini.as_bytes()).unwrap());
+        }
+
+        #[test]
+        fn test_from_slice() {
+            let ini = b"name = app\n[database]\nhost = localhost\nport = 5432\n";
+
+            let config: Config = from_slice(ini).unwrap();
+            assert_eq!(config.database.host, "localhost");
+        }
+
+        #[test]
+        fn test_from_slice_invalid_utf8() {
+            match from_slice::<Config>(b"name = \xff\n") {
+                Err(Error::InvalidUtf8(e)) => assert_eq!(e.valid_up_to(), 7),
+                other => panic!("expected invalid utf-8 error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_serialize_unsupported_root() {
+        match to_string(&5u32) {
+            Err(Error::UnsupportedRoot { typ }) => assert_eq!(typ, "u32"),
+            other => panic!("expected unsupported root error, got {:?}", other),
+        }
+
+        match to_string(&"hi") {
+            Err(Error::UnsupportedRoot { typ }) => assert_eq!(typ, "str"),
+            other => panic!("expected unsupported root error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_serialize_unsupported_root_wrapped() {
+        #[derive(Serialize)]
+        struct Port(u16);
+
+        // Wrappers are looked through, the scalar within is reported
+        for (result, typ) in [
+            (to_string(&Port(8080)), "u16"),
+            (to_string(&Some(8080)), "i32"),
+            (to_string(&None::<u16>), "none"),
+            (to_string(&()), "unit"),
+            (to_string_in_section("server", &8080u16), "u16"),
+            (to_string_with(&'c', &SerializeOptions::default()), "char"),
+        ] {
+            match result {
+                Err(Error::UnsupportedRoot { typ: actual }) => assert_eq!(actual, typ),
+                other => panic!("expected unsupported root error, got {:?}", other),
+            }
+        }
+
+        // Nothing is written before the error
+        let mut output = Vec::new();
+        assert!(to_writer(&mut output, &"hi").is_err());
+        assert!(output.is_empty());
+
+        assert_eq!(
+            to_string(&8080u16).unwrap_err().to_string(),
+            "unsupported root type: u16, expected a struct or map"
+        );
+    }
+
+    #[test]
+    fn test_deserialize_unsupported_root() {
+        match from_str::<bool>("enabled = false") {
+            Err(Error::UnsupportedRoot { typ }) => assert_eq!(typ, "bool"),
+            other => panic!("expected unsupported root error, got {:?}", other),
+        }
+
+        match from_str::<i32>("42") {
+            Err(Error::UnsupportedRoot { typ }) => assert_eq!(typ, "i32"),
+            other => panic!("expected unsupported root error, got {:?}", other),
+        }
+
+        assert!(matches!(
+            from_str::<String>("name = app"),
+            Err(Error::UnsupportedRoot { .. })
+        ));
+
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Pair(u8, u8);
+
+        for (result, typ) in [
+            (from_str::<Vec<String>>("a, b").map(drop), "sequence"),
+            (from_str::<(u8, u8)>("1, 2").map(drop), "tuple"),
+            (from_str::<Pair>("1, 2").map(drop), "tuple struct"),
+        ] {
+            match result {
+                Err(Error::UnsupportedRoot { typ: actual }) => assert_eq!(actual, typ),
+                other => panic!("expected unsupported root error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_line_number() {
+        #[derive(Debug, Deserialize)]
+        struct Config {
+            name: String,
+            server: Server,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Server {
+            host: String,
+            port: u16,
+            tags: Vec<u8>,
+        }
+
+        let ini = "name = app\n[server]\nhost = localhost\nport = 80\ntags = 1\n";
+        let config: Config = from_str(ini).unwrap();
+        assert_eq!(config.name, "app");
+        assert_eq!(config.server.host, "localhost");
+        assert_eq!((config.server.port, config.server.tags), (80, vec![1]));
+
+        let ini = "name = app\n\n[server]\nhost = localhost\nport = not_a_number\ntags = 1\n";
+        let err = from_str::<Config>(ini).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 5: invalid value for u16: not_a_number"
+        );
+
+        let ini = "name = app\n[server]\nhost = localhost\nport = 80\ntags = 1\ntags = x\n";
+        match from_str::<Config>(ini) {
+            Err(Error::AtLine { line, source, .. }) => {
+                assert_eq!(line, 6);
+                assert!(matches!(*source, Error::InvalidValue { .. }));
+            }
+            other => panic!("expected error at line 6, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_escaping_comment_chars() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            value: String,
+        }
+
+        for (value, escaped) in [
+            (";", r"value = \;"),
+            ("#", r"value = \#"),
+            (r"\;", r"value = \\\;"),
+            (r"\#", r"value = \\\#"),
+        ] {
+            let config = Config {
+                value: value.to_string(),
+            };
+
+            let ini = to_string(&config).unwrap();
+            assert_eq!(ini.trim_end(), escaped);
+
+            let parsed: Config = from_str(&ini).unwrap();
+            assert_eq!(config, parsed);
+        }
+
+        let parsed: Config = from_str(r"value = \;").unwrap();
+        assert_eq!(parsed.value, ";");
+        let parsed: Config = from_str(r"value = \#").unwrap();
+        assert_eq!(parsed.value, "#");
+    }
+
+    #[test]
+    fn test_empty_string_roundtrip() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            title: String,
+            section: Section,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Section {
+            value: String,
+            note: Option<String>,
+        }
+
+        let config = Config {
+            title: String::new(),
+            section: Section {
+                value: String::new(),
+                note: Some(String::new()),
+            },
+        };
+
+        let ini = to_string(&config).unwrap();
+        assert_eq!(ini, "title = \n[section]\nvalue = \nnote = \n");
+
+        let parsed: Config = from_str(&ini).unwrap();
+        assert_eq!(config, parsed);
+    }
+
+    #[test]
+    fn test_quoted_whitespace_roundtrip() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            padded: String,
+            leading: String,
+            plain: String,
+            quoted: String,
+        }
+
+        let config = Config {
+            padded: "  spaced  ".to_string(),
+            leading: "\tindented".to_string(),
+            plain: "no spaces".to_string(),
+            quoted: "\"x\"".to_string(),
+        };
+
+        let ini = to_string(&config).unwrap();
+        assert_eq!(
+            ini,
+            "padded = \"  spaced  \"\nleading = \"\\tindented\"\nplain = no spaces\nquoted = \\\"x\\\"\n"
+        );
+
+        let parsed: Config = from_str(&ini).unwrap();
+        assert_eq!(config, parsed);
+
+        let parsed: Config =
+            from_str("padded = \"  a  \"\nleading = \" b\" ; comment\nplain = \"\"\nquoted = \"")
+                .unwrap();
+        assert_eq!(parsed.padded, "  a  ");
+        assert_eq!(parsed.leading, " b");
+        assert_eq!(parsed.plain, "");
+        assert_eq!(parsed.quoted, "\"");
+    }
+
+    #[test]
+    fn test_quoted_comment_chars() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Config {
+            semicolon: String,
+            hash: String,
+            padded: String,
+            hosts: Vec<String>,
+            unclosed: String,
+        }
+
+        let ini = "semicolon = \"x ; y\"\nhash = \"a#b\" # comment\npadded = \" c;d \" ;\n\
+                   hosts = a, \"b;c\" ; comment\nunclosed = \"x ; y\n";
+        let config: Config = from_str(ini).unwrap();
+        assert_eq!(config.semicolon, "x ; y");
+        assert_eq!(config.hash, "a#b");
+        assert_eq!(config.padded, " c;d ");
+        assert_eq!(config.hosts, ["a", "b;c"]);
+        // A quote that isn't closed doesn't hide the comment
+        assert_eq!(config.unclosed, "\"x");
+    }
+
+    #[test]
+    fn test_line_continuation() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Query {
+            sql: String,
+            path: String,
+            port: u16,
+        }
+
+        let ini =
+            "sql = SELECT * \\\n    FROM users \\\n    WHERE id = 1\npath = C:\\\\\nport = 80\n";
+        let query: Query = from_str(ini).unwrap();
+        assert_eq!(query.sql, "SELECT * FROM users WHERE id = 1");
+        assert_eq!(query.path, "C:\\");
+        assert_eq!(query.port, 80);
+
+        let err = from_str::<Query>("sql = a \\\n  b\npath = p\nport = \\\n x\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 4: invalid value for u16: x");
+
+        // A continuation on the last line ends with the input
+        let query: Query = from_str("path = p\nport = 1\nsql = a \\").unwrap();
+        assert_eq!(query.sql, "a");
+    }
+
+    #[test]
+    fn test_comment_ending_in_backslash() {
+        use std::collections::BTreeMap;
+
+        let ini = "a = 1\nb = c ; note \\\nc = 3\n";
+        let map: BTreeMap<String, String> = from_str(ini).unwrap();
+        assert_eq!(map["b"], "c");
+        assert_eq!(map["c"], "3");
+
+        let doc = IniDocument::parse(ini);
+        assert_eq!(doc.get("", "c").as_deref(), Some("3"));
+        assert_eq!(doc.to_string(), ini);
+
+        // Without inline comments the backslash ends the value
+        let options = DeserializeOptions {
+            inline_comments: InlineComments::Never,
+            ..Default::default()
+        };
+        let map: BTreeMap<String, String> = from_str_with(ini, &options).unwrap();
+        assert_eq!(map["b"], "c ; note c = 3");
+    }
+
+    #[test]
+    fn test_escaped_backslash_before_letter() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            path: String,
+        }
+
+        let config = Config {
+            path: r"C:\nope\tmp".to_string(),
+        };
+
+        let ini = to_string(&config).unwrap();
+        assert_eq!(ini, "path = C:\\\\nope\\\\tmp\n");
+
+        let parsed: Config = from_str(&ini).unwrap();
+        assert_eq!(config, parsed);
+
+        let parsed: Config = from_str(r"path = a\\\nb").unwrap();
+        assert_eq!(parsed.path, "a\\\nb");
+    }
+
+    #[test]
+    fn test_char_roundtrip() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Chars {
+            newline: char,
+            comment: char,
+            space: char,
+            accent: char,
+        }
+
+        let chars = Chars {
+            newline: '\n',
+            comment: ';',
+            space: ' ',
+            accent: 'é',
+        };
+        let ini = to_string(&chars).unwrap();
+        assert_eq!(
+            ini,
+            "newline = \"\\n\"\ncomment = \\;\nspace = \" \"\naccent = é\n"
+        );
+        assert_eq!(from_str::<Chars>(&ini).unwrap(), chars);
+    }
+
+    #[test]
+    fn test_parse_raw() {
+        let ini = "name = app\n[database]\nhost = \" local \"\nport = 5432 ; comment\n\
+                   [env]\nPATH = a\nPATH = b\n";
+        let sections = parse_raw(ini).unwrap();
+        assert_eq!(
+            sections,
+            [
+                (
+                    "".to_string(),
+                    vec![("name".to_string(), "app".to_string())]
+                ),
+                (
+                    "database".to_string(),
+                    vec![
+                        ("host".to_string(), " local ".to_string()),
+                        ("port".to_string(), "5432".to_string()),
+                    ]
+                ),
+                (
+                    "env".to_string(),
+                    vec![
+                        ("PATH".to_string(), "a".to_string()),
+                        ("PATH".to_string(), "b".to_string()),
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_escaping() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct EscapeTest {
+            multiline: String,
+            special_chars: String,
+        }
+
+        let test = EscapeTest {
+            multiline: "Line 1\nLine 2\tTabbed".to_string(),
+            special_chars: "Value with \"quotes\" and ; semicolon # hash".to_string(),
+        };
+
+        let ini_str = to_string(&test).unwrap();
+        assert!(ini_str.contains(r"Line 1\nLine 2\tTabbed"));
+        assert!(ini_str.contains(r#"Value with \"quotes\" and \; semicolon \# hash"#));
+
+        let deserialized: EscapeTest = from_str(&ini_str).unwrap();
+        assert_eq!(test, deserialized);
+    }
+
+    #[cfg(feature = "indexmap")]
+    mod indexmap {
+        use super::*;
+        use ::indexmap::IndexMap;
+
+        #[derive(Debug, Deserialize)]
+        struct Config {
+            name: String,
+            env: IndexMap<String, String>,
+        }
+
+        #[test]
+        fn test_section_map_keeps_file_order() {
+            let ini = "name = app\n[env]\nZ_PATH = /opt/bin\nA_PATH = /usr/bin\nM_PATH = /bin\n";
+
+            let config: Config = from_str(ini).unwrap();
+            assert_eq!(config.name, "app");
+            let keys: Vec<&str> = config.env.keys().map(String::as_str).collect();
+            assert_eq!(keys, ["Z_PATH", "A_PATH", "M_PATH"]);
+            assert_eq!(config.env["A_PATH"], "/usr/bin");
+        }
+
+        #[test]
+        fn test_flattened_fields_roundtrip_in_file_order() {
+            #[derive(Debug, Serialize, Deserialize, PartialEq)]
+            struct Plugins {
+                plugins: Plugin,
+            }
+
+            #[derive(Debug, Serialize, Deserialize, PartialEq)]
+            struct Plugin {
+                enabled: String,
+                #[serde(flatten)]
+                extra: IndexMap<String, String>,
+            }
+
+            let ini = "[plugins]\nzeta = z\nenabled = yes\nalpha = a\nmid = m\n";
+
+            let parsed: Plugins = from_str(ini).unwrap();
+            let keys: Vec<&str> = parsed.plugins.extra.keys().map(String::as_str).collect();
+            assert_eq!(keys, ["zeta", "alpha", "mid"]);
+
+            let ini = to_string(&parsed).unwrap();
+            assert_eq!(
+                ini,
+                "[plugins]\nenabled = yes\nzeta = z\nalpha = a\nmid = m\n"
+            );
+            assert_eq!(parsed, from_str(&ini).unwrap());
+        }
+    }
+
+    mod repeated {
+        use super::*;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Build {
+            name: String,
+            include: Vec<String>,
+            paths: Paths,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Paths {
+            dirs: Vec<String>,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Single {
+            name: String,
+        }
+
+        const INI: &str = "name = app\ninclude = a\ninclude = \"b, c\"\ninclude = d\n\
+                           [paths]\ndirs = /usr\ndirs = /opt\n";
+
+        #[test]
+        fn test_repeated_keys_into_vec() {
+            let build: Build = from_str(INI).unwrap();
+            assert_eq!(build.name, "app");
+            assert_eq!(build.include, ["a", "b, c", "d"]);
+            assert_eq!(build.paths.dirs, ["/usr", "/opt"]);
+        }
+
+        #[test]
+        fn test_repeated_scalar_takes_last() {
+            let single: Single = from_str("name = first\nname = second").unwrap();
+            assert_eq!(single.name, "second");
+        }
+
+        #[test]
+        fn test_repeated_scalar_error() {
+            let options = DeserializeOptions {
+                duplicate_keys: DuplicateKeys::Error,
+                ..Default::default()
+            };
+
+            match from_str_with::<Single>("name = first\nname = second", &options) {
+                Err(Error::AtLine {
+                    line: 2, source, ..
+                }) => {
+                    assert!(matches!(*source, Error::DuplicateKey { key, section }
+                        if key == "name" && section.is_empty()))
+                }
+                other => panic!("expected duplicate key error, got {other:?}"),
+            }
+
+            // Sequences still collect every occurrence
+            let build: Build = from_str_with(INI, &options).unwrap();
+            assert_eq!(build.include, ["a", "b, c", "d"]);
+        }
+    }
+
+    mod inline_map {
+        use super::*;
+        use std::collections::{BTreeMap, HashMap};
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Service {
+            name: String,
+            labels: HashMap<String, String>,
+        }
+
+        fn inline() -> SerializeOptions {
+            SerializeOptions {
+                inline_maps: true,
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn test_inline_map_roundtrip() {
+            let service = Service {
+                name: "web".to_string(),
+                labels: HashMap::from([
+                    ("tier".to_string(), "frontend".to_string()),
+                    ("team".to_string(), "a, b".to_string()),
+                ]),
+            };
+
+            let ini = to_string_with(&service, &inline()).unwrap();
+            assert!(!ini.contains("[labels]"));
+
+            let parsed: Service = from_str(&ini).unwrap();
+            assert_eq!(service, parsed);
+        }
+
+        #[test]
+        fn test_serialize_inline_map() {
+            #[derive(Serialize)]
+            struct Ports {
+                ports: BTreeMap<String, u16>,
+            }
+
+            let ports = Ports {
+                ports: BTreeMap::from([("http".to_string(), 80), ("https".to_string(), 443)]),
+            };
+
+            let ini = to_string_with(&ports, &inline()).unwrap();
+            assert_eq!(ini, "ports = http=80, https=443\n");
+        }
+
+        #[test]
+        fn test_deserialize_inline_map() {
+            let service: Service = from_str("name = web\nlabels = tier=frontend, dsn=a=b").unwrap();
+            assert_eq!(service.labels["tier"], "frontend");
+            assert_eq!(service.labels["dsn"], "a=b");
+
+            match from_str::<Service>("name = web\nlabels = tier") {
+                Err(Error::AtLine {
+                    line: 2, source, ..
+                }) => {
+                    assert!(matches!(*source, Error::InvalidValue { .. }))
+                }
+                other => panic!("expected invalid value error, got {other:?}"),
+            }
+        }
+
+        #[test]
+        fn test_inline_map_special_keys() {
+            #[derive(Debug, Serialize, Deserialize, PartialEq)]
+            struct Env {
+                env: BTreeMap<String, String>,
+            }
+
+            let env = Env {
+                env: BTreeMap::from([
+                    ("a=b".to_string(), "c,d".to_string()),
+                    ("x,y".to_string(), "1".to_string()),
+                    ("p\\=q;r".to_string(), "=".to_string()),
+                ]),
+            };
+
+            let ini = to_string_with(&env, &inline()).unwrap();
+            assert_eq!(ini, "env = \"a\\=b=c,d\", p\\\\\\=q\\;r==, \"x,y=1\"\n");
+            assert_eq!(from_str::<Env>(&ini).unwrap(), env);
+
+            let percent = SerializeOptions {
+                escape_mode: EscapeMode::Percent,
+                ..inline()
+            };
+            let options = DeserializeOptions {
+                escape_mode: EscapeMode::Percent,
+                ..Default::default()
+            };
+            let ini = to_string_with(&env, &percent).unwrap();
+            assert_eq!(from_str_with::<Env>(&ini, &options).unwrap(), env);
+
+            // Verbatim keys can't hold the `=` ending them
+            let verbatim = SerializeOptions {
+                escape: false,
+                ..inline()
+            };
+            assert!(matches!(
+                to_string_with(&env, &verbatim),
+                Err(Error::UnrepresentableValue { ref value }) if value == "a=b"
+            ));
+        }
+    }
+
+    mod warnings {
+        use super::*;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            port: u16,
+            debug: bool,
+        }
+
+        #[test]
+        fn test_mixed_separators() {
+            let ini = "name = app\nport=8080\ndebug = true\n";
+
+            let (config, warnings): (Config, _) =
+                from_str_with_warnings(ini, &DeserializeOptions::default()).unwrap();
+            assert_eq!(config.port, 8080);
+            assert_eq!(warnings, [Warning::InconsistentSeparator { line: 2 }]);
+        }
+
+        #[test]
+        fn test_mixed_indentation() {
+            let ini = "[server]\n  name = app\n\tport = 8080\n  debug = true\n";
+
+            #[derive(Debug, Deserialize)]
+            struct Root {
+                server: Config,
+            }
+
+            let (root, warnings): (Root, _) =
+                from_str_with_warnings(ini, &DeserializeOptions::default()).unwrap();
+            assert_eq!(root.server.port, 8080);
+            assert_eq!(warnings, [Warning::InconsistentIndentation { line: 3 }]);
+        }
+
+        #[test]
+        fn test_consistent_style() {
+            let ini = "name = app\nport = 8080\n\n; comment\ndebug = true\n";
+
+            let (_, warnings): (Config, _) =
+                from_str_with_warnings(ini, &DeserializeOptions::default()).unwrap();
+            assert!(warnings.is_empty());
+        }
+
+        #[test]
+        fn test_unknown_fields() {
+            let ini = "name = app\nprot = 8080\nport = 80\ndebug = true\n\n[server]\nname = api\nport = 1\ndebgu = yes\ndebug = false\n[logs]\nlevel = info\n[cache.redis]\nurl = x\n";
+
+            #[derive(Debug, Deserialize)]
+            struct Root {
+                name: String,
+                port: u16,
+                debug: bool,
+                server: Config,
+            }
+
+            let (root, warnings): (Root, _) =
+                from_str_with_warnings(ini, &DeserializeOptions::default()).unwrap();
+            assert_eq!(
+                (root.name.as_str(), root.port, root.debug),
+                ("app", 80, true)
+            );
+            assert_eq!(root.server.name, "api");
+            assert_eq!(
+                warnings,
+                [
+                    Warning::UnknownField {
+                        key: "prot".to_string(),
+                        section: String::new(),
+                        line: 2,
+                    },
+                    Warning::UnknownField {
+                        key: "debgu".to_string(),
+                        section: "server".to_string(),
+                        line: 9,
+                    },
+                    Warning::UnknownField {
+                        key: "logs".to_string(),
+                        section: String::new(),
+                        line: 11,
+                    },
+                    Warning::UnknownField {
+                        key: "cache".to_string(),
+                        section: String::new(),
+                        line: 13,
+                    },
+                ]
+            );
+            assert_eq!(
+                warnings[1].to_string(),
+                "line 9: unknown field: debgu in section [server]"
+            );
+
+            // The same keys fail when unknown fields are denied
+            let options = DeserializeOptions {
+                deny_unknown_fields: true,
+                ..Default::default()
+            };
+            assert!(matches!(
+                from_str_with_warnings::<Root>(ini, &options),
+                Err(Error::AtLine { line: 2, .. })
+            ));
+        }
+
+        #[test]
+        fn test_no_unknown_fields() {
+            use std::collections::HashMap;
+
+            #[derive(Debug, Deserialize, PartialEq)]
+            #[serde(tag = "type", rename_all = "lowercase")]
+            enum Store {
+                File { path: String },
+            }
+
+            #[derive(Debug, Deserialize, PartialEq)]
+            struct Root {
+                name: String,
+                labels: HashMap<String, String>,
+                store: Store,
+            }
+
+            let ini = "name = app\n[labels]\nteam = core\n[store]\ntype = file\npath = /tmp\n";
+            let (root, warnings): (Root, _) =
+                from_str_with_warnings(ini, &DeserializeOptions::default()).unwrap();
+            assert!(warnings.is_empty(), "{warnings:?}");
+            assert_eq!(
+                root,
+                Root {
+                    name: "app".to_string(),
+                    labels: HashMap::from([("team".to_string(), "core".to_string())]),
+                    store: Store::File {
+                        path: "/tmp".to_string(),
+                    },
+                }
+            );
+        }
+    }
+
+    mod delimiter {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            database: Database,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Database {
+            url: String,
+            port: u16,
+        }
+
+        #[test]
+        fn test_colon_delimiter_roundtrip() {
+            let config = Config {
+                name: "app".to_string(),
+                database: Database {
+                    url: "postgres://localhost:5432".to_string(),
+                    port: 5432,
+                },
+            };
+            let ser_options = SerializeOptions {
+                delimiter: ':',
+                ..Default::default()
+            };
+            let de_options = DeserializeOptions {
+                delimiter: ':',
+                ..Default::default()
+            };
+
+            let ini = to_string_with(&config, &ser_options).unwrap();
+            assert_eq!(
+                ini,
+                "name : app\n[database]\nurl : postgres://localhost:5432\nport : 5432\n"
+            );
+
+            let parsed: Config = from_str_with(&ini, &de_options).unwrap();
+            assert_eq!(config, parsed);
+        }
+
+        #[test]
+        fn test_colon_delimiter_without_spaces() {
+            let options = DeserializeOptions {
+                delimiter: ':',
+                ..Default::default()
+            };
+
+            let ini = "name:app\n[database]\nurl:a=b:c\nport:80\n";
+            let config: Config = from_str_with(ini, &options).unwrap();
+            assert_eq!(config.name, "app");
+            assert_eq!(config.database.url, "a=b:c");
+        }
+
+        #[test]
+        fn test_value_containing_delimiter() {
+            // Only the first delimiter ends the key, the value keeps any others
+            for (ini, url) in [
+                ("url = a=b\nport = 80\n", "a=b"),
+                (
+                    "url=postgres://db?sslmode=require&x=1\nport=80\n",
+                    "postgres://db?sslmode=require&x=1",
+                ),
+                ("url = = leading\nport = 80\n", "= leading"),
+                ("url = trailing=\nport = 80\n", "trailing="),
+            ] {
+                let database: Database = from_str(ini).unwrap();
+                assert_eq!(database.url, url);
+                assert_eq!(parse_raw(ini).unwrap()[0].1[0].1, url);
+                let database: Database = from_reader(ini.as_bytes()).unwrap();
+                assert_eq!(database.url, url);
+            }
+        }
+
+        #[test]
+        fn test_value_containing_delimiter_and_comment_char() {
+            let ini = "url = a=b;c=d\nport = 80\n";
+
+            // Kept whole by default, as the `;` doesn't follow whitespace
+            let database: Database = from_str(ini).unwrap();
+            assert_eq!(database.url, "a=b;c=d");
+
+            // Cut at the `;` when comments start anywhere
+            let options = DeserializeOptions {
+                inline_comments: InlineComments::Anywhere,
+                ..Default::default()
+            };
+            let database: Database = from_str_with(ini, &options).unwrap();
+            assert_eq!(database.url, "a=b");
+
+            for inline_comments in [InlineComments::Never, InlineComments::AfterWhitespace] {
+                let options = DeserializeOptions {
+                    inline_comments,
+                    ..Default::default()
+                };
+                let database: Database = from_str_with(ini, &options).unwrap();
+                assert_eq!(database.url, "a=b;c=d");
+            }
+
+            let database: Database = from_str("url = a=b\\;c=d\nport = 80\n").unwrap();
+            assert_eq!(database.url, "a=b;c=d");
+        }
+
+        #[test]
+        fn test_key_containing_delimiter() {
+            use std::collections::BTreeMap;
+
+            let map = BTreeMap::from([
+                ("a=b".to_string(), "1=2".to_string()),
+                ("plain".to_string(), "3".to_string()),
+            ]);
+            let ini = to_string(&map).unwrap();
+            assert_eq!(ini, "a\\=b = 1=2\nplain = 3\n");
+            assert_eq!(from_str::<BTreeMap<String, String>>(&ini).unwrap(), map);
+
+            #[derive(Debug, Serialize, Deserialize, PartialEq)]
+            struct Renamed {
+                #[serde(rename = "host:port")]
+                address: String,
+                #[serde(rename = "x=y")]
+                flag: bool,
+            }
+
+            let renamed = Renamed {
+                address: "localhost:80".to_string(),
+                flag: true,
+            };
+            let ser_options = SerializeOptions {
+                delimiter: ':',
+                space_around_delimiter: false,
+                bare_flags: true,
+                ..Default::default()
+            };
+            let de_options = DeserializeOptions {
+                delimiter: ':',
+                bare_keys: true,
+                ..Default::default()
+            };
+            let ini = to_string_with(&renamed, &ser_options).unwrap();
+            assert_eq!(ini, "host\\:port:localhost:80\nx=y\n");
+            assert_eq!(
+                from_str_with::<Renamed>(&ini, &de_options).unwrap(),
+                renamed
+            );
+        }
+
+        #[test]
+        fn test_key_escaping_roundtrip() {
+            use std::collections::BTreeMap;
+
+            let map: BTreeMap<String, String> = [
+                "a\nb = c",
+                "x\n[admin]\nrole = root",
+                ";weird",
+                "#x",
+                " pad ",
+                "[s]",
+                "a\\=b",
+                "dir\\",
+                "\"quoted\"",
+            ]
+            .into_iter()
+            .map(|key| (key.to_string(), "v".to_string()))
+            .collect();
+
+            let ini = to_string(&map).unwrap();
+            assert_eq!(
+                ini,
+                "\" pad \" = v\n\\\"quoted\\\" = v\n\\#x = v\n\\;weird = v\n\\[s] = v\n\
+                 a\\nb \\= c = v\na\\\\\\=b = v\ndir\\\\ = v\n\
+                 x\\n[admin]\\nrole \\= root = v\n"
+            );
+            assert_eq!(from_str::<BTreeMap<String, String>>(&ini).unwrap(), map);
+
+            let options = SerializeOptions {
+                space_around_delimiter: false,
+                ..Default::default()
+            };
+            let ini = to_string_with(&map, &options).unwrap();
+            assert_eq!(from_str::<BTreeMap<String, String>>(&ini).unwrap(), map);
+
+            let ser_options = SerializeOptions {
+                escape_mode: EscapeMode::Percent,
+                ..Default::default()
+            };
+            let de_options = DeserializeOptions {
+                escape_mode: EscapeMode::Percent,
+                ..Default::default()
+            };
+            let ini = to_string_with(&map, &ser_options).unwrap();
+            assert_eq!(
+                ini,
+                "%20pad%20 = v\n%22quoted%22 = v\n%23x = v\n%3Bweird = v\n%5Bs] = v\n\
+                 a%0Ab %3D c = v\na\\%3Db = v\ndir\\ = v\nx%0A[admin]%0Arole %3D root = v\n"
+            );
+            assert_eq!(
+                from_str_with::<BTreeMap<String, String>>(&ini, &de_options).unwrap(),
+                map
+            );
+        }
+
+        #[test]
+        fn test_unrepresentable_key() {
+            use std::collections::BTreeMap;
+
+            // Verbatim keys can't be escaped
+            let options = SerializeOptions {
+                escape: false,
+                ..Default::default()
+            };
+            let map = BTreeMap::from([("a=b", 1)]);
+            assert_eq!(
+                to_string_with(&map, &options).unwrap_err().to_string(),
+                "unsupported feature 'delimiters in keys' at field 'a=b'"
+            );
+
+            for key in ["a\nb", ";weird", "[s]", " pad "] {
+                let map = BTreeMap::from([(key, 1)]);
+                assert!(matches!(
+                    to_string_with(&map, &options),
+                    Err(Error::UnrepresentableValue { ref value }) if value == key
+                ));
+            }
+        }
+
+        #[test]
+        fn test_escaped_delimiter_read_verbatim() {
+            use std::collections::BTreeMap;
+
+            let options = DeserializeOptions {
+                unescape: false,
+                ..Default::default()
+            };
+            let map: BTreeMap<String, String> = from_str_with("a\\=b = 1\n", &options).unwrap();
+            assert_eq!(map["a\\"], "b = 1");
+        }
+
+        #[test]
+        fn test_document_key_containing_delimiter() {
+            let mut doc = IniDocument::parse("a\\=b = 1 ; one\n");
+            assert_eq!(doc.get("", "a=b").as_deref(), Some("1"));
+
+            doc.set("", "a=b", &2).unwrap();
+            doc.set("", "c=d", &3).unwrap();
+            assert_eq!(doc.to_string(), "a\\=b = 2 ; one\nc\\=d = 3\n");
+        }
+
+        #[test]
+        fn test_roundtrip_value_containing_delimiter() {
+            for url in ["a=b;c=d", "=", "= leading", "k1=v1 # k2=v2"] {
+                let database = Database {
+                    url: url.to_string(),
+                    port: 80,
+                };
+                let ini = to_string(&database).unwrap();
+                assert_eq!(from_str::<Database>(&ini).unwrap(), database);
+
+                let options = SerializeOptions {
+                    space_around_delimiter: false,
+                    ..Default::default()
+                };
+                let ini = to_string_with(&database, &options).unwrap();
+                assert_eq!(from_str::<Database>(&ini).unwrap(), database);
+            }
+        }
+
+        #[test]
+        fn test_no_space_around_delimiter() {
+            #[derive(Serialize)]
+            struct Optional {
+                name: String,
+                port: Option<u16>,
+            }
+
+            let options = SerializeOptions {
+                space_around_delimiter: false,
+                ..Default::default()
+            };
+
+            let optional = Optional {
+                name: "app".to_string(),
+                port: None,
+            };
+            assert_eq!(
+                to_string_with(&optional, &options).unwrap(),
+                "name=app\n; port=\n"
+            );
+
+            let options = SerializeOptions {
+                align: true,
+                ..options
+            };
+            assert_eq!(
+                to_string_with(&optional, &options).unwrap(),
+                "name  =app\n; port=\n"
+            );
+        }
+
+        #[test]
+        fn test_serializer_with_options() {
+            let config = Config {
+                name: "app".to_string(),
+                database: Database {
+                    url: "localhost".to_string(),
+                    port: 5432,
+                },
+            };
+            let options = SerializeOptions {
+                delimiter: ':',
+                space_around_delimiter: false,
+                ..Default::default()
+            };
+
+            let mut output = Vec::new();
+            config
+                .serialize(&mut ser::Serializer::with_options(&mut output, options))
+                .unwrap();
+            assert_eq!(
+                String::from_utf8(output).unwrap(),
+                "name:app\n[database]\nurl:localhost\nport:5432\n"
+            );
+        }
+    }
+
+    mod inline_comments {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Database {
+            dsn: String,
+        }
+
+        fn verbatim() -> DeserializeOptions {
+            DeserializeOptions {
+                inline_comments: InlineComments::Never,
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn test_dsn_without_inline_comments() {
+            let db: Database =
+                from_str_with("dsn = host=localhost;port=5432", &verbatim()).unwrap();
+            assert_eq!(db.dsn, "host=localhost;port=5432");
+
+            let db: Database =
+                from_str_with(r"dsn = host=localhost\;port=5432", &verbatim()).unwrap();
+            assert_eq!(db.dsn, "host=localhost;port=5432");
+        }
+
+        #[test]
+        fn test_dsn_with_inline_comments() {
+            // Only a comment character following whitespace starts a comment by default
+            let db: Database = from_str("dsn = host=localhost;port=5432 ; primary").unwrap();
+            assert_eq!(db.dsn, "host=localhost;port=5432");
+
+            let options = DeserializeOptions {
+                inline_comments: InlineComments::Anywhere,
+                ..Default::default()
+            };
+            let db: Database = from_str_with("dsn = host=localhost;port=5432", &options).unwrap();
+            assert_eq!(db.dsn, "host=localhost");
+
+            let db: Database = from_str(r"dsn = host=localhost\;port=5432 ; primary").unwrap();
+            assert_eq!(db.dsn, "host=localhost;port=5432");
+        }
+
+        #[test]
+        fn test_dsn_roundtrip_with_inline_comments() {
+            let db = Database {
+                dsn: "host=localhost;port=5432#main".to_string(),
+            };
+
+            let ini = to_string(&db).unwrap();
+            assert_eq!(from_str::<Database>(&ini).unwrap(), db);
+        }
+
+        #[test]
+        fn test_comment_chars_unescaped_without_inline_comments() {
+            let db = Database {
+                dsn: "https://x#frag;a".to_string(),
+            };
+            let options = SerializeOptions {
+                inline_comments: InlineComments::Never,
+                ..Default::default()
+            };
+
+            let ini = to_string_with(&db, &options).unwrap();
+            assert_eq!(ini, "dsn = https://x#frag;a\n");
+            assert_eq!(from_str_with::<Database>(&ini, &verbatim()).unwrap(), db);
+        }
+
+        #[test]
+        fn test_trailing_comments() {
+            #[derive(Debug, Deserialize, PartialEq)]
+            struct Server {
+                port: u16,
+                host: String,
+                hosts: Vec<String>,
+            }
+
+            let ini =
+                "port = 8080 ; the http port\nhost = local\\#1 # primary\nhosts = a, b ; list\n";
+            let server: Server = from_str(ini).unwrap();
+            assert_eq!(server.port, 8080);
+            assert_eq!(server.host, "local#1");
+            assert_eq!(server.hosts, ["a", "b"]);
+        }
+
+        #[test]
+        fn test_comments_after_whitespace() {
+            #[derive(Debug, Deserialize, PartialEq)]
+            struct Server {
+                port: u16,
+                url: String,
+                dsn: String,
+            }
+
+            let options = DeserializeOptions {
+                inline_comments: InlineComments::AfterWhitespace,
+                ..Default::default()
+            };
+            let ini = "port = 8080 # note\nurl = http://x#y\t;tabbed\ndsn = a;b \\; c\n";
+            let server: Server = from_str_with(ini, &options).unwrap();
+            assert_eq!(server.port, 8080);
+            assert_eq!(server.url, "http://x#y");
+            assert_eq!(server.dsn, "a;b ; c");
+        }
+
+        #[test]
+        fn test_escape_after_whitespace() {
+            #[derive(Debug, Serialize, Deserialize, PartialEq)]
+            struct Links {
+                url: String,
+                note: String,
+                tags: Vec<String>,
+            }
+
+            let links = Links {
+                url: "http://x#y;z".to_string(),
+                note: "#1 ; see #2".to_string(),
+                tags: vec!["#a".to_string(), "b#".to_string()],
+            };
+            let ser_options = SerializeOptions {
+                inline_comments: InlineComments::AfterWhitespace,
+                ..Default::default()
+            };
+            let de_options = DeserializeOptions {
+                inline_comments: InlineComments::AfterWhitespace,
+                ..Default::default()
+            };
+
+            let ini = to_string_with(&links, &ser_options).unwrap();
+            assert_eq!(
+                ini,
+                "url = http://x#y;z\nnote = \\#1 \\; see \\#2\ntags = \\#a, b#\n"
+            );
+            assert_eq!(from_str_with::<Links>(&ini, &de_options).unwrap(), links);
+        }
+
+        #[test]
+        fn test_omit_none() {
+            #[derive(Debug, Serialize, Deserialize, PartialEq)]
+            struct User {
+                name: String,
+                email: Option<String>,
+                address: Address,
+            }
+
+            #[derive(Debug, Serialize, Deserialize, PartialEq)]
+            struct Address {
+                city: Option<String>,
+                zip: Option<u32>,
+            }
+
+            let options = SerializeOptions {
+                option_style: OptionStyle::Omit,
+                ..Default::default()
+            };
+            let user = User {
+                name: "alice".to_string(),
+                email: None,
+                address: Address {
+                    city: None,
+                    zip: Some(12345),
+                },
+            };
+
+            let ini = to_string_with(&user, &options).unwrap();
+            assert_eq!(ini, "name = alice\n[address]\nzip = 12345\n");
+            assert_eq!(user, from_str(&ini).unwrap());
+        }
+
+        #[test]
+        fn test_empty_value_none() {
+            #[derive(Debug, Serialize, Deserialize, PartialEq)]
+            struct Account {
+                user: String,
+                port: Option<u16>,
+                verbose: Option<bool>,
+                timeout: Option<f64>,
+            }
+
+            let account = Account {
+                user: "alice".to_string(),
+                port: None,
+                verbose: Some(true),
+                timeout: None,
+            };
+            let options = SerializeOptions {
+                option_style: OptionStyle::EmptyValue,
+                align: true,
+                ..Default::default()
+            };
+            let ini = to_string_with(&account, &options).unwrap();
+            assert_eq!(
+                ini,
+                "user    = alice\nport    = \nverbose = true\ntimeout = \n"
+            );
+
+            // The empty value is read back as `None` only when asked to
+            let de_options = DeserializeOptions {
+                empty_as_none: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                from_str_with::<Account>(&ini, &de_options).unwrap(),
+                account
+            );
+            assert!(from_str::<Account>(&ini).is_err());
+
+            // A bare flag has no value either, but is still `Some(true)`
+            let de_options = DeserializeOptions {
+                bare_keys: true,
+                ..de_options
+            };
+            let ini = "user = alice\nport =\nverbose\ntimeout = ; unset\n";
+            assert_eq!(from_str_with::<Account>(ini, &de_options).unwrap(), account);
+        }
+
+        #[test]
+        fn test_empty_string_option() {
+            #[derive(Debug, Serialize, Deserialize, PartialEq)]
+            struct Login {
+                password: Option<String>,
+                hint: Option<String>,
+                note: Option<String>,
+            }
+
+            // Without the flag an empty value is an empty string
+            let ini = "password = \nhint = \"\"\n";
+            let login: Login = from_str(ini).unwrap();
+            assert_eq!(login.password.as_deref(), Some(""));
+            assert_eq!(login.hint.as_deref(), Some(""));
+
+            let options = DeserializeOptions {
+                empty_as_none: true,
+                ..Default::default()
+            };
+            let login: Login = from_str_with(ini, &options).unwrap();
+            assert_eq!(login.password, None);
+            assert_eq!(login.hint.as_deref(), Some(""));
+            assert_eq!(login.note, None);
+
+            // Written with empty values, `None` and `Some("")` stay apart
+            let login = Login {
+                password: None,
+                hint: Some(String::new()),
+                note: Some("x".to_string()),
+            };
+            let ser_options = SerializeOptions {
+                option_style: OptionStyle::EmptyValue,
+                ..Default::default()
+            };
+            let ini = to_string_with(&login, &ser_options).unwrap();
+            assert_eq!(ini, "password = \nhint = \"\"\nnote = x\n");
+            assert_eq!(from_str_with::<Login>(&ini, &options).unwrap(), login);
+        }
+
+        #[test]
+        fn test_hash_comment_char() {
+            #[derive(Debug, Serialize, Deserialize, PartialEq)]
+            struct Optional {
+                name: String,
+                port: Option<u16>,
+            }
+
+            let options = SerializeOptions {
+                comment_char: '#',
+                ..Default::default()
+            };
+            let optional = Optional {
+                name: "app".to_string(),
+                port: None,
+            };
+
+            let ini = to_string_with(&optional, &options).unwrap();
+            assert_eq!(ini, "name = app\n# port = \n");
+            assert_eq!(optional, from_str(&ini).unwrap());
+        }
+    }
+
+    mod strict {
+        use super::*;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            server: Server,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Server {
+            port: u16,
+        }
+
+        fn strict() -> DeserializeOptions {
+            DeserializeOptions {
+                deny_unknown_fields: true,
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn test_unknown_fields_ignored_by_default() {
+            let ini = "name = app\nextra = 1\n[server]\nport = 80\nhost = x\n[other]\n";
+            let config: Config = from_str(ini).unwrap();
+            assert_eq!(config.server.port, 80);
+        }
+
+        #[test]
+        fn test_unknown_sections_skipped_whole() {
+            // Unknown sections with subsections and keys of their own, before, between and
+            // within known ones, leave the known fields in place
+            let ini = "name = app\n[before]\nport = 1\n[before.inner]\nport = 2\n\
+                       [server]\nport = 80\n[server.tls]\nport = 443\n[server.tls.deep]\nx = y\n\
+                       [after.a.b]\nname = other\n";
+            let config: Config = from_str(ini).unwrap();
+
+            assert_eq!(
+                config,
+                Config {
+                    name: "app".to_string(),
+                    server: Server { port: 80 },
+                }
+            );
+        }
+
+        #[test]
+        fn test_unknown_subsection() {
+            let ini = "name = app\n[server]\nport = 80\n[server.tls]\nport = 443\n";
+            let err = from_str_with::<Config>(ini, &strict()).unwrap_err();
+            assert_eq!(err.to_string(), "unknown field: tls in section [server]");
+        }
+
+        #[test]
+        fn test_unknown_root_key() {
+            let err =
+                from_str_with::<Config>("name = app\nextra = 1\n[server]\nport = 80", &strict())
+                    .unwrap_err();
+            assert_eq!(err.to_string(), "line 2: unknown field: extra");
+        }
+
+        #[test]
+        fn test_unknown_section_key() {
+            let ini = "name = app\n[server]\nport = 80\nhost = x\n";
+            match from_str_with::<Config>(ini, &strict()) {
+                Err(Error::AtLine {
+                    line: 4, source, ..
+                }) => assert!(matches!(
+                    *source,
+                    Error::UnknownField { key, section } if key == "host" && section == "server"
+                )),
+                other => panic!("expected unknown field error, got {other:?}"),
+            }
+        }
+
+        #[test]
+        fn test_unknown_section() {
+            let ini = "name = app\n[server]\nport = 80\n[other]\nkey = value\n";
+            let err = from_str_with::<Config>(ini, &strict()).unwrap_err();
+            assert_eq!(err.to_string(), "unknown field: other");
+        }
+
+        #[test]
+        fn test_strict_accepts_known_fields() {
+            let ini = "name = app\n[server]\nport = 80\n";
+            let config: Config = from_str_with(ini, &strict()).unwrap();
+            assert_eq!(config.name, "app");
+        }
+    }
+
+    mod dotted {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            server: Server,
+            name: String,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Server {
+            tls: Tls,
+            port: u16,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Tls {
+            cert: Cert,
+            enabled: bool,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Cert {
+            path: String,
+        }
+
+        fn config() -> Config {
+            Config {
+                server: Server {
+                    tls: Tls {
+                        cert: Cert {
+                            path: "/etc/cert.pem".to_string(),
+                        },
+                        enabled: true,
+                    },
+                    port: 443,
+                },
+                name: "app".to_string(),
+            }
+        }
+
+        #[test]
+        fn test_serialize_dotted_sections() {
+            assert_eq!(
+                to_string(&config()).unwrap(),
+                "name = app\n[server]\nport = 443\n[server.tls]\nenabled = true\n\
+                 [server.tls.cert]\npath = /etc/cert.pem\n"
+            );
+        }
+
+        #[test]
+        fn test_dotted_sections_roundtrip() {
+            let ini = to_string(&config()).unwrap();
+            assert_eq!(from_str::<Config>(&ini).unwrap(), config());
+        }
+
+        #[test]
+        fn test_implied_parent_section() {
+            let ini = "name = app\n[server.tls.cert]\npath = /etc/cert.pem\n\
+                       [server.tls]\nenabled = true\n[server]\nport = 443\n";
+            assert_eq!(from_str::<Config>(ini).unwrap(), config());
+        }
+    }
+
+    mod defaults {
+        use super::*;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            #[serde(default = "default_timeout")]
+            timeout: u64,
+            #[serde(default)]
+            server: Server,
+            proxy: Option<Server>,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq, Default)]
+        struct Server {
+            #[serde(default)]
+            port: u16,
+            host: Option<String>,
+        }
+
+        fn default_timeout() -> u64 {
+            30
+        }
+
+        #[test]
+        fn test_default_for_missing_key() {
+            let config: Config = from_str("name = app\n[server]\nport = 80\n").unwrap();
+            assert_eq!(config.timeout, 30);
+            assert_eq!(config.server.port, 80);
+            assert_eq!(config.server.host, None);
+        }
+
+        #[test]
+        fn test_default_for_missing_section() {
+            let config: Config = from_str("name = app\n").unwrap();
+            assert_eq!(config.server, Server::default());
+            assert_eq!(config.proxy, None);
+        }
+
+        #[test]
+        fn test_default_for_empty_section() {
+            let config: Config = from_str("name = app\n[proxy]\n").unwrap();
+            assert_eq!(config.proxy, Some(Server::default()));
+        }
+
+        #[test]
+        fn test_missing_field() {
+            match from_str::<Config>("timeout = 5\n") {
+                Err(Error::MissingField { field, section }) => {
+                    assert_eq!(field, "name");
+                    assert_eq!(section, "");
+                }
+                other => panic!("expected missing field error, got {other:?}"),
+            }
+        }
+
+        #[test]
+        fn test_missing_field_in_section() {
+            #[derive(Debug, Deserialize, PartialEq)]
+            struct Tls {
+                cert: String,
+            }
+
+            #[derive(Debug, Deserialize, PartialEq)]
+            struct Server {
+                tls: Tls,
+            }
+
+            #[derive(Debug, Deserialize, PartialEq)]
+            struct Root {
+                server: Server,
+            }
+
+            let err = from_str::<Root>("[server.tls]\nkey = a.pem\n").unwrap_err();
+            match &err {
+                Error::MissingField { field, section } => {
+                    assert_eq!(field, "cert");
+                    assert_eq!(section, "server.tls");
+                }
+                other => panic!("expected missing field error, got {other:?}"),
+            }
+            assert_eq!(
+                err.to_string(),
+                "missing field: cert in section [server.tls]"
+            );
+        }
+    }
+
+    mod case_insensitive {
+        use super::*;
+        use std::collections::HashMap;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            database: Database,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Database {
+            #[serde(rename = "Host")]
+            host: String,
+            port: u16,
+        }
+
+        const INI: &str = "NAME = app\n[DataBase]\nhost = localhost\nPort = 5432\n";
+
+        fn case_insensitive() -> DeserializeOptions {
+            DeserializeOptions {
+                case_insensitive: true,
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn test_case_insensitive_names() {
+            let config: Config = from_str_with(INI, &case_insensitive()).unwrap();
+            assert_eq!(config.name, "app");
+            assert_eq!(config.database.host, "localhost");
+            assert_eq!(config.database.port, 5432);
+        }
+
+        #[test]
+        fn test_case_sensitive_by_default() {
+            assert!(matches!(
+                from_str::<Config>(INI),
+                Err(Error::MissingField { field, .. }) if field == "name"
+            ));
+        }
+
+        #[test]
+        fn test_case_insensitive_map_keys() {
+            #[derive(Deserialize)]
+            struct Config {
+                env: HashMap<String, String>,
+            }
+
+            let config: Config =
+                from_str_with("[Env]\nHOME = /root\n", &case_insensitive()).unwrap();
+            assert_eq!(config.env["home"], "/root");
+        }
+    }
+
+    mod root_section {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            server: Server,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Server {
+            port: u16,
+        }
+
+        fn config() -> Config {
+            Config {
+                name: "app".to_string(),
+                server: Server { port: 80 },
+            }
+        }
+
+        #[test]
+        fn test_serialize_root_section() {
+            let options = SerializeOptions {
+                root_section: Some("DEFAULT".to_string()),
+                ..Default::default()
+            };
+            assert_eq!(
+                to_string_with(&config(), &options).unwrap(),
+                "[DEFAULT]\nname = app\n[server]\nport = 80\n"
+            );
+        }
+
+        #[test]
+        fn test_deserialize_root_section() {
+            let options = DeserializeOptions {
+                root_section: Some("DEFAULT".to_string()),
+                ..Default::default()
+            };
+            let ini = "[server]\nport = 80\n[DEFAULT]\nname = app\n";
+            assert_eq!(from_str_with::<Config>(ini, &options).unwrap(), config());
+        }
+    }
+
+    mod bool_style {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Flags {
+            enabled: bool,
+            verbose: bool,
+        }
+
+        #[test]
+        fn test_bool_styles_roundtrip() {
+            let flags = Flags {
+                enabled: true,
+                verbose: false,
+            };
+            for (style, expected) in [
+                (BoolStyle::TrueFalse, "enabled = true\nverbose = false\n"),
+                (BoolStyle::YesNo, "enabled = yes\nverbose = no\n"),
+                (BoolStyle::OnOff, "enabled = on\nverbose = off\n"),
+                (BoolStyle::OneZero, "enabled = 1\nverbose = 0\n"),
+            ] {
+                let options = SerializeOptions {
+                    bool_style: style,
+                    ..Default::default()
+                };
+                let ini = to_string_with(&flags, &options).unwrap();
+                assert_eq!(ini, expected);
+                assert_eq!(from_str::<Flags>(&ini).unwrap(), flags);
+            }
+        }
+
+        #[test]
+        fn test_bool_case_insensitive() {
+            let flags: Flags = from_str("enabled = YES\nverbose = Off\n").unwrap();
+            assert!(flags.enabled && !flags.verbose);
+        }
+
+        #[test]
+        fn test_invalid_bool() {
+            let err = from_str::<Flags>("enabled = maybe\nverbose = no\n").unwrap_err();
+            assert_eq!(err.to_string(), "line 1: invalid value for bool: maybe");
+        }
+    }
+
+    mod integers {
+        use super::*;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Limits {
+            count: u32,
+            max_bytes: u64,
+            mode: u16,
+            mask: u8,
+            offset: i32,
+        }
+
+        #[test]
+        fn test_integer_formats() {
+            let ini = "count = 1_000_000\nmax_bytes = 0x1000\nmode = 0o755\nmask = 0b1010\n\
+                       offset = -0x10\n";
+            assert_eq!(
+                from_str::<Limits>(ini).unwrap(),
+                Limits {
+                    count: 1_000_000,
+                    max_bytes: 0x1000,
+                    mode: 0o755,
+                    mask: 0b1010,
+                    offset: -0x10,
+                }
+            );
+        }
+
+        #[test]
+        fn test_invalid_integer_digits() {
+            let ini = "count = 1\nmax_bytes = 0xFG\nmode = 0\nmask = 0\noffset = 0\n";
+            match from_str::<Limits>(ini) {
+                Err(Error::AtLine {
+                    line: 2, source, ..
+                }) => assert!(matches!(
+                    *source,
+                    Error::InvalidValue { typ, value } if typ == "u64" && value == "0xFG"
+                )),
+                other => panic!("expected invalid value error, got {other:?}"),
+            }
+        }
+
+        #[test]
+        fn test_misplaced_underscores() {
+            for value in ["_5", "5_", "5__0", "0x_1", "-_1", "_"] {
+                let ini =
+                    format!("count = {value}\nmax_bytes = 0\nmode = 0\nmask = 0\noffset = 0\n");
+                match from_str::<Limits>(&ini) {
+                    Err(Error::AtLine { source, .. }) => assert!(
+                        matches!(*source, Error::InvalidValue { value: ref v, .. } if v == value),
+                        "{value}: {source:?}"
+                    ),
+                    other => panic!("expected invalid value error for {value}, got {other:?}"),
+                }
+            }
+        }
+
+        #[test]
+        fn test_inferred_integer_formats() {
+            let value: Value = from_str("a = 1_000\nb = 0x10\nc = -0b11\nd = 1__0\n").unwrap();
+            assert_eq!(value.get("a"), Some(&Value::Integer(1000)));
+            assert_eq!(value.get("b"), Some(&Value::Integer(16)));
+            assert_eq!(value.get("c"), Some(&Value::Integer(-3)));
+            assert_eq!(value.get("d"), Some(&Value::String("1__0".to_string())));
+        }
+
+        #[test]
+        fn test_integer_out_of_range() {
+            let ini = "count = 1\nmax_bytes = 1\nmode = 0\nmask = 0x100\noffset = 0\n";
+            let err = from_str::<Limits>(ini).unwrap_err();
+            assert_eq!(err.to_string(), "line 4: invalid value for u8: 0x100");
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Wide {
+            id: u128,
+            delta: i128,
+        }
+
+        #[test]
+        fn test_roundtrip_128_bit_integers() {
+            for wide in [
+                Wide {
+                    id: u128::MAX,
+                    delta: i128::MIN,
+                },
+                Wide {
+                    id: u128::from(u64::MAX) + 1,
+                    delta: i128::MAX,
+                },
+                Wide { id: 0, delta: -1 },
+            ] {
+                let ini = to_string(&wide).unwrap();
+                assert_eq!(ini, format!("id = {}\ndelta = {}\n", wide.id, wide.delta));
+                assert_eq!(from_str::<Wide>(&ini).unwrap(), wide);
+            }
+
+            let wide: Wide = from_str("id = 0xFFFF_FFFF_FFFF_FFFF_FFFF_FFFF_FFFF_FFFF\ndelta = -0x8000_0000_0000_0000_0000_0000_0000_0000").unwrap();
+            assert_eq!(
+                wide,
+                Wide {
+                    id: u128::MAX,
+                    delta: i128::MIN,
+                }
+            );
+        }
+
+        #[test]
+        fn test_128_bit_integer_out_of_range() {
+            let err = from_str::<Wide>("id = 340282366920938463463374607431768211456\ndelta = 0")
+                .unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "line 1: invalid value for u128: 340282366920938463463374607431768211456"
+            );
+
+            let err = from_str::<Wide>("id = -1\ndelta = 0").unwrap_err();
+            assert_eq!(err.to_string(), "line 1: invalid value for u128: -1");
+        }
+    }
+
+    mod floats {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize)]
+        struct Bounds {
+            low: f64,
+            high: f64,
+            missing: f32,
+        }
+
+        #[test]
+        fn test_special_floats_roundtrip() {
+            let bounds = Bounds {
+                low: f64::NEG_INFINITY,
+                high: f64::INFINITY,
+                missing: f32::NAN,
+            };
+            let ini = to_string(&bounds).unwrap();
+            assert_eq!(ini, "low = -inf\nhigh = inf\nmissing = nan\n");
+
+            let parsed: Bounds = from_str(&ini).unwrap();
+            assert_eq!(parsed.low, f64::NEG_INFINITY);
+            assert_eq!(parsed.high, f64::INFINITY);
+            assert!(parsed.missing.is_nan());
+        }
+
+        #[test]
+        fn test_special_float_spellings() {
+            let parsed: Bounds =
+                from_str("low = -Infinity\nhigh = +.INF\nmissing = NaN\n").unwrap();
+            assert_eq!(parsed.low, f64::NEG_INFINITY);
+            assert_eq!(parsed.high, f64::INFINITY);
+            assert!(parsed.missing.is_nan());
+        }
+
+        #[derive(Debug, Serialize, Deserialize)]
+        struct Measurement {
+            small: f64,
+            ratio: f64,
+            half: f32,
+        }
+
+        #[test]
+        fn test_float_fixed_point() {
+            let measurement = Measurement {
+                small: 0.0000001,
+                ratio: 1.23456,
+                half: 1.1,
+            };
+            let ini = to_string(&measurement).unwrap();
+            assert_eq!(ini, "small = 0.0000001\nratio = 1.23456\nhalf = 1.1\n");
+
+            let parsed: Measurement = from_str(&ini).unwrap();
+            assert_eq!(parsed.small, 0.0000001);
+            assert_eq!(parsed.half, 1.1);
+        }
+
+        #[test]
+        fn test_float_decimals() {
+            let measurement = Measurement {
+                small: 0.0000001,
+                ratio: 1.23456,
+                half: 2.5,
+            };
+            let options = SerializeOptions {
+                float_decimals: Some(3),
+                ..Default::default()
+            };
+            let ini = to_string_with(&measurement, &options).unwrap();
+            assert_eq!(ini, "small = 0\nratio = 1.235\nhalf = 2.5\n");
+
+            let parsed: Measurement = from_str(&ini).unwrap();
+            assert!((parsed.ratio - measurement.ratio).abs() < 0.0005);
+        }
+    }
+
+    mod verbatim {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Encoded {
+            path: String,
+            query: String,
+        }
+
+        #[test]
+        fn test_verbatim_roundtrip() {
+            let encoded = Encoded {
+                path: r"C:\new\table".to_string(),
+                query: "a=1%3B2\"x".to_string(),
+            };
+            let ser_options = SerializeOptions {
+                escape: false,
+                inline_comments: InlineComments::Never,
+                ..Default::default()
+            };
+            let de_options = DeserializeOptions {
+                unescape: false,
+                inline_comments: InlineComments::Never,
+                ..Default::default()
+            };
+
+            let ini = to_string_with(&encoded, &ser_options).unwrap();
+            assert_eq!(ini, "path = C:\\new\\table\nquery = a=1%3B2\"x\n");
+            assert_eq!(
+                from_str_with::<Encoded>(&ini, &de_options).unwrap(),
+                encoded
+            );
+        }
+
+        #[test]
+        fn test_verbatim_line_break() {
+            let encoded = Encoded {
+                path: "a\nb".to_string(),
+                query: String::new(),
+            };
+            let options = SerializeOptions {
+                escape: false,
+                ..Default::default()
+            };
+            assert!(matches!(
+                to_string_with(&encoded, &options),
+                Err(Error::UnrepresentableValue { ref value }) if value == "a\nb"
+            ));
+
+            // A carriage return ends the line for readers as well
+            let encoded = Encoded {
+                path: "a\rb".to_string(),
+                query: String::new(),
+            };
+            assert!(matches!(
+                to_string_with(&encoded, &options),
+                Err(Error::UnrepresentableValue { .. })
+            ));
+        }
+
+        #[test]
+        fn test_verbatim_trailing_backslash() {
+            let ser_options = SerializeOptions {
+                escape: false,
+                ..Default::default()
+            };
+            let de_options = DeserializeOptions {
+                unescape: false,
+                ..Default::default()
+            };
+
+            // A single backslash would join the next line to the value
+            let encoded = Encoded {
+                path: r"C:\dir\".to_string(),
+                query: "q".to_string(),
+            };
+            assert!(matches!(
+                to_string_with(&encoded, &ser_options),
+                Err(Error::UnrepresentableValue { ref value }) if value == r"C:\dir\"
+            ));
+
+            // Two of them don't continue the line and round-trip
+            let encoded = Encoded {
+                path: r"C:\dir\\".to_string(),
+                query: r"a\b".to_string(),
+            };
+            let ini = to_string_with(&encoded, &ser_options).unwrap();
+            assert_eq!(ini, "path = C:\\dir\\\\\nquery = a\\b\n");
+            assert_eq!(
+                from_str_with::<Encoded>(&ini, &de_options).unwrap(),
+                encoded
+            );
+        }
+    }
+
+    mod tuples {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Rgb(u8, u8, u8);
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Shape {
+            origin: (f64, f64),
+            address: [u8; 4],
+            color: Rgb,
+        }
+
+        #[test]
+        fn test_tuple_roundtrip() {
+            let shape = Shape {
+                origin: (1.5, -2.0),
+                address: [127, 0, 0, 1],
+                color: Rgb(255, 128, 0),
+            };
+            let ini = to_string(&shape).unwrap();
+            assert_eq!(
+                ini,
+                "origin = 1.5, -2\naddress = 127, 0, 0, 1\ncolor = 255, 128, 0\n"
+            );
+            assert_eq!(from_str::<Shape>(&ini).unwrap(), shape);
+        }
+
+        #[test]
+        fn test_tuple_arity() {
+            let ini = "origin = 1, 2\naddress = 127, 0, 1\ncolor = 0, 0, 0\n";
+            let err = from_str::<Shape>(ini).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "line 2: invalid value for tuple of 4 elements: 127, 0, 1"
+            );
+        }
+    }
+
+    #[cfg(feature = "base64")]
+    mod base64 {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Key {
+            #[serde(with = "serde_bytes")]
+            data: Vec<u8>,
+        }
+
+        #[test]
+        fn test_bytes_roundtrip() {
+            let key = Key {
+                data: vec![0, 159, 146, 150, 255],
+            };
+            let ini = to_string(&key).unwrap();
+            assert_eq!(ini, "data = AJ+Slv8=\n");
+            assert_eq!(from_str::<Key>(&ini).unwrap(), key);
+        }
+
+        #[test]
+        fn test_invalid_base64() {
+            let err = from_str::<Key>("data = not base64!").unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "line 1: invalid value for base64: not base64!"
+            );
+        }
+    }
+
+    mod duplicate_sections {
+        use super::*;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Config {
+            database: Database,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Database {
+            host: String,
+            port: u16,
+        }
+
+        const INI: &str = "[database]\nhost = localhost\n[other]\n[database]\nport = 5432\n";
+
+        #[test]
+        fn test_merge_duplicate_sections() {
+            let config: Config = from_str(INI).unwrap();
+            assert_eq!(config.database.host, "localhost");
+            assert_eq!(config.database.port, 5432);
+        }
+
+        #[test]
+        fn test_duplicate_section_error() {
+            let options = DeserializeOptions {
+                duplicate_sections: DuplicateSections::Error,
+                ..Default::default()
+            };
+            match from_str_with::<Config>(INI, &options) {
+                Err(Error::AtLine {
+                    line: 4, source, ..
+                }) => assert!(matches!(
+                    *source,
+                    Error::DuplicateSection { name } if name == "database"
+                )),
+                other => panic!("expected duplicate section error, got {other:?}"),
+            }
+        }
+    }
+
+    mod document {
+        use super::*;
+
+        const INI: &str = "; generated\nname = app\n\n# the database\n[database]\n\
+                           host = \"local host\"  ; primary\nurl = a\\\n  b\n\n[cache]\nsize = 1\n";
+
+        #[test]
+        fn test_document_roundtrip() {
+            assert_eq!(IniDocument::parse(INI).to_string(), INI);
+        }
+
+        #[test]
+        fn test_document_get() {
+            let doc = IniDocument::parse(INI);
+            assert_eq!(doc.get("", "name").as_deref(), Some("app"));
+            assert_eq!(doc.get("database", "host").as_deref(), Some("local host"));
+            assert_eq!(doc.get("database", "url").as_deref(), Some("ab"));
+            assert_eq!(doc.get("database", "size"), None);
+        }
+
+        #[test]
+        fn test_document_set() {
+            let mut doc = IniDocument::parse(INI);
+            doc.set("database", "host", "db;1").unwrap();
+            doc.set("database", "port", &5432).unwrap();
+            doc.set("", "debug", &true).unwrap();
+            doc.set("logging", "level", "info").unwrap();
+            doc.set("cache", "size", &None::<u32>).unwrap();
+
+            assert_eq!(
+                doc.to_string(),
+                "; generated\nname = app\ndebug = true\n\n# the database\n[database]\n\
+                 host = db;1  ; primary\nurl = a\\\n  b\nport = 5432\n\n[cache]\n\
+                 [logging]\nlevel = info\n"
+            );
+            assert_eq!(doc.get("database", "host").as_deref(), Some("db;1"));
+        }
+
+        #[test]
+        fn test_document_crlf() {
+            let ini = INI.replace('\n', "\r\n");
+            let mut doc = IniDocument::parse(&ini);
+            assert_eq!(doc.to_string(), ini);
+            assert_eq!(doc.get("database", "url").as_deref(), Some("ab"));
+
+            doc.set("cache", "ttl", &60).unwrap();
+            assert!(
+                doc.to_string()
+                    .ends_with("[cache]\r\nsize = 1\r\nttl = 60\r\n")
+            );
+        }
+
+        #[test]
+        fn test_document_header_comment() {
+            let mut doc = IniDocument::parse("a = 1\n[db] ; primary\nport = 5432\n");
+            assert_eq!(doc.get("db", "port").as_deref(), Some("5432"));
+            assert_eq!(doc.get("", "port"), None);
+
+            doc.set("db", "user", "admin").unwrap();
+            assert_eq!(
+                doc.to_string(),
+                "a = 1\n[db] ; primary\nport = 5432\nuser = admin\n"
+            );
+
+            #[derive(Debug, Deserialize, PartialEq)]
+            struct Db {
+                port: u16,
+            }
+
+            #[derive(Debug, Deserialize, PartialEq)]
+            struct Root {
+                db: Db,
+            }
+
+            let root: Root = from_str("[db] # primary\nport = 5432\n").unwrap();
+            assert_eq!(root.db.port, 5432);
+            // Comment characters within the brackets are part of the name
+            let doc = IniDocument::parse("[a;b]\nport = 1\n");
+            assert_eq!(doc.get("a;b", "port").as_deref(), Some("1"));
+        }
+    }
+
+    mod flatten {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            #[serde(flatten)]
+            logging: Logging,
+            server: Server,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Logging {
+            debug: bool,
+            level: u8,
+            ratio: f32,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Server {
+            port: u16,
+            #[serde(flatten)]
+            logging: Logging,
+        }
+
+        #[test]
+        fn test_flatten_roundtrip() {
+            let config = Config {
+                name: "app".to_string(),
+                logging: Logging {
+                    debug: true,
+                    level: 3,
+                    ratio: 0.5,
+                },
+                server: Server {
+                    port: 80,
+                    logging: Logging {
+                        debug: false,
+                        level: 1,
+                        ratio: 1.0,
+                    },
+                },
+            };
+
+            let ini = to_string(&config).unwrap();
+            assert_eq!(
+                ini,
+                "name = app\ndebug = true\nlevel = 3\nratio = 0.5\n\
+                 [server]\nport = 80\ndebug = false\nlevel = 1\nratio = 1\n"
+            );
+            assert_eq!(from_str::<Config>(&ini).unwrap(), config);
+        }
+
+        #[test]
+        fn test_flatten_quoted_string() {
+            #[derive(Debug, Deserialize, PartialEq)]
+            struct Versioned {
+                #[serde(flatten)]
+                version: Version,
+            }
+
+            #[derive(Debug, Deserialize, PartialEq)]
+            struct Version {
+                version: String,
+            }
+
+            let parsed: Versioned = from_str("version = \"2\"").unwrap();
+            assert_eq!(parsed.version.version, "2");
+        }
+
+        #[test]
+        fn test_flatten_string_map() {
+            use std::collections::BTreeMap;
+
+            #[derive(Debug, Deserialize, PartialEq)]
+            struct Plugin {
+                name: String,
+                #[serde(flatten)]
+                extra: BTreeMap<String, String>,
+            }
+
+            let ini = "name = cache
+size = 1
+mode = lru
+";
+
+            // Inferred as an integer, which a map of strings rejects
+            let result = from_str::<Plugin>(ini);
+            assert!(
+                matches!(result, Err(Error::Custom(ref message)) if message.contains("integer `1`")),
+                "{result:?}"
+            );
+
+            let options = DeserializeOptions {
+                infer_types: false,
+                ..Default::default()
+            };
+            let plugin: Plugin = from_str_with(ini, &options).unwrap();
+            assert_eq!(plugin.extra["size"], "1");
+            assert_eq!(plugin.extra["mode"], "lru");
+        }
+    }
+
+    mod root_map {
+        use super::*;
+        use std::collections::HashMap;
+
+        type Sections = HashMap<String, HashMap<String, String>>;
+
+        fn options() -> DeserializeOptions {
+            DeserializeOptions {
+                root_map_key: Some("root".to_string()),
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn test_sections_as_map() {
+            let ini =
+                "name = app\n\n[server]\nhost = localhost\nport = 80\n\n[env]\nHOME = /root\n";
+            let sections: Sections = from_str_with(ini, &options()).unwrap();
+
+            assert_eq!(sections.len(), 3);
+            assert_eq!(sections["root"]["name"], "app");
+            assert_eq!(sections["server"]["host"], "localhost");
+            assert_eq!(sections["server"]["port"], "80");
+            assert_eq!(sections["env"]["HOME"], "/root");
+        }
+
+        #[test]
+        fn test_without_root_keys() {
+            let sections: Sections = from_str_with("[server]\nport = 80\n", &options()).unwrap();
+
+            assert_eq!(sections.len(), 1);
+            assert_eq!(sections["server"]["port"], "80");
+        }
+
+        #[test]
+        fn test_root_entry_named_like_a_section() {
+            let ini = "name = app\n[name]\nfirst = a\n";
+            let sections: Sections = from_str_with(ini, &options()).unwrap();
+
+            assert_eq!(sections["root"]["name"], "app");
+            assert_eq!(sections["name"]["first"], "a");
+        }
+    }
+
+    mod layout {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            server: Server,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Server {
+            port: u16,
+            tls: Tls,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Tls {
+            enabled: bool,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Sections {
+            server: Server,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Optional {
+            name: Option<String>,
+        }
+
+        fn config() -> Config {
+            Config {
+                name: "app".to_string(),
+                server: Server {
+                    port: 80,
+                    tls: Tls { enabled: true },
+                },
+            }
+        }
+
+        #[test]
+        fn test_default_layout() {
+            assert_eq!(
+                to_string(&config()).unwrap(),
+                "name = app\n[server]\nport = 80\n[server.tls]\nenabled = true\n"
+            );
+        }
+
+        #[test]
+        fn test_blank_line_between_sections() {
+            let options = SerializeOptions {
+                blank_line_between_sections: true,
+                ..Default::default()
+            };
+            let ini = to_string_with(&config(), &options).unwrap();
+
+            assert_eq!(
+                ini,
+                "name = app\n\n[server]\nport = 80\n\n[server.tls]\nenabled = true\n"
+            );
+            assert_eq!(from_str::<Config>(&ini).unwrap(), config());
+        }
+
+        #[test]
+        fn test_no_blank_line_before_first_section() {
+            let sections = Sections {
+                server: config().server,
+            };
+            let options = SerializeOptions {
+                blank_line_between_sections: true,
+                ..Default::default()
+            };
+
+            assert_eq!(
+                to_string_with(&sections, &options).unwrap(),
+                "[server]\nport = 80\n\n[server.tls]\nenabled = true\n"
+            );
+        }
+
+        #[test]
+        fn test_without_trailing_newline() {
+            let options = SerializeOptions {
+                trailing_newline: false,
+                blank_line_between_sections: true,
+                ..Default::default()
+            };
+            let ini = to_string_with(&config(), &options).unwrap();
+
+            assert_eq!(
+                ini,
+                "name = app\n\n[server]\nport = 80\n\n[server.tls]\nenabled = true"
+            );
+            assert_eq!(from_str::<Config>(&ini).unwrap(), config());
+        }
+
+        #[test]
+        fn test_empty_document_without_trailing_newline() {
+            #[derive(Serialize)]
+            struct Empty {}
+
+            let options = SerializeOptions {
+                trailing_newline: false,
+                ..Default::default()
+            };
+            assert_eq!(to_string_with(&Empty {}, &options).unwrap(), "");
+        }
+
+        #[test]
+        fn test_crlf_roundtrip() {
+            let options = SerializeOptions {
+                line_ending: LineEnding::Crlf,
+                blank_line_between_sections: true,
+                ..Default::default()
+            };
+            let ini = to_string_with(&config(), &options).unwrap();
+
+            assert_eq!(
+                ini,
+                "name = app\r\n\r\n[server]\r\nport = 80\r\n\r\n[server.tls]\r\nenabled = true\r\n"
+            );
+            assert_eq!(from_str::<Config>(&ini).unwrap(), config());
+            assert_eq!(from_reader::<_, Config>(ini.as_bytes()).unwrap(), config());
+        }
+
+        #[test]
+        fn test_crlf_without_trailing_newline() {
+            let options = SerializeOptions {
+                line_ending: LineEnding::Crlf,
+                trailing_newline: false,
+                ..Default::default()
+            };
+            let optional = Optional { name: None };
+
+            assert_eq!(to_string_with(&optional, &options).unwrap(), "; name = ");
+        }
+    }
+
+    mod bare_keys {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Flags {
+            verbose: bool,
+            quiet: bool,
+            #[serde(default)]
+            color: bool,
+            label: String,
+        }
+
+        fn bare_keys() -> DeserializeOptions {
+            DeserializeOptions {
+                bare_keys: true,
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn test_bare_keys() {
+            let ini = "verbose\nquiet = false\nlabel ; no value\n";
+            let flags: Flags = from_str_with(ini, &bare_keys()).unwrap();
+
+            assert_eq!(
+                flags,
+                Flags {
+                    verbose: true,
+                    quiet: false,
+                    color: false,
+                    label: String::new(),
+                }
+            );
+        }
+
+        #[test]
+        fn test_bare_keys_ignored_by_default() {
+            let result = from_str::<Flags>("verbose\nquiet = false\nlabel = x\n");
+            assert!(matches!(
+                result,
+                Err(Error::MissingField { field, .. }) if field == "verbose"
+            ));
+        }
+
+        #[test]
+        fn test_bare_flags_roundtrip() {
+            let flags = Flags {
+                verbose: true,
+                quiet: false,
+                color: true,
+                label: "x".to_string(),
+            };
+            let options = SerializeOptions {
+                bare_flags: true,
+                align: true,
+                ..Default::default()
+            };
+            let ini = to_string_with(&flags, &options).unwrap();
+
+            assert_eq!(ini, "verbose\nquiet = false\ncolor\nlabel = x\n");
+            assert_eq!(from_str_with::<Flags>(&ini, &bare_keys()).unwrap(), flags);
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Server {
+            debug: bool,
+            server: Tls,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Tls {
+            tls: bool,
+            verify: bool,
+        }
+
+        fn flag_fields() -> DeserializeOptions {
+            DeserializeOptions {
+                flag_fields: Some(vec!["debug".to_string(), "server.tls".to_string()]),
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn test_flag_fields() {
+            // Listed fields may be bare without `bare_keys`, or have a value
+            for ini in [
+                "debug\n[server]\ntls\nverify = true\n",
+                "debug = true\n[server]\ntls = true\nverify = true\n",
+            ] {
+                let server: Server = from_str_with(ini, &flag_fields()).unwrap();
+                assert!(server.debug && server.server.tls && server.server.verify);
+            }
+        }
+
+        #[test]
+        fn test_bare_key_not_a_flag_field() {
+            let ini = "debug\n[server]\ntls\nverify\n";
+            let err = from_str_with::<Server>(ini, &flag_fields()).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "line 4: missing value: verify in section [server]"
+            );
+            assert_eq!(
+                err.span(),
+                Some(ini.rfind("verify").unwrap()..ini.len() - 1)
+            );
+
+            // A flag of one section isn't a flag of another
+            let options = DeserializeOptions {
+                flag_fields: Some(vec!["tls".to_string()]),
+                ..Default::default()
+            };
+            let err =
+                from_str_with::<Server>("debug = false\n[server]\ntls\nverify = false\n", &options)
+                    .unwrap_err();
+            assert!(matches!(err, Error::AtLine { line: 3, source, .. }
+                if matches!(*source, Error::MissingValue { ref key, ref section } if key == "tls" && section == "server")));
+        }
+
+        #[test]
+        fn test_flag_fields_case_insensitive() {
+            let options = DeserializeOptions {
+                case_insensitive: true,
+                ..flag_fields()
+            };
+            let server: Server =
+                from_str_with("DEBUG\n[Server]\nTLS\nverify = no\n", &options).unwrap();
+            assert!(server.debug && server.server.tls && !server.server.verify);
+        }
+    }
+
+    mod optional_sections {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            cache: Option<CacheSettings>,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct CacheSettings {
+            size: u32,
+            redis: Option<Redis>,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Redis {
+            url: String,
+        }
+
+        #[test]
+        fn test_absent_section_is_none() {
+            let config: Config = from_str("name = app\n").unwrap();
+            assert_eq!(config.cache, None);
+        }
+
+        #[test]
+        fn test_present_section_is_some() {
+            let config: Config = from_str("name = app\n[cache]\nsize = 64\n").unwrap();
+            assert_eq!(
+                config.cache,
+                Some(CacheSettings {
+                    size: 64,
+                    redis: None,
+                })
+            );
+        }
+
+        #[test]
+        fn test_nested_optional_section_roundtrip() {
+            let config = Config {
+                name: "app".to_string(),
+                cache: Some(CacheSettings {
+                    size: 64,
+                    redis: Some(Redis {
+                        url: "redis://localhost".to_string(),
+                    }),
+                }),
+            };
+            let ini = to_string(&config).unwrap();
+            assert_eq!(from_str::<Config>(&ini).unwrap(), config);
+        }
+
+        #[test]
+        fn test_present_section_missing_field() {
+            let result = from_str::<Config>("name = app\n[cache]\n");
+            assert!(matches!(
+                result,
+                Err(Error::MissingField { field, section }) if field == "size" && section == "cache"
+            ));
+        }
+    }
+
+    #[cfg(feature = "interpolate")]
+    mod interpolate {
+        use super::*;
+        use std::collections::HashMap;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Database {
+            url: String,
+            password: String,
+            hosts: Vec<String>,
+        }
+
+        fn variables() -> DeserializeOptions {
+            let map = HashMap::from([
+                ("HOST".to_string(), "db.local".to_string()),
+                ("DB_PASSWORD".to_string(), "secret".to_string()),
+                ("EMPTY".to_string(), String::new()),
+            ]);
+            DeserializeOptions {
+                variables: Some(Variables::Map(map)),
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn test_interpolate_variables() {
+            let ini = "url = postgres://${HOST}:${PORT:-5432}/app\n\
+                       password = ${DB_PASSWORD}\n\
+                       hosts = ${HOST}, ${EMPTY:-backup.local}\n";
+            let db: Database = from_str_with(ini, &variables()).unwrap();
+
+            assert_eq!(db.url, "postgres://db.local:5432/app");
+            assert_eq!(db.password, "secret");
+            assert_eq!(db.hosts, vec!["db.local", "backup.local"]);
+        }
+
+        #[test]
+        fn test_literal_dollar() {
+            let ini = "url = $$HOME\npassword = 5$ or $$5\nhosts = \n";
+            let db: Database = from_str_with(ini, &variables()).unwrap();
+
+            assert_eq!(db.url, "$HOME");
+            assert_eq!(db.password, "5$ or $5");
+        }
+
+        #[test]
+        fn test_undefined_variable() {
+            let ini = "url = x\npassword = ${MISSING}\nhosts = \n";
+            let err = from_str_with::<Database>(ini, &variables()).unwrap_err();
+            assert_eq!(err.to_string(), "line 2: undefined variable: ${MISSING}");
+        }
+
+        #[test]
+        fn test_unterminated_reference() {
+            let err = from_str_with::<Database>("url = ${HOST", &variables()).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "line 1: invalid value for variable reference: ${HOST"
+            );
+        }
+
+        #[test]
+        fn test_not_interpolated_by_default() {
+            let ini = "url = ${HOST}\npassword = $$\nhosts = \n";
+            let db: Database = from_str(ini).unwrap();
+
+            assert_eq!(db.url, "${HOST}");
+            assert_eq!(db.password, "$$");
+        }
+
+        #[test]
+        fn test_interpolate_env() {
+            let options = DeserializeOptions {
+                variables: Some(Variables::Env),
+                ..Default::default()
+            };
+            let path = std::env::var("PATH").unwrap_or_default();
+            let raw = parse_raw_with("path = ${PATH:-}\n", &options).unwrap();
+
+            assert_eq!(raw[0].1, vec![("path".to_string(), path)]);
+        }
+    }
+
+    mod layered {
+        use super::*;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            tags: Vec<String>,
+            server: Server,
+            cache: Option<Cache>,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Server {
+            host: String,
+            port: u16,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Cache {
+            size: u32,
+        }
+
+        const BASE: &str = "name = app\ntags = a\ntags = b\n[server]\nhost = 0.0.0.0\nport = 80\n";
+
+        #[test]
+        fn test_later_sources_override() {
+            let local = "tags = c\n[server]\nport = 8080\n[cache]\nsize = 64\n";
+            let config: Config = from_strs(&[BASE, local]).unwrap();
+
+            assert_eq!(
+                config,
+                Config {
+                    name: "app".to_string(),
+                    tags: vec!["c".to_string()],
+                    server: Server {
+                        host: "0.0.0.0".to_string(),
+                        port: 8080,
+                    },
+                    cache: Some(Cache { size: 64 }),
+                }
+            );
+        }
+
+        #[test]
+        fn test_single_source() {
+            let layered: Config = from_strs(&[BASE]).unwrap();
+            let single: Config = from_str(BASE).unwrap();
+            assert_eq!(layered, single);
+        }
+
+        #[test]
+        fn test_duplicate_keys_across_sources() {
+            let options = DeserializeOptions {
+                duplicate_keys: DuplicateKeys::Error,
+                ..Default::default()
+            };
+            let base = "name = app\ntags = a\n[server]\nhost = 0.0.0.0\nport = 80\n";
+            let config: Config = from_strs_with(&[base, "name = other\n"], &options).unwrap();
+            assert_eq!(config.name, "other");
+        }
+
+        #[test]
+        fn test_error_in_later_source() {
+            let err = from_strs::<Config>(&[BASE, "\n[server]\nport = high\n"]).unwrap_err();
+            assert_eq!(err.to_string(), "line 3: invalid value for u16: high");
+        }
+    }
+
+    mod borrowed {
+        use super::*;
+        use std::borrow::Cow;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Config<'a> {
+            name: &'a str,
+            #[serde(borrow)]
+            motd: Cow<'a, str>,
+            server: Server<'a>,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Server<'a> {
+            host: &'a str,
+        }
+
+        #[test]
+        fn test_borrow_str() {
+            let ini = "name = app\nmotd = \"  hello  \"\n[server]\nhost = localhost ; comment\n";
+            let config: Config = from_str(ini).unwrap();
+
+            assert_eq!(config.name, "app");
+            assert_eq!(config.server.host, "localhost");
+            assert_eq!(config.motd, "  hello  ");
+            assert!(matches!(config.motd, Cow::Borrowed(_)));
+        }
+
+        #[test]
+        fn test_cow_owned_when_unescaped() {
+            let ini = "name = app\nmotd = line\\none\n[server]\nhost = localhost\n";
+            let config: Config = from_str(ini).unwrap();
+
+            assert_eq!(config.motd, "line\none");
+            assert!(matches!(config.motd, Cow::Owned(_)));
+        }
+
+        #[test]
+        fn test_borrow_str_needing_unescape() {
+            let ini = "name = a\\;b\nmotd = hi\n[server]\nhost = localhost\n";
+            let err = from_str::<Config>(ini).unwrap_err();
+            assert!(
+                err.to_string().contains("expected a borrowed string"),
+                "{err}"
+            );
+        }
+
+        #[test]
+        fn test_borrow_continued_value() {
+            let ini = "name = app\nmotd = hello \\\n  world\n[server]\nhost = localhost\n";
+            let config: Config = from_str(ini).unwrap();
+
+            assert_eq!(config.motd, "hello world");
+            assert!(matches!(config.motd, Cow::Owned(_)));
+        }
+    }
+
+    mod renamed {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        #[serde(rename = "app", rename_all = "kebab-case")]
+        struct Config {
+            app_name: String,
+            max_connections: u32,
+            app: AppSettings,
+            tls_settings: Option<TlsSettings>,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        #[serde(rename_all = "kebab-case")]
+        struct AppSettings {
+            log_level: String,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        #[serde(rename = "tls", rename_all = "kebab-case")]
+        struct TlsSettings {
+            cert_path: String,
+        }
+
+        fn config() -> Config {
+            Config {
+                app_name: "demo".to_string(),
+                max_connections: 10,
+                app: AppSettings {
+                    log_level: "debug".to_string(),
+                },
+                tls_settings: Some(TlsSettings {
+                    cert_path: "cert.pem".to_string(),
+                }),
+            }
+        }
+
+        #[test]
+        fn test_deserialize_rename_all() {
+            let ini = "app-name = demo\nmax-connections = 10\n[app]\nlog-level = debug\n[tls-settings]\ncert-path = cert.pem\n";
+            assert_eq!(from_str::<Config>(ini).unwrap(), config());
+        }
+
+        #[test]
+        fn test_round_trip_rename_all() {
+            // The root struct is renamed like one of its sections, which must not be read instead
+            let ini = to_string(&config()).unwrap();
+            assert!(ini.contains("[tls-settings]\ncert-path = cert.pem"));
+            assert_eq!(from_str::<Config>(&ini).unwrap(), config());
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        #[serde(rename_all = "kebab-case")]
+        struct Service {
+            service_name: String,
+            http_server: HttpServer,
+            #[serde(rename = "backend")]
+            upstream_pool: Pool,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+        struct HttpServer {
+            max_body_size: u32,
+            tls_config: TlsConfig,
+        }
+
+        // Sections are named after the field holding the struct, not after the struct
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        #[serde(rename = "Tls", rename_all = "camelCase")]
+        struct TlsConfig {
+            cert_path: String,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        #[serde(rename_all = "PascalCase")]
+        struct Pool {
+            idle_timeout: u32,
+        }
+
+        #[test]
+        fn test_nested_rename_styles() {
+            let service = Service {
+                service_name: "api".to_string(),
+                http_server: HttpServer {
+                    max_body_size: 1024,
+                    tls_config: TlsConfig {
+                        cert_path: "cert.pem".to_string(),
+                    },
+                },
+                upstream_pool: Pool { idle_timeout: 30 },
+            };
+            let ini = "service-name = api\n\
+                       [http-server]\nMAX_BODY_SIZE = 1024\n\
+                       [http-server.TLS_CONFIG]\ncertPath = cert.pem\n\
+                       [backend]\nIdleTimeout = 30\n";
+
+            assert_eq!(to_string(&service).unwrap(), ini);
+            assert_eq!(from_str::<Service>(ini).unwrap(), service);
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        #[serde(rename = "server")]
+        struct Root {
+            name: String,
+            server: Server,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        #[serde(rename = "Server")]
+        struct Server {
+            host: String,
+        }
+
+        #[test]
+        fn test_single_section_file() {
+            let root = Root {
+                name: "app".to_string(),
+                server: Server {
+                    host: "localhost".to_string(),
+                },
+            };
+
+            // The root struct is named like the only section, which is still read as its field
+            let ini = "name = app\n[server]\nhost = localhost\n";
+            assert_eq!(from_str::<Root>(ini).unwrap(), root);
+            let ini = "name = app\n[server]\nhost = localhost\n[other]\nkey = 1\n";
+            assert_eq!(from_str::<Root>(ini).unwrap(), root);
+        }
+
+        #[test]
+        fn test_root_named_like_section() {
+            // Without any of its fields at the root, a struct reads the section named after it,
+            // however many sections follow
+            for ini in [
+                "[Server]\nhost = localhost\n",
+                "[Server]\nhost = localhost\n[other]\nkey = 1\n",
+                "[other]\nkey = 1\n[Server]\nhost = localhost\n",
+            ] {
+                assert_eq!(from_str::<Server>(ini).unwrap().host, "localhost");
+            }
+
+            // The root keys take precedence
+            let ini = "host = localhost\n[Server]\nhost = remote\n";
+            assert_eq!(from_str::<Server>(ini).unwrap().host, "localhost");
+
+            // Other sections don't stand in for the root
+            match from_str::<Server>("[other]\nhost = localhost\n") {
+                Err(Error::MissingField { field, section }) => {
+                    assert_eq!((field.as_str(), section.as_str()), ("host", ""));
+                }
+                other => panic!("expected missing field error, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_root_section_named_after_struct() {
+            #[derive(Debug, Deserialize, PartialEq)]
+            struct Config {
+                name: String,
+            }
+
+            #[derive(Debug, Deserialize, PartialEq)]
+            #[serde(rename = "general")]
+            struct General {
+                name: String,
+                db: Option<Db>,
+            }
+
+            #[derive(Debug, Deserialize, PartialEq)]
+            struct Db {
+                port: u16,
+            }
+
+            let config: Config = from_str("[Config]\nname = x\n").unwrap();
+            assert_eq!(config.name, "x");
+
+            let general: General = from_str("[general]\nname = x\n").unwrap();
+            assert_eq!(general.name, "x");
+            assert_eq!(general.db, None);
+
+            // Its subsections are the struct's sections
+            let general: General =
+                from_str("[general]\nname = x\n[general.db]\nport = 1\n").unwrap();
+            assert_eq!(general.db, Some(Db { port: 1 }));
+
+            // The section is matched like any other
+            let options = DeserializeOptions {
+                case_insensitive: true,
+                ..Default::default()
+            };
+            let config: Config = from_str_with("[CONFIG]\nName = x\n", &options).unwrap();
+            assert_eq!(config.name, "x");
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        #[serde(deny_unknown_fields)]
+        struct Aliased {
+            #[serde(alias = "max_conns")]
+            max_connections: u32,
+            #[serde(alias = "db")]
+            database: Database,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Database {
+            #[serde(alias = "hostname", alias = "server")]
+            host: String,
+        }
+
+        fn aliased() -> Aliased {
+            Aliased {
+                max_connections: 10,
+                database: Database {
+                    host: "localhost".to_string(),
+                },
+            }
+        }
+
+        #[test]
+        fn test_deserialize_alias() {
+            let ini = "max_conns = 10\n[db]\nserver = localhost\n";
+            assert_eq!(from_str::<Aliased>(ini).unwrap(), aliased());
+
+            let ini = "max_connections = 10\n[database]\nhostname = localhost\n";
+            assert_eq!(from_str::<Aliased>(ini).unwrap(), aliased());
+        }
+
+        #[test]
+        fn test_deserialize_alias_case_insensitive() {
+            let options = DeserializeOptions {
+                case_insensitive: true,
+                ..Default::default()
+            };
+            let ini = "MAX_CONNS = 10\n[DB]\nHostName = localhost\n";
+            assert_eq!(from_str_with::<Aliased>(ini, &options).unwrap(), aliased());
+        }
+
+        #[test]
+        fn test_alias_and_name_both_set() {
+            let ini = "max_conns = 10\nmax_connections = 20\n[db]\nhost = localhost\n";
+            assert!(matches!(
+                from_str::<Aliased>(ini),
+                Err(Error::Custom(message)) if message == "duplicate field `max_connections`"
+            ));
+        }
+    }
+
+    mod in_section {
+        use super::*;
+
+        #[derive(Serialize)]
+        struct Plugin {
+            enabled: bool,
+            path: String,
+            limits: Limits,
+        }
+
+        #[derive(Serialize)]
+        struct Limits {
+            memory: u32,
+        }
+
+        #[test]
+        fn test_serialize_in_section() {
+            let plugin = Plugin {
+                enabled: true,
+                path: "/usr/lib/foo.so".to_string(),
+                limits: Limits { memory: 64 },
+            };
+
+            assert_eq!(
+                to_string_in_section("plugin.foo", &plugin).unwrap(),
+                "[plugin.foo]\nenabled = true\npath = /usr/lib/foo.so\n[plugin.foo.limits]\nmemory = 64\n"
+            );
+        }
+
+        #[test]
+        fn test_concatenated_sections() {
+            #[derive(Debug, Deserialize, PartialEq)]
+            struct Config {
+                name: String,
+                server: Server,
+            }
+
+            #[derive(Debug, Serialize, Deserialize, PartialEq)]
+            struct Server {
+                port: u16,
+            }
+
+            let options = SerializeOptions {
+                align: true,
+                ..Default::default()
+            };
+            let ini = "name = app\n".to_string()
+                + &to_string_in_section_with("server", &Server { port: 80 }, &options).unwrap();
+
+            assert_eq!(
+                from_str::<Config>(&ini).unwrap(),
+                Config {
+                    name: "app".to_string(),
+                    server: Server { port: 80 },
+                }
+            );
+        }
+
+        #[test]
+        fn test_serialize_scalar_in_section() {
+            assert!(to_string_in_section("name", &1).is_err());
+        }
+    }
+
+    mod enum_sections {
+        use super::*;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        enum Plugin {
+            Http { host: String, port: u16 },
+            Script(Script),
+            Disabled,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Script {
+            path: String,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            plugin: Plugin,
+        }
+
+        #[test]
+        fn test_deserialize_struct_variant() {
+            let ini = "name = app\n[plugin]\ntype = Http\nhost = localhost\nport = 80\n";
+            assert_eq!(
+                from_str::<Config>(ini).unwrap().plugin,
+                Plugin::Http {
+                    host: "localhost".to_string(),
+                    port: 80,
+                }
+            );
+        }
+
+        #[test]
+        fn test_deserialize_newtype_and_unit_variants() {
+            let ini = "name = app\n[plugin]\ntype = Script\npath = run.sh\n";
+            assert_eq!(
+                from_str::<Config>(ini).unwrap().plugin,
+                Plugin::Script(Script {
+                    path: "run.sh".to_string(),
+                })
+            );
+
+            let ini = "name = app\n[plugin]\ntype = Disabled\n";
+            assert_eq!(from_str::<Config>(ini).unwrap().plugin, Plugin::Disabled);
+        }
+
+        #[test]
+        fn test_deserialize_root_enum() {
+            let plugin: Plugin = from_str("type = Http\nhost = localhost\nport = 80\n").unwrap();
+            assert_eq!(
+                plugin,
+                Plugin::Http {
+                    host: "localhost".to_string(),
+                    port: 80,
+                }
+            );
+        }
+
+        #[test]
+        fn test_custom_enum_tag() {
+            let options = DeserializeOptions {
+                enum_tag: "kind".to_string(),
+                ..Default::default()
+            };
+            let ini = "name = app\n[plugin]\nkind = Disabled\n";
+            assert_eq!(
+                from_str_with::<Config>(ini, &options).unwrap().plugin,
+                Plugin::Disabled
+            );
+        }
+
+        #[test]
+        fn test_missing_and_unknown_variant() {
+            let result = from_str::<Config>("name = app\n[plugin]\nhost = localhost\n");
+            assert!(
+                matches!(result, Err(Error::MissingField { ref field, ref section })
+                if field == "type" && section == "plugin")
+            );
+
+            let result = from_str::<Config>("name = app\n[plugin]\ntype = Ftp\n");
+            assert!(matches!(result, Err(Error::AtLine { line: 3, source, .. })
+                if matches!(*source, Error::Custom(ref message) if message.contains("Ftp"))));
+        }
+    }
+
+    mod skipped {
+        use super::*;
+
+        #[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+        struct Cache {
+            size: u32,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            #[serde(skip)]
+            token: String,
+            #[serde(skip)]
+            cache: Cache,
+            server: Server,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Server {
+            port: u16,
+            #[serde(skip)]
+            retries: u32,
+        }
+
+        #[test]
+        fn test_roundtrip_skipped_fields() {
+            let config = Config {
+                name: "app".to_string(),
+                token: "secret".to_string(),
+                cache: Cache { size: 64 },
+                server: Server {
+                    port: 80,
+                    retries: 3,
+                },
+            };
+
+            let ini = to_string(&config).unwrap();
+            assert_eq!(ini, "name = app\n[server]\nport = 80\n");
+
+            // Skipped fields take their default
+            assert_eq!(
+                from_str::<Config>(&ini).unwrap(),
+                Config {
+                    name: "app".to_string(),
+                    token: String::new(),
+                    cache: Cache::default(),
+                    server: Server {
+                        port: 80,
+                        retries: 0,
+                    },
+                }
+            );
+        }
+
+        #[test]
+        fn test_deserialize_skipped_fields_present() {
+            // Keys and sections named like a skipped field are ignored
+            let ini = "name = app\ntoken = secret\n[cache]\nsize = 64\n[server]\nport = 80\nretries = 3\n";
+            let config: Config = from_str(ini).unwrap();
+            assert_eq!(config.token, "");
+            assert_eq!(config.cache, Cache::default());
+            assert_eq!(config.server.retries, 0);
+
+            // Unless unknown fields are rejected, as they don't match any field
+            let options = DeserializeOptions {
+                deny_unknown_fields: true,
+                ..Default::default()
+            };
+            assert!(matches!(
+                from_str_with::<Config>(ini, &options),
+                Err(Error::AtLine { line: 2, source, .. })
+                    if matches!(*source, Error::UnknownField { ref key, .. } if key == "token")
+            ));
+        }
+    }
+
+    mod value {
+        use super::*;
+
+        fn section(entries: &[(&str, Value)]) -> Value {
+            Value::Section(
+                entries
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.clone()))
+                    .collect(),
+            )
+        }
+
+        fn string(value: &str) -> Value {
+            Value::String(value.to_string())
+        }
+
+        const INI: &str = "name = app\n[server]\nhost = localhost\nport = 8080\n[server.tls]\nenabled = true\nratio = 0.5\n";
+
+        #[test]
+        fn test_deserialize_value() {
+            let value: Value = from_str(INI).unwrap();
+            assert_eq!(
+                value,
+                section(&[
+                    ("name", string("app")),
+                    (
+                        "server",
+                        section(&[
+                            ("host", string("localhost")),
+                            ("port", Value::Integer(8080)),
+                            (
+                                "tls",
+                                section(&[
+                                    ("enabled", Value::Bool(true)),
+                                    ("ratio", Value::Float(0.5))
+                                ])
+                            ),
+                        ])
+                    ),
+                ])
+            );
+            assert_eq!(
+                value.get("server").and_then(|server| server.get("host")),
+                Some(&string("localhost"))
+            );
+            assert_eq!(value.get("missing"), None);
+        }
+
+        #[test]
+        fn test_deserialize_value_as_strings() {
+            let options = DeserializeOptions {
+                infer_types: false,
+                ..Default::default()
+            };
+            let value: Value = from_str_with(INI, &options).unwrap();
+            let server = value.get("server").unwrap();
+            assert_eq!(server.get("port").and_then(Value::as_str), Some("8080"));
+
+            let tls = server.get("tls").unwrap();
+            assert_eq!(tls.get("enabled").and_then(Value::as_str), Some("true"));
+            assert_eq!(tls.get("ratio").and_then(Value::as_str), Some("0.5"));
+        }
+
+        #[test]
+        fn test_untyped_fields() {
+            #[derive(Debug, Deserialize)]
+            struct Plugin {
+                name: String,
+                level: Value,
+                label: Value,
+                settings: Value,
+            }
+
+            let ini = "name = 42\nlevel = 3\nlabel = \"true\"\n\
+                       [settings]\nenabled = true\nratio = 0.5\nmode = fast\n";
+
+            let options = DeserializeOptions {
+                infer_types: false,
+                ..Default::default()
+            };
+            let plugin: Plugin = from_str_with(ini, &options).unwrap();
+            assert_eq!(plugin.level, string("3"));
+            assert_eq!(plugin.settings.get("enabled"), Some(&string("true")));
+
+            let plugin: Plugin = from_str(ini).unwrap();
+            // Typed fields are read as their type regardless
+            assert_eq!(plugin.name, "42");
+            assert_eq!(plugin.level, Value::Integer(3));
+            assert_eq!(plugin.label, string("true"));
+            assert_eq!(
+                plugin.settings,
+                section(&[
+                    ("enabled", Value::Bool(true)),
+                    ("ratio", Value::Float(0.5)),
+                    ("mode", string("fast")),
+                ])
+            );
+        }
+
+        #[test]
+        fn test_serialize_value() {
+            let value = section(&[
+                ("name", string("app")),
+                ("server", section(&[("port", Value::Integer(80))])),
+                ("debug", Value::Bool(false)),
+            ]);
+            assert_eq!(
+                to_string(&value).unwrap(),
+                "name = app\ndebug = false\n[server]\nport = 80\n"
+            );
+        }
+
+        #[test]
+        fn test_convert_value() {
+            #[derive(Debug, Serialize, Deserialize, PartialEq)]
+            struct Config {
+                name: String,
+                server: Server,
+            }
+
+            #[derive(Debug, Serialize, Deserialize, PartialEq)]
+            struct Server {
+                host: String,
+                port: u16,
+            }
+
+            let config = Config {
+                name: "app".to_string(),
+                server: Server {
+                    host: "localhost".to_string(),
+                    port: 8080,
+                },
+            };
+            let value = to_value(&config).unwrap();
+            assert_eq!(
+                value.get("server").and_then(|server| server.get("port")),
+                Some(&Value::Integer(8080))
+            );
+            assert_eq!(from_value::<Config>(value).unwrap(), config);
+
+            // Typed values are read back by their INI spelling
+            let value = section(&[
+                ("name", string("app")),
+                (
+                    "server",
+                    section(&[
+                        ("host", string("localhost")),
+                        ("port", Value::Integer(8080)),
+                    ]),
+                ),
+            ]);
+            assert_eq!(from_value::<Config>(value).unwrap(), config);
+
+            assert!(matches!(to_value(&1), Err(Error::UnsupportedRoot { .. })));
+        }
+
+        #[test]
+        fn test_convert_value_keeps_strings() {
+            use std::collections::BTreeMap;
+
+            #[derive(Debug, Serialize, Deserialize, PartialEq)]
+            struct Order {
+                zip: String,
+                price: String,
+                paid: String,
+                quantity: u32,
+                tags: Vec<String>,
+                note: Option<String>,
+            }
+
+            let order = Order {
+                zip: "007".to_string(),
+                price: "1.50".to_string(),
+                paid: "true".to_string(),
+                quantity: 3,
+                tags: vec!["01".to_string(), "a;b".to_string()],
+                note: None,
+            };
+            let value = to_value(&order).unwrap();
+            assert_eq!(
+                value,
+                section(&[
+                    ("zip", string("007")),
+                    ("price", string("1.50")),
+                    ("paid", string("true")),
+                    ("quantity", Value::Integer(3)),
+                    ("tags", string("01, a;b")),
+                ])
+            );
+            assert_eq!(from_value::<Order>(value.clone()).unwrap(), order);
+
+            // Untyped fields keep them as strings as well
+            let untyped: BTreeMap<String, Value> = from_value(value).unwrap();
+            assert_eq!(untyped["zip"], string("007"));
+            assert_eq!(untyped["price"], string("1.50"));
+        }
+    }
+
+    mod section_order {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            server: Server,
+            database: Port,
+            name: String,
+            general: General,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Server {
+            tls: Port,
+            admin: Port,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Port {
+            port: u16,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct General {
+            debug: bool,
+        }
+
+        fn config() -> Config {
+            Config {
+                server: Server {
+                    tls: Port { port: 443 },
+                    admin: Port { port: 8080 },
+                },
+                database: Port { port: 5432 },
+                name: "app".to_string(),
+                general: General { debug: false },
+            }
+        }
+
+        // Section headers in the order they were written
+        fn headers(ini: &str) -> Vec<&str> {
+            ini.lines().filter(|line| line.starts_with('[')).collect()
+        }
+
+        #[test]
+        fn test_declaration_order() {
+            let ini = to_string(&config()).unwrap();
+            assert!(ini.starts_with("name = app\n"));
+            assert_eq!(
+                headers(&ini),
+                [
+                    "[server]",
+                    "[server.tls]",
+                    "[server.admin]",
+                    "[database]",
+                    "[general]"
+                ]
+            );
+        }
+
+        #[test]
+        fn test_alphabetical_order() {
+            let options = SerializeOptions {
+                section_order: SectionOrder::Alphabetical,
+                ..Default::default()
+            };
+            let ini = to_string_with(&config(), &options).unwrap();
+            assert!(ini.starts_with("name = app\n"));
+            assert_eq!(
+                headers(&ini),
+                [
+                    "[database]",
+                    "[general]",
+                    "[server]",
+                    "[server.admin]",
+                    "[server.tls]"
+                ]
+            );
+            assert_eq!(from_str::<Config>(&ini).unwrap(), config());
+        }
+
+        #[test]
+        fn test_explicit_order() {
+            let options = SerializeOptions {
+                section_order: SectionOrder::Explicit(vec![
+                    "general".to_string(),
+                    "server.admin".to_string(),
+                ]),
+                ..Default::default()
+            };
+            let ini = to_string_with(&config(), &options).unwrap();
+            assert!(ini.starts_with("name = app\n[general]\ndebug = false\n"));
+            assert_eq!(
+                headers(&ini),
+                [
+                    "[general]",
+                    "[server]",
+                    "[server.admin]",
+                    "[server.tls]",
+                    "[database]"
+                ]
+            );
+            assert_eq!(from_str::<Config>(&ini).unwrap(), config());
+        }
+    }
+
+    mod repeated_sections {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            servers: Vec<Server>,
+            tags: Vec<String>,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Server {
+            host: String,
+            port: u16,
+            backup: Option<bool>,
+        }
+
+        fn config() -> Config {
+            Config {
+                name: "app".to_string(),
+                servers: vec![
+                    Server {
+                        host: "a.example.com".to_string(),
+                        port: 80,
+                        backup: None,
+                    },
+                    Server {
+                        host: "b.example.com".to_string(),
+                        port: 8080,
+                        backup: Some(true),
+                    },
+                ],
+                tags: vec!["web".to_string(), "api".to_string()],
+            }
+        }
+
+        #[test]
+        fn test_serialize_repeated_sections() {
+            assert_eq!(
+                to_string(&config()).unwrap(),
+                "name = app\ntags = web, api\n\
+                 [servers]\nhost = a.example.com\nport = 80\n; backup = \n\
+                 [servers]\nhost = b.example.com\nport = 8080\nbackup = true\n"
+            );
+        }
+
+        #[test]
+        fn test_roundtrip_repeated_sections() {
+            let ini = to_string(&config()).unwrap();
+            assert_eq!(from_str::<Config>(&ini).unwrap(), config());
+            assert_eq!(from_reader::<_, Config>(ini.as_bytes()).unwrap(), config());
+        }
+
+        #[test]
+        fn test_deserialize_single_section_as_element() {
+            let ini = "name = app\ntags = web\n[servers]\nhost = a.example.com\nport = 80\n";
+            let config: Config = from_str(ini).unwrap();
+            assert_eq!(config.servers.len(), 1);
+            assert_eq!(config.servers[0].host, "a.example.com");
+        }
+
+        #[test]
+        fn test_repeated_section_missing_field() {
+            let ini =
+                "name = app\ntags = web\n[servers]\nhost = a\nport = 80\n[servers]\nhost = b\n";
+            assert!(matches!(
+                from_str::<Config>(ini),
+                Err(Error::MissingField { ref field, ref section })
+                    if field == "port" && section == "servers"
+            ));
+        }
+
+        #[test]
+        fn test_nested_section_in_repeated_section() {
+            #[derive(Serialize)]
+            struct Outer {
+                items: Vec<Inner>,
+            }
+
+            #[derive(Serialize)]
+            struct Inner {
+                tls: Server,
+            }
+
+            let outer = Outer {
+                items: vec![Inner {
+                    tls: config().servers.remove(0),
+                }],
+            };
+            assert!(matches!(
+                to_string(&outer),
+                Err(Error::AtField { source, .. })
+                    if matches!(*source, Error::UnsupportedFeature(_))
+            ));
+        }
+
+        #[test]
+        fn test_layered_repeated_sections() {
+            let base = "name = app\ntags = web\n[servers]\nhost = a\nport = 80\n[servers]\nhost = b\nport = 81\n";
+            let local = "[servers]\nhost = c\nport = 82\n";
+            let config: Config = from_strs(&[base, local]).unwrap();
+            let hosts: Vec<_> = config.servers.iter().map(|s| s.host.as_str()).collect();
+            assert_eq!(hosts, ["c"]);
+        }
+    }
+
+    mod deterministic {
+        use super::*;
+        use std::collections::{BTreeMap, HashMap};
+
+        #[derive(Serialize)]
+        struct Config {
+            name: String,
+            env: BTreeMap<String, String>,
+            servers: Vec<Server>,
+            limits: Limits,
+        }
+
+        #[derive(Serialize)]
+        struct Server {
+            host: String,
+        }
+
+        #[derive(Serialize)]
+        struct Limits {
+            memory: Option<u32>,
+            ratio: f64,
+        }
+
+        #[test]
+        fn test_identical_output() {
+            let config = || Config {
+                name: "app".to_string(),
+                env: ["PATH", "HOME", "USER"]
+                    .into_iter()
+                    .map(|key| (key.to_string(), key.to_lowercase()))
+                    .collect(),
+                servers: vec![
+                    Server {
+                        host: "a".to_string(),
+                    },
+                    Server {
+                        host: "b".to_string(),
+                    },
+                ],
+                limits: Limits {
+                    memory: None,
+                    ratio: 0.1,
+                },
+            };
+
+            let expected = "name = app\n[env]\nHOME = home\nPATH = path\nUSER = user\n\
+                            [servers]\nhost = a\n[servers]\nhost = b\n\
+                            [limits]\n; memory = \nratio = 0.1\n";
+            for _ in 0..10 {
+                assert_eq!(to_string(&config()).unwrap(), expected);
+            }
+        }
+
+        #[test]
+        fn test_parsed_document_output() {
+            let ini = "[b]\nkey = 2\n[a]\nkey = 1\n";
+            let options = DeserializeOptions {
+                root_map_key: Some(String::new()),
+                ..Default::default()
+            };
+
+            // Each parse builds maps with their own random order
+            let parsed = || -> HashMap<String, HashMap<String, String>> {
+                from_str_with(ini, &options).unwrap()
+            };
+            let ser_options = SerializeOptions {
+                section_order: SectionOrder::Alphabetical,
+                ..Default::default()
+            };
+            for _ in 0..10 {
+                assert_eq!(
+                    to_string_with(&parsed(), &ser_options).unwrap(),
+                    "[a]\nkey = 1\n[b]\nkey = 2\n"
+                );
+            }
+
+            // A `Value` keeps the order of the file
+            let value: Value = from_str(ini).unwrap();
+            assert_eq!(to_string(&value).unwrap(), ini);
+        }
+    }
+
+    mod unsupported {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            server: Server,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Server {
+            allowed_hosts: Vec<Vec<String>>,
+            tls: Option<Tls>,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Tls {
+            cert: String,
+        }
+
+        #[test]
+        fn test_serialize_names_field_and_section() {
+            let config = Config {
+                name: "app".to_string(),
+                server: Server {
+                    allowed_hosts: vec![vec!["a.com".to_string()]],
+                    tls: None,
+                },
+            };
+            assert_eq!(
+                to_string(&config).unwrap_err().to_string(),
+                "unsupported feature 'nested sequences' at field 'allowed_hosts' in section [server]"
+            );
+        }
+
+        #[test]
+        fn test_serialize_root_field() {
+            #[derive(Serialize)]
+            enum Mode {
+                Fixed(u32),
+            }
+
+            #[derive(Serialize)]
+            struct Root {
+                mode: Mode,
+            }
+
+            match to_string(&Root {
+                mode: Mode::Fixed(3),
+            }) {
+                Err(Error::AtField {
+                    field,
+                    section,
+                    source,
+                }) => {
+                    assert_eq!((field.as_str(), section.as_str()), ("mode", ""));
+                    assert!(
+                        matches!(*source, Error::UnsupportedFeature(feature) if feature == "enum variants")
+                    );
+                }
+                other => panic!("expected unsupported feature error, got {other:?}"),
+            }
+        }
+
+        #[test]
+        fn test_deserialize_names_field_and_section() {
+            let ini = "name = app\n[server]\nallowed_hosts = a.com\ntls = on\n";
+            assert_eq!(
+                from_str::<Config>(ini).unwrap_err().to_string(),
+                "line 4: unsupported feature 'structs in values' at field 'tls' in section [server]"
+            );
+        }
+
+        #[test]
+        fn test_deserialize_names_section() {
+            #[derive(Debug, Deserialize)]
+            enum Shape {
+                Point(#[allow(dead_code)] u32, #[allow(dead_code)] u32),
+            }
+
+            #[derive(Debug, Deserialize)]
+            struct Root {
+                #[allow(dead_code)]
+                shape: Shape,
+            }
+
+            assert_eq!(
+                from_str::<Root>("[shape]\ntype = Point\n")
+                    .unwrap_err()
+                    .to_string(),
+                "unsupported feature 'tuple variants' in section [shape]"
+            );
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    mod timestamp {
+        use super::*;
+        use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Job {
+            #[serde(with = "crate::timestamp")]
+            started: DateTime<Utc>,
+            #[serde(default, with = "crate::timestamp::option")]
+            finished: Option<DateTime<FixedOffset>>,
+        }
+
+        fn job() -> Job {
+            Job {
+                started: Utc.timestamp_opt(1_714_566_600, 123_456_789).unwrap(),
+                finished: None,
+            }
+        }
+
+        #[test]
+        fn test_full_precision() {
+            let ini = to_string(&job()).unwrap();
+            assert_eq!(
+                ini,
+                "started = 2024-05-01T12:30:00.123456789Z\n; finished = \n"
+            );
+            assert_eq!(from_str::<Job>(&ini).unwrap(), job());
+        }
+
+        #[test]
+        fn test_offset() {
+            let finished = FixedOffset::east_opt(2 * 3600)
+                .unwrap()
+                .with_ymd_and_hms(2024, 5, 1, 14, 45, 0)
+                .unwrap();
+            let job = Job {
+                finished: Some(finished),
+                ..job()
+            };
+
+            let ini = to_string(&job).unwrap();
+            assert!(ini.ends_with("finished = 2024-05-01T14:45:00+02:00\n"));
+            assert_eq!(from_str::<Job>(&ini).unwrap(), job);
+        }
+
+        #[test]
+        fn test_colon_delimiter() {
+            let ser_options = SerializeOptions {
+                delimiter: ':',
+                ..Default::default()
+            };
+            let de_options = DeserializeOptions {
+                delimiter: ':',
+                ..Default::default()
+            };
+
+            let ini = to_string_with(&job(), &ser_options).unwrap();
+            assert_eq!(from_str_with::<Job>(&ini, &de_options).unwrap(), job());
+        }
+
+        #[test]
+        fn test_invalid_timestamp() {
+            assert_eq!(
+                from_str::<Job>("started = yesterday\n")
+                    .unwrap_err()
+                    .to_string(),
+                "line 1: custom error: invalid value: string \"yesterday\", expected an RFC 3339 timestamp"
+            );
+        }
+    }
+
+    mod spacing {
+        use super::*;
+        use std::collections::BTreeMap;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Server {
+            port: u16,
+        }
+
+        #[test]
+        fn test_spacing_around_delimiter() {
+            for ini in [
+                "port=8080",
+                "port =8080",
+                "port= 8080",
+                "port = 8080",
+                "port\t=\t8080",
+                "  port   =   8080  ",
+                "\tport=8080\t",
+            ] {
+                let server: Server = from_str(ini).unwrap();
+                assert_eq!(server.port, 8080, "{ini:?}");
+            }
+        }
+
+        #[test]
+        fn test_inner_spaces_preserved() {
+            #[derive(Debug, Deserialize, PartialEq)]
+            struct Profile {
+                #[serde(rename = "display name")]
+                display_name: String,
+            }
+
+            let profile: Profile = from_str("display name=Jane  Doe\n").unwrap();
+            assert_eq!(profile.display_name, "Jane  Doe");
+
+            let profile: Profile = from_str("  display name \t=\t Jane Doe \n").unwrap();
+            assert_eq!(profile.display_name, "Jane Doe");
+
+            // Inner whitespace of a key isn't collapsed
+            let entries: BTreeMap<String, String> = from_str("display  name = x\n").unwrap();
+            assert_eq!(entries.keys().collect::<Vec<_>>(), ["display  name"]);
+        }
+    }
+
+    mod key_transform {
+        use super::*;
+        use std::collections::BTreeMap;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            app_name: String,
+            log_file: Option<String>,
+            database: Database,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Database {
+            max_connections: u32,
+            connection_pool: Pool,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Pool {
+            idle_timeout: u32,
+        }
+
+        fn config() -> Config {
+            Config {
+                app_name: "demo".to_string(),
+                log_file: None,
+                database: Database {
+                    max_connections: 10,
+                    connection_pool: Pool { idle_timeout: 30 },
+                },
+            }
+        }
+
+        fn transformed(key_transform: KeyTransform) -> String {
+            let options = SerializeOptions {
+                key_transform,
+                ..Default::default()
+            };
+            to_string_with(&config(), &options).unwrap()
+        }
+
+        #[test]
+        fn test_upper_round_trip() {
+            let ini = transformed(KeyTransform::Upper);
+            assert_eq!(
+                ini,
+                "APP_NAME = demo\n; LOG_FILE = \n[DATABASE]\nMAX_CONNECTIONS = 10\n\
+                 [DATABASE.CONNECTION_POOL]\nIDLE_TIMEOUT = 30\n"
+            );
+
+            let options = DeserializeOptions {
+                case_insensitive: true,
+                ..Default::default()
+            };
+            assert_eq!(from_str_with::<Config>(&ini, &options).unwrap(), config());
+        }
+
+        #[test]
+        fn test_kebab() {
+            assert_eq!(
+                transformed(KeyTransform::Kebab),
+                "app-name = demo\n; log-file = \n[database]\nmax-connections = 10\n\
+                 [database.connection-pool]\nidle-timeout = 30\n"
+            );
+        }
+
+        #[test]
+        fn test_map_keys() {
+            let entries = BTreeMap::from([
+                ("maxRetries".to_string(), 3),
+                ("HTTPPort".to_string(), 80),
+                ("log-level2Debug".to_string(), 1),
+            ]);
+            let options = SerializeOptions {
+                key_transform: KeyTransform::Snake,
+                ..Default::default()
+            };
+            assert_eq!(
+                to_string_with(&entries, &options).unwrap(),
+                "httpport = 80\nlog_level2_debug = 1\nmax_retries = 3\n"
+            );
+
+            let options = SerializeOptions {
+                key_transform: KeyTransform::Lower,
+                ..Default::default()
+            };
+            assert_eq!(
+                to_string_with(&entries, &options).unwrap(),
+                "httpport = 80\nlog-level2debug = 1\nmaxretries = 3\n"
+            );
+        }
+
+        #[test]
+        fn test_self_referential_none() {
+            #[derive(Serialize)]
+            struct Node {
+                node_name: String,
+                child_node: Option<Box<Node>>,
+            }
+
+            let node = Node {
+                node_name: "a".to_string(),
+                child_node: Some(Box::new(Node {
+                    node_name: "b".to_string(),
+                    child_node: None,
+                })),
+            };
+            let options = SerializeOptions {
+                key_transform: KeyTransform::Upper,
+                ..Default::default()
+            };
+            assert_eq!(
+                to_string_with(&node, &options).unwrap(),
+                "NODE_NAME = a\n[CHILD_NODE]\nNODE_NAME = b\n"
+            );
+        }
+
+        #[test]
+        fn test_colliding_sections() {
+            let pools = BTreeMap::from([
+                ("DB".to_string(), Pool { idle_timeout: 1 }),
+                ("db".to_string(), Pool { idle_timeout: 2 }),
+            ]);
+            assert_eq!(
+                to_string(&pools).unwrap(),
+                "[DB]\nidle_timeout = 1\n[db]\nidle_timeout = 2\n"
+            );
+
+            let options = SerializeOptions {
+                key_transform: KeyTransform::Upper,
+                ..Default::default()
+            };
+            assert!(matches!(
+                to_string_with(&pools, &options),
+                Err(Error::DuplicateSection { name }) if name == "DB"
+            ));
+
+            let nested = BTreeMap::from([("cache".to_string(), pools)]);
+            assert!(matches!(
+                to_string_with(&nested, &options),
+                Err(Error::DuplicateSection { name }) if name == "CACHE.DB"
+            ));
+        }
+
+        #[test]
+        fn test_repeated_sections() {
+            #[derive(Serialize)]
+            struct Cluster {
+                db_pools: Vec<Pool>,
+            }
+
+            let cluster = Cluster {
+                db_pools: vec![Pool { idle_timeout: 1 }, Pool { idle_timeout: 2 }],
+            };
+            let options = SerializeOptions {
+                key_transform: KeyTransform::Upper,
+                ..Default::default()
+            };
+            assert_eq!(
+                to_string_with(&cluster, &options).unwrap(),
+                "[DB_POOLS]\nIDLE_TIMEOUT = 1\n[DB_POOLS]\nIDLE_TIMEOUT = 2\n"
+            );
+        }
+    }
+
+    mod spans {
+        use super::*;
+        use std::collections::BTreeMap;
+
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Server {
+            name: String,
+            port: u16,
+            #[serde(default)]
+            backups: Vec<u16>,
+        }
+
+        // The text an error points at
+        fn spanned(ini: &str, err: Error) -> &str {
+            &ini[err.span().expect("error without span")]
+        }
+
+        #[test]
+        fn test_value_span() {
+            let ini = "name = app\nport = not_a_number ; http\n";
+            let err = from_str::<Server>(ini).unwrap_err();
+            assert_eq!(err.span(), Some(18..30));
+            assert_eq!(spanned(ini, err), "not_a_number");
+        }
+
+        #[test]
+        fn test_span_after_bom_and_crlf() {
+            let ini = "\u{FEFF}name = app\r\n\r\nport = 80x\r\n";
+            let err = from_str::<Server>(ini).unwrap_err();
+            assert_eq!(spanned(ini, err), "80x");
+
+            let err = from_reader::<_, Server>(ini.as_bytes()).unwrap_err();
+            assert_eq!(spanned(ini, err), "80x");
+        }
+
+        #[test]
+        fn test_repeated_key_span() {
+            let ini = "name = app\nport = 80\nbackups = 81\nbackups = x\n";
+            let err = from_str::<Server>(ini).unwrap_err();
+            assert_eq!(err.span(), Some(44..45));
+        }
+
+        #[test]
+        fn test_header_and_bare_key_spans() {
+            let options = DeserializeOptions {
+                duplicate_sections: DuplicateSections::Error,
+                ..Default::default()
+            };
+            let ini = "[a]\nkey = 1\n  [ a ]  \n";
+            let err =
+                from_str_with::<BTreeMap<String, BTreeMap<String, u8>>>(ini, &options).unwrap_err();
+            assert_eq!(spanned(ini, err), "[ a ]");
+
+            let options = DeserializeOptions {
+                bare_keys: true,
+                ..Default::default()
+            };
+            let ini = "name = app\n port\n";
+            let err = from_str_with::<Server>(ini, &options).unwrap_err();
+            assert_eq!(spanned(ini, err), "port");
+        }
+
+        #[test]
+        fn test_no_span() {
+            let ini = "name = app\nport = 80\\\n  x\n";
+            let err = from_str::<Server>(ini).unwrap_err();
+            assert!(matches!(
+                err,
+                Error::AtLine {
+                    line: 2,
+                    span: None,
+                    ..
+                }
+            ));
+
+            let err = from_str::<Server>("name = app\n").unwrap_err();
+            assert_eq!(err.span(), None);
+        }
+    }
+
+    mod describe {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            log_file: Option<String>,
+            server: Server,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Server {
+            port: u16,
+            tls: Tls,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Tls {
+            cert: String,
+        }
+
+        impl IniDescribe for Config {
+            fn describe(section: &str, key: &str) -> Option<&'static str> {
+                match (section, key) {
+                    ("", "name") => Some("Name of the application"),
+                    ("", "log_file") => Some("Where to write the log"),
+                    ("", "server") => Some("Where to listen"),
+                    ("server", "port") => Some("TCP port"),
+                    ("server", "tls") => Some("Certificate to serve\nLeave out for plain HTTP"),
+                    ("SERVER", "PORT") => Some("Shouting"),
+                    _ => None,
+                }
+            }
+        }
+
+        fn config() -> Config {
+            Config {
+                name: "app".to_string(),
+                log_file: None,
+                server: Server {
+                    port: 8080,
+                    tls: Tls {
+                        cert: "cert.pem".to_string(),
+                    },
+                },
+            }
+        }
+
+        #[test]
+        fn test_keys_and_sections() {
+            let ini = to_string_described(&config()).unwrap();
+            assert_eq!(
+                ini,
+                "; Name of the application\nname = app\n; Where to write the log\n; log_file = \n\
+                 ; Where to listen\n[server]\n; TCP port\nport = 8080\n\
+                 ; Certificate to serve\n; Leave out for plain HTTP\n[server.tls]\ncert = cert.pem\n"
+            );
+            assert_eq!(from_str::<Config>(&ini).unwrap(), config());
+        }
+
+        #[test]
+        fn test_without_describe() {
+            // The same value written by `to_string` has no comments
+            let ini = to_string(&config()).unwrap();
+            assert!(!ini.contains("Name of the application"));
+        }
+
+        #[test]
+        fn test_options() {
+            let options = SerializeOptions {
+                align: true,
+                space_around_delimiter: true,
+                blank_line_between_sections: true,
+                comment_char: '#',
+                option_style: OptionStyle::Commented,
+                ..Default::default()
+            };
+            let ini = to_string_described_with(&config(), &options).unwrap();
+            assert_eq!(
+                ini,
+                "# Name of the application\nname       = app\n# Where to write the log\n\
+                 # log_file = \n\n# Where to listen\n[server]\n# TCP port\nport = 8080\n\n\
+                 # Certificate to serve\n# Leave out for plain HTTP\n[server.tls]\ncert = cert.pem\n"
+            );
+        }
+
+        #[test]
+        fn test_omitted_key() {
+            let options = SerializeOptions {
+                option_style: OptionStyle::Omit,
+                ..Default::default()
+            };
+            let ini = to_string_described_with(&config(), &options).unwrap();
+            assert!(!ini.contains("log"));
+            assert!(ini.starts_with("; Name of the application\nname = app\n; Where to listen\n"));
+        }
+
+        #[test]
+        fn test_key_transform() {
+            // Descriptions are looked up by the names as written
+            let options = SerializeOptions {
+                key_transform: KeyTransform::Upper,
+                ..Default::default()
+            };
+            let ini = to_string_described_with(&config(), &options).unwrap();
+            assert!(ini.contains("[SERVER]\n; Shouting\nPORT = 8080\n"));
+            assert!(!ini.contains("; Name of the application"));
+        }
+
+        #[derive(Serialize)]
+        struct Cluster {
+            servers: Vec<Tls>,
+        }
+
+        impl IniDescribe for Cluster {
+            fn describe(section: &str, key: &str) -> Option<&'static str> {
+                match (section, key) {
+                    ("", "servers") => Some("One section per server"),
+                    ("servers", "cert") => Some("Certificate"),
+                    _ => None,
+                }
+            }
+        }
+
+        #[test]
+        fn test_repeated_sections() {
+            let cluster = Cluster {
+                servers: vec![
+                    Tls {
+                        cert: "a.pem".to_string(),
+                    },
+                    Tls {
+                        cert: "b.pem".to_string(),
+                    },
+                ],
+            };
+            let ini = to_string_described(&cluster).unwrap();
+            assert_eq!(
+                ini,
+                "; One section per server\n[servers]\n; Certificate\ncert = a.pem\n\
+                 [servers]\n; Certificate\ncert = b.pem\n"
+            );
+        }
+
+        #[derive(Serialize)]
+        struct Node {
+            speed: f32,
+            child: Option<Box<Node>>,
+        }
+
+        impl IniDescribe for Node {
+            fn describe(_section: &str, key: &str) -> Option<&'static str> {
+                match key {
+                    "speed" => Some("Playback speed"),
+                    "child" => Some("Overrides for the child"),
+                    _ => None,
+                }
+            }
+        }
+
+        #[test]
+        fn test_self_referential() {
+            // The `None` child of the child is left out along with its description
+            let node = Node {
+                speed: 1.0,
+                child: Some(Box::new(Node {
+                    speed: 1.5,
+                    child: None,
+                })),
+            };
+            assert_eq!(
+                to_string_described(&node).unwrap(),
+                "; Playback speed\nspeed = 1\n; Overrides for the child\n[child]\n\
+                 ; Playback speed\nspeed = 1.5\n"
+            );
+        }
+    }
+
+    mod overlay {
+        use super::*;
+        use serde::de::{IgnoredAny, MapAccess};
+        use std::collections::{BTreeMap, HashMap};
+
+        #[derive(Debug, Clone, Deserialize, PartialEq)]
+        struct Config {
+            name: String,
+            tags: Vec<String>,
+            server: Server,
+            cache: Option<Cache>,
+            extra: BTreeMap<String, String>,
+        }
+
+        #[derive(Debug, Clone, Deserialize, PartialEq)]
+        struct Server {
+            host: String,
+            port: u16,
+        }
+
+        #[derive(Debug, Clone, Deserialize, PartialEq)]
+        struct Cache {
+            size: u32,
+        }
+
+        impl<'de> IniOverlay<'de> for Config {
+            fn overlay<A: MapAccess<'de>>(&mut self, mut map: A) -> Result<(), A::Error> {
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "name" => self.name = map.next_value()?,
+                        "tags" => self.tags = map.next_value()?,
+                        "server" => map.next_value_seed(OverlaySeed(&mut self.server))?,
+                        "cache" => self.cache = map.next_value()?,
+                        "extra" => map.next_value_seed(OverlaySeed(&mut self.extra))?,
+                        _ => map.next_value::<IgnoredAny>().map(drop)?,
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        impl<'de> IniOverlay<'de> for Server {
+            fn overlay<A: MapAccess<'de>>(&mut self, mut map: A) -> Result<(), A::Error> {
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "host" => self.host = map.next_value()?,
+                        "port" => self.port = map.next_value()?,
+                        _ => map.next_value::<IgnoredAny>().map(drop)?,
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        fn defaults() -> Config {
+            Config {
+                name: "app".to_string(),
+                tags: vec!["a".to_string(), "b".to_string()],
+                server: Server {
+                    host: "0.0.0.0".to_string(),
+                    port: 80,
+                },
+                cache: None,
+                extra: BTreeMap::new(),
+            }
+        }
+
+        #[test]
+        fn test_missing_keys_keep_values() {
+            let mut config = defaults();
+            from_str_into("[server]\nport = 8080\n", &mut config).unwrap();
+
+            let mut expected = defaults();
+            expected.server.port = 8080;
+            assert_eq!(config, expected);
+
+            from_str_into("", &mut config).unwrap();
+            assert_eq!(config, expected);
+        }
+
+        #[test]
+        fn test_keys_replace_values() {
+            let mut config = defaults();
+            from_str_into("name = other\ntags = c\n[cache]\nsize = 64\n", &mut config).unwrap();
+
+            assert_eq!(
+                config,
+                Config {
+                    name: "other".to_string(),
+                    tags: vec!["c".to_string()],
+                    cache: Some(Cache { size: 64 }),
+                    ..defaults()
+                }
+            );
+
+            // Present options are kept as well
+            from_str_into("name = again\n", &mut config).unwrap();
+            assert_eq!(config.cache, Some(Cache { size: 64 }));
+        }
+
+        #[test]
+        fn test_map_entries_overlaid() {
+            // Entries INI can't write are kept, as the target is never written out
+            let mut config = defaults();
+            config.extra.insert(";k".to_string(), "v".to_string());
+            from_str_into("name = y\n", &mut config).unwrap();
+            assert_eq!(config.extra[";k"], "v");
+
+            from_str_into("[extra]\nk2 = w\n", &mut config).unwrap();
+            assert_eq!(
+                config.extra,
+                BTreeMap::from([
+                    (";k".to_string(), "v".to_string()),
+                    ("k2".to_string(), "w".to_string()),
+                ])
+            );
+
+            let mut map = HashMap::from([("a".to_string(), 1), ("b".to_string(), 2)]);
+            from_str_into("b = 3\nc = 4\n", &mut map).unwrap();
+            assert_eq!(
+                map,
+                HashMap::from([
+                    ("a".to_string(), 1),
+                    ("b".to_string(), 3),
+                    ("c".to_string(), 4),
+                ])
+            );
+        }
+
+        #[test]
+        fn test_error_keeps_target() {
+            // Nothing is assigned unless the whole string parses
+            let options = DeserializeOptions {
+                duplicate_sections: DuplicateSections::Error,
+                ..Default::default()
+            };
+            let mut config = defaults();
+            let ini = "name = other\n[server]\nport = 1\n[server]\nport = 2\n";
+            assert!(from_str_into_with(ini, &mut config, &options).is_err());
+            assert_eq!(config, defaults());
+
+            // Values are assigned as they are read
+            let err = from_str_into("name = other\n[server]\nport = high\n", &mut config);
+            assert_eq!(
+                err.unwrap_err().to_string(),
+                "line 3: invalid value for u16: high"
+            );
+            assert_eq!(config.name, "other");
+            assert_eq!(config.server, defaults().server);
+        }
+
+        #[test]
+        fn test_with_options() {
+            let options = DeserializeOptions {
+                delimiter: ':',
+                seq_delimiter: SeqDelimiter::Whitespace,
+                case_insensitive: true,
+                root_section: Some("DEFAULT".to_string()),
+                ..Default::default()
+            };
+            let mut config = defaults();
+            from_str_into_with(
+                "[DEFAULT]\nName: other\n[Server]\nPort: 8080\n",
+                &mut config,
+                &options,
+            )
+            .unwrap();
+
+            let mut expected = defaults();
+            expected.name = "other".to_string();
+            expected.server.port = 8080;
+            assert_eq!(config, expected);
+        }
+
+        #[cfg(feature = "interpolate")]
+        #[test]
+        fn test_variables_expand_once() {
+            let options = DeserializeOptions {
+                variables: Some(Variables::Map(HashMap::from([(
+                    "HOST".to_string(),
+                    "db".to_string(),
+                )]))),
+                ..Default::default()
+            };
+            let mut config = defaults();
+            config.name = "${HOST} costs $$5".to_string();
+            from_str_into_with("[server]\nhost = ${HOST}\n", &mut config, &options).unwrap();
+
+            assert_eq!(config.name, "${HOST} costs $$5");
+            assert_eq!(config.server.host, "db");
+        }
+    }
+
+    mod escape_mode {
+        use super::*;
+        use std::collections::BTreeMap;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Config {
+            text: String,
+            tags: Vec<String>,
+        }
+
+        fn options(mode: EscapeMode) -> (SerializeOptions, DeserializeOptions) {
+            let ser = SerializeOptions {
+                escape_mode: mode,
+                ..Default::default()
+            };
+            let de = DeserializeOptions {
+                escape_mode: mode,
+                ..Default::default()
+            };
+            (ser, de)
+        }
+
+        #[test]
+        fn test_roundtrip_each_mode() {
+            let config = Config {
+                text: " line one\nline \"two\"\r\n\tthree ; # 100% C:\\dir\\".to_string(),
+                tags: vec!["a;b".to_string(), "\"c, d\"".to_string(), "50%".to_string()],
+            };
+            for mode in [EscapeMode::Backslash, EscapeMode::Percent] {
+                let (ser_options, de_options) = options(mode);
+                let ini = to_string_with(&config, &ser_options).unwrap();
+                assert_eq!(ini.lines().count(), 2, "{mode:?}: {ini}");
+                assert_eq!(
+                    from_str_with::<Config>(&ini, &de_options).unwrap(),
+                    config,
+                    "{mode:?}: {ini}"
+                );
+            }
+        }
+
+        #[test]
+        fn test_percent_encoding() {
+            let (ser_options, de_options) = options(EscapeMode::Percent);
+            let config = Config {
+                text: "a \"b\"\n100% ; c:\\ é".to_string(),
+                tags: vec!["x#y".to_string()],
+            };
+
+            let ini = to_string_with(&config, &ser_options).unwrap();
+            assert_eq!(ini, "text = a %22b%22%0A100%25 %3B c:%5C é\ntags = x%23y\n");
+            assert_eq!(from_str_with::<Config>(&ini, &de_options).unwrap(), config);
+
+            // Comment characters are part of the value without inline comments
+            let ser_options = SerializeOptions {
+                inline_comments: InlineComments::Never,
+                ..ser_options
+            };
+            let ini = to_string_with(&config, &ser_options).unwrap();
+            assert!(ini.ends_with("tags = x#y\n"), "{ini}");
+        }
+
+        #[test]
+        fn test_percent_decoding() {
+            let (_, options) = options(EscapeMode::Percent);
+            let config: Config =
+                from_str_with("text = 5%, %zz %4 %C3%A9 %FF\ntags = %2c\n", &options).unwrap();
+            assert_eq!(config.text, "5%, %zz %4 é \u{FFFD}");
+            // Encoded separators don't split elements
+            assert_eq!(config.tags, [","]);
+
+            // Backslashes are read as written
+            let config: Config = from_str_with("text = a\\nb\ntags = \n", &options).unwrap();
+            assert_eq!(config.text, "a\\nb");
+        }
+
+        #[test]
+        fn test_percent_encoded_keys() {
+            let (ser_options, de_options) = options(EscapeMode::Percent);
+            let map = BTreeMap::from([
+                ("a=b".to_string(), "1".to_string()),
+                ("50%".to_string(), "2".to_string()),
+                ("c\\".to_string(), "3".to_string()),
+            ]);
+
+            let ini = to_string_with(&map, &ser_options).unwrap();
+            assert_eq!(ini, "50%25 = 2\na%3Db = 1\nc\\ = 3\n");
+            assert_eq!(
+                from_str_with::<BTreeMap<String, String>>(&ini, &de_options).unwrap(),
+                map
+            );
+        }
+
+        #[test]
+        fn test_document_percent_encoding() {
+            let (_, options) = options(EscapeMode::Percent);
+            let mut doc = IniDocument::parse_with("text = a%0Ab\n", &options);
+            assert_eq!(doc.get("", "text").as_deref(), Some("a\nb"));
+
+            doc.set("", "text", "c;d").unwrap();
+            assert_eq!(doc.to_string(), "text = c%3Bd\n");
+        }
     }
 }
@@ -1,40 +1,618 @@
-use crate::{Error, error::Result};
+use crate::{Error, EscapeMode, InlineComments, de::too_deep, error::Result};
 use serde::{Serialize, ser};
+use std::{borrow::Cow, fmt, io};
 
-pub struct Serializer {
-    output: String,
+pub struct Serializer<W> {
+    writer: W,
     current_section: Option<String>,
-    section_names: Vec<String>,
+    // Name of the struct being serialized, if any
+    struct_name: Option<&'static str>,
+    // Struct fields written as a section, by struct name and key
+    section_fields: Vec<(&'static str, String)>,
+    options: SerializeOptions,
+    // Lines of the current section, written out once the section is complete
+    lines: Vec<Line>,
+    // Key of the map entry whose value is serialized next
+    map_key: Option<String>,
+    // Sections of the current struct or map, buffered in memory and written after its key/value
+    // lines once the root is complete, as later root keys have to come before them
+    sections: Vec<Serializer<Vec<u8>>>,
+    // Cleared serializers of sections already written out, reused for the next sections so a
+    // reused serializer doesn't allocate them again. Lent to a section while it is serialized.
+    spare: Vec<Serializer<Vec<u8>>>,
+    // Set for the serializers of sections, which buffer all their lines for the root to write out
+    nested: bool,
+    // Number of sections this one is nested in
+    depth: usize,
+    // Path of the section each element of the sequence being serialized is written as, with the
+    // description for the first of them
+    repeated_section: Option<(String, Option<&'static str>)>,
+    // Describes keys and sections as comments, see `IniDescribe`
+    describe: Option<Describe>,
+}
+
+type Describe = fn(&str, &str) -> Option<&'static str>;
+
+/// Descriptions of the keys and sections of a type, written as comments by
+/// [`to_string_described`].
+///
+/// `describe` is called with the section a key is written in and the key, both spelled as in
+/// the file, i.e. after [`SerializeOptions::key_transform`]. Keys of the root section come with
+/// an empty section name, and a section is described by its parent section and its name.
+/// Descriptions spanning several lines are written as one comment line each.
+///
+/// ```rust
+/// use serde::Serialize;
+/// use serini::{IniDescribe, to_string_described};
+///
+/// #[derive(Serialize)]
+/// struct Config {
+///     name: String,
+///     server: Server,
+/// }
+///
+/// #[derive(Serialize)]
+/// struct Server {
+///     port: u16,
+/// }
+///
+/// impl IniDescribe for Config {
+///     fn describe(section: &str, key: &str) -> Option<&'static str> {
+///         match (section, key) {
+///             ("", "name") => Some("Name of the application"),
+///             ("", "server") => Some("Where to listen"),
+///             ("server", "port") => Some("TCP port"),
+///             _ => None,
+///         }
+///     }
+/// }
+///
+/// let config = Config {
+///     name: "app".to_string(),
+///     server: Server { port: 8080 },
+/// };
+/// assert_eq!(
+///     to_string_described(&config).unwrap(),
+///     "; Name of the application\nname = app\n; Where to listen\n[server]\n; TCP port\nport = 8080\n"
+/// );
+/// ```
+pub trait IniDescribe {
+    /// The description of `key` within `section`, if any.
+    fn describe(section: &str, key: &str) -> Option<&'static str>;
+}
+
+/// Options controlling how values are written as INI.
+#[derive(Debug, Clone)]
+pub struct SerializeOptions {
+    /// Pad keys so the delimiters of all lines within a section line up.
+    ///
+    /// Root fields and each section are aligned independently of each other.
+    pub align: bool,
+    /// Pad section names inside their brackets, writing `[ name ]` instead of `[name]`.
+    pub header_padding: bool,
+    /// Character placed between a key and its value, `=` by default.
+    pub delimiter: char,
+    /// Surround the delimiter with spaces, writing `key = value` instead of `key=value`.
+    pub space_around_delimiter: bool,
+    /// Character starting the commented lines written for `None` values, `;` by default.
+    pub comment_char: char,
+    /// How `None` values are written.
+    pub option_style: OptionStyle,
+    /// Separator placed between the elements of a sequence, `", "` by default.
+    ///
+    /// Must match the [`SeqDelimiter`](crate::SeqDelimiter) used when reading the values back.
+    pub seq_separator: String,
+    /// Write maps held by a field inline as `key = k1=v1, k2=v2` instead of as a section.
+    ///
+    /// Entries are joined by [`seq_separator`](Self::seq_separator). Keys are escaped like
+    /// values, a `=` within them included, which fails with [`Error::UnrepresentableValue`]
+    /// when [`escape`](Self::escape) is off.
+    pub inline_maps: bool,
+    /// Write root fields under a section header with this name, e.g. `[DEFAULT]`, instead of
+    /// before the first header.
+    ///
+    /// Must match [`DeserializeOptions::root_section`](crate::DeserializeOptions::root_section)
+    /// when reading the values back.
+    pub root_section: Option<String>,
+    /// How booleans are written, `true`/`false` by default.
+    pub bool_style: BoolStyle,
+    /// Round floats to at most this many decimal places, e.g. `Some(3)` writes `1.23456` as
+    /// `1.235` and `2.5` as is. By default floats are written in their shortest form that reads
+    /// back as the same value.
+    ///
+    /// Floats are never written in scientific notation, `1e-7` is written as `0.0000001`.
+    pub float_decimals: Option<usize>,
+    /// Comment rule the output is read back with, `;` and `#` are only escaped where they would
+    /// start a comment.
+    ///
+    /// [`InlineComments::Anywhere`], the default, escapes all of them, which is safe for any
+    /// reader, including those that start a comment at any `;` or `#` rather than following the
+    /// default rule of [`DeserializeOptions`](crate::DeserializeOptions).
+    /// [`InlineComments::AfterWhitespace`] escapes those at the start of the value or after
+    /// whitespace, so `http://x#y` is written as is.
+    pub inline_comments: InlineComments,
+    /// Escape special characters in values, `true` by default. Otherwise values are written
+    /// verbatim, and fail to serialize with [`Error::UnrepresentableValue`] if they contain a
+    /// line break or end in a single backslash, which would continue the line.
+    ///
+    /// Must match [`DeserializeOptions::unescape`](crate::DeserializeOptions::unescape) when
+    /// reading the values back.
+    pub escape: bool,
+    /// Escape sequences written by [`escape`](Self::escape), backslash escapes by default.
+    ///
+    /// Must match [`DeserializeOptions::escape_mode`](crate::DeserializeOptions::escape_mode)
+    /// when reading the values back.
+    pub escape_mode: EscapeMode,
+    /// Separate each section from the lines before it with a blank line. A section starting the
+    /// document isn't preceded by one.
+    pub blank_line_between_sections: bool,
+    /// End the output with a line break, `true` by default. Otherwise the last line is left
+    /// unterminated.
+    ///
+    /// Only applied by [`to_writer_with`] and the functions built on it, as a [`Serializer`]
+    /// can't tell which line is the last.
+    pub trailing_newline: bool,
+    /// Line break ending each line, `\n` by default.
+    pub line_ending: LineEnding,
+    /// Write `true` booleans as a bare key, e.g. `verbose` instead of `verbose = true`. `false`
+    /// is written as usual.
+    ///
+    /// Needs [`DeserializeOptions::bare_keys`](crate::DeserializeOptions::bare_keys) to read the
+    /// keys back.
+    pub bare_flags: bool,
+    /// Order in which the sections following the same lines are written, the order of the fields
+    /// by default. Root keys always come first, and subsections follow their parent.
+    pub section_order: SectionOrder,
+    /// Spelling applied to keys and section names when writing, e.g. [`KeyTransform::Upper`]
+    /// writes `max_connections` as `MAX_CONNECTIONS`. Names are written as they are by default.
+    ///
+    /// Reading doesn't undo it. Keys written with [`KeyTransform::Upper`] or
+    /// [`KeyTransform::Lower`] are read back with
+    /// [`DeserializeOptions::case_insensitive`](crate::DeserializeOptions::case_insensitive),
+    /// the other transforms need fields renamed to match, e.g. with `#[serde(alias)]`.
+    ///
+    /// Sections that end up with the same name, such as map entries `db` and `DB` written in
+    /// uppercase, fail with [`Error::DuplicateSection`].
+    pub key_transform: KeyTransform,
+    /// Number of levels sections may be nested in, 128 by default. Deeper sections fail with
+    /// [`Error::UnsupportedFeature`] rather than running out of stack, as each level is written
+    /// by recursion.
+    pub max_depth: usize,
+}
+
+impl Default for SerializeOptions {
+    fn default() -> Self {
+        SerializeOptions {
+            align: false,
+            header_padding: false,
+            delimiter: '=',
+            space_around_delimiter: true,
+            comment_char: ';',
+            option_style: OptionStyle::default(),
+            seq_separator: ", ".to_string(),
+            inline_maps: false,
+            root_section: None,
+            bool_style: BoolStyle::default(),
+            float_decimals: None,
+            inline_comments: InlineComments::Anywhere,
+            escape: true,
+            escape_mode: EscapeMode::default(),
+            blank_line_between_sections: false,
+            trailing_newline: true,
+            line_ending: LineEnding::default(),
+            bare_flags: false,
+            section_order: SectionOrder::default(),
+            key_transform: KeyTransform::default(),
+            max_depth: 128,
+        }
+    }
+}
+
+impl SerializeOptions {
+    // Escape the parts of a key that would be read differently: the delimiter, which would end
+    // the key early, line breaks, a leading `;`, `#` or `[` that would turn the line into a
+    // comment or section header, and whitespace at either end, which is trimmed
+    pub(crate) fn escape_key<'k>(&self, key: &'k str) -> Result<Cow<'k, str>> {
+        let leading = key.starts_with([';', '#', '[']);
+        let padded = key.trim() != key;
+
+        if !self.escape {
+            if key.contains(self.delimiter) {
+                return Err(Error::unsupported("delimiters in keys"));
+            }
+            if key.contains(['\n', '\r']) || leading || padded {
+                return Err(Error::UnrepresentableValue {
+                    value: key.to_string(),
+                });
+            }
+            return Ok(Cow::Borrowed(key));
+        }
+
+        if self.escape_mode == EscapeMode::Percent {
+            // Percent-encoded keys are decoded entirely, so a `%` within them is encoded as well
+            let special = |c: char| matches!(c, '%' | '"') || c == self.delimiter;
+            if !key.contains(special)
+                && !key.contains(|c: char| c.is_ascii_control())
+                && !leading
+                && !padded
+            {
+                return Ok(Cow::Borrowed(key));
+            }
+            let start = key.len() - key.trim_start().len();
+            let end = key.trim_end().len();
+            let mut escaped = String::with_capacity(key.len());
+            for (i, c) in key.char_indices() {
+                let outside = i < start || i >= end;
+                if special(c) || c.is_ascii_control() || outside || (i == 0 && leading) {
+                    push_percent_encoded(&mut escaped, c);
+                } else {
+                    escaped.push(c);
+                }
+            }
+            return Ok(Cow::Owned(escaped));
+        }
+
+        let special = |c: char| matches!(c, '\\' | '\n' | '\r' | '\t' | '"') || c == self.delimiter;
+        if !key.contains(special) && !leading && !padded {
+            return Ok(Cow::Borrowed(key));
+        }
+        let mut escaped = String::with_capacity(key.len() + 2);
+        for (i, c) in key.char_indices() {
+            match c {
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                '\t' => escaped.push_str("\\t"),
+                _ if special(c) || (i == 0 && leading) => {
+                    escaped.push('\\');
+                    escaped.push(c);
+                }
+                _ => escaped.push(c),
+            }
+        }
+        // Whitespace can't be escaped with a backslash, but it is kept within quotes
+        if padded {
+            escaped = format!("\"{escaped}\"");
+        }
+        Ok(Cow::Owned(escaped))
+    }
+}
+
+/// Order of the sections within the document or their parent section.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum SectionOrder {
+    /// The order of the struct fields or map entries.
+    #[default]
+    Declaration,
+    /// Sorted by name.
+    Alphabetical,
+    /// The listed sections first, in the order given by their full name such as `server.tls`,
+    /// followed by the others in declaration order. Names are spelled as written, after any
+    /// [`SerializeOptions::key_transform`].
+    Explicit(Vec<String>),
+}
+
+/// Spelling of the keys and section names written, see [`SerializeOptions::key_transform`].
+///
+/// Words are separated by `_`, `-` or a lowercase letter followed by an uppercase one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeyTransform {
+    /// Names as given by the fields or map keys.
+    #[default]
+    None,
+    /// `MAX_CONNECTIONS`.
+    Upper,
+    /// `max_connections`, with the case of each letter lowered only.
+    Lower,
+    /// `max-connections`.
+    Kebab,
+    /// `max_connections`.
+    Snake,
+}
+
+impl KeyTransform {
+    fn apply(self, key: &str) -> Cow<'_, str> {
+        match self {
+            KeyTransform::None => Cow::Borrowed(key),
+            KeyTransform::Upper => Cow::Owned(key.to_uppercase()),
+            KeyTransform::Lower => Cow::Owned(key.to_lowercase()),
+            KeyTransform::Kebab => Cow::Owned(join_words(key, '-')),
+            KeyTransform::Snake => Cow::Owned(join_words(key, '_')),
+        }
+    }
+}
+
+// Lowercase the words of a name and join them with the separator
+fn join_words(key: &str, separator: char) -> String {
+    let mut output = String::with_capacity(key.len() + 4);
+    let mut after_lowercase = false;
+
+    for c in key.chars() {
+        if c == '_' || c == '-' {
+            output.push(separator);
+        } else if c.is_uppercase() {
+            if after_lowercase {
+                output.push(separator);
+            }
+            output.extend(c.to_lowercase());
+        } else {
+            output.push(c);
+        }
+        after_lowercase = c.is_lowercase() || c.is_ascii_digit();
+    }
+
+    output
+}
+
+/// Line break written at the end of each line.
+///
+/// Both are accepted when reading.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`, as used on Unix.
+    #[default]
+    Lf,
+    /// `\r\n`, as expected by some Windows tools.
+    Crlf,
+}
+
+impl LineEnding {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
+/// Output for a field holding `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OptionStyle {
+    /// Write the key as a commented line, e.g. `; age = `.
+    #[default]
+    Commented,
+    /// Leave the key out entirely.
+    Omit,
+    /// Write the key with an empty value, e.g. `age = `, and empty strings quoted as `""`.
+    ///
+    /// Read back as `None` and `Some("")` with
+    /// [`DeserializeOptions::empty_as_none`](crate::DeserializeOptions::empty_as_none).
+    EmptyValue,
+}
+
+/// Spelling of boolean values.
+///
+/// All of them are accepted when reading, regardless of case.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BoolStyle {
+    /// `true` and `false`.
+    #[default]
+    TrueFalse,
+    /// `yes` and `no`.
+    YesNo,
+    /// `on` and `off`.
+    OnOff,
+    /// `1` and `0`.
+    OneZero,
+}
+
+impl BoolStyle {
+    fn spelling(self, v: bool) -> &'static str {
+        match (self, v) {
+            (BoolStyle::TrueFalse, true) => "true",
+            (BoolStyle::TrueFalse, false) => "false",
+            (BoolStyle::YesNo, true) => "yes",
+            (BoolStyle::YesNo, false) => "no",
+            (BoolStyle::OnOff, true) => "on",
+            (BoolStyle::OnOff, false) => "off",
+            (BoolStyle::OneZero, true) => "1",
+            (BoolStyle::OneZero, false) => "0",
+        }
+    }
+}
+
+enum Line {
+    // The description of the key on the next line
+    Comment(&'static str),
+    KeyValue { key: String, value: String },
+    // A field holding `None`, written according to the option style
+    Unset { key: String },
+    Bare { key: String },
 }
 
 pub fn to_string<T>(value: &T) -> Result<String>
 where
     T: Serialize,
 {
-    let mut serializer = Serializer {
-        output: String::new(),
-        current_section: None,
-        section_names: Vec::new(),
-    };
+    to_string_with(value, &SerializeOptions::default())
+}
+
+pub fn to_string_with<T>(value: &T, options: &SerializeOptions) -> Result<String>
+where
+    T: Serialize,
+{
+    serialize_to_string(value, options, |_| Ok(()))
+}
 
-    // First pass: collect all section names
-    let mut section_collector = SectionCollector {
-        sections: Vec::new(),
+/// Serializes a value like [`to_string`], with the delimiters of each section aligned.
+pub fn to_string_pretty<T>(value: &T) -> Result<String>
+where
+    T: Serialize,
+{
+    let options = SerializeOptions {
+        align: true,
+        ..Default::default()
     };
-    value.serialize(&mut section_collector)?;
-    serializer.section_names = section_collector.sections;
+    to_string_with(value, &options)
+}
+
+/// Serializes a value like [`to_string`], with the descriptions given by [`IniDescribe`] written
+/// as comments above their keys and sections.
+pub fn to_string_described<T>(value: &T) -> Result<String>
+where
+    T: Serialize + IniDescribe,
+{
+    to_string_described_with(value, &SerializeOptions::default())
+}
+
+/// Serializes a value like [`to_string_described`], with the given formatting options.
+pub fn to_string_described_with<T>(value: &T, options: &SerializeOptions) -> Result<String>
+where
+    T: Serialize + IniDescribe,
+{
+    serialize_to_string(value, options, |serializer| {
+        serializer.describe = Some(T::describe);
+        Ok(())
+    })
+}
+
+/// Serializes a struct or map as a single section headed `[name]`, with its nested structs and
+/// maps as subsections such as `[name.child]`.
+///
+/// The result can be concatenated with other serialized sections to assemble a file piece by
+/// piece.
+pub fn to_string_in_section<T>(name: &str, value: &T) -> Result<String>
+where
+    T: Serialize,
+{
+    to_string_in_section_with(name, value, &SerializeOptions::default())
+}
+
+/// Serializes a value like [`to_string_in_section`], with the given formatting options.
+pub fn to_string_in_section_with<T>(
+    name: &str,
+    value: &T,
+    options: &SerializeOptions,
+) -> Result<String>
+where
+    T: Serialize,
+{
+    serialize_to_string(value, options, |serializer| {
+        // With the section entered up front, the value's fields are written as part of it
+        serializer.current_section = Some(name.to_string());
+        serializer.write_section_header(name, None)
+    })
+}
+
+/// Serializes a value as INI into `writer`.
+///
+/// Only the keys of the root section are written as they are produced. Sections are buffered in
+/// memory and written once the value is complete, as keys of the root section may follow them.
+pub fn to_writer<W, T>(writer: W, value: &T) -> Result<()>
+where
+    W: io::Write,
+    T: Serialize,
+{
+    to_writer_with(writer, value, &SerializeOptions::default())
+}
+
+/// Serializes a value like [`to_writer`], with the given formatting options.
+pub fn to_writer_with<W, T>(writer: W, value: &T, options: &SerializeOptions) -> Result<()>
+where
+    W: io::Write,
+    T: Serialize,
+{
+    serialize_into(writer, value, options, |_| Ok(()))
+}
 
-    // Second pass: actual serialization
+// Serialize a value into `writer` with a serializer prepared by `setup`, which every entry point
+// shares
+fn serialize_into<W, T, F>(writer: W, value: &T, options: &SerializeOptions, setup: F) -> Result<()>
+where
+    W: io::Write,
+    T: ?Sized + Serialize,
+    F: FnOnce(&mut Serializer<Output<W>>) -> Result<()>,
+{
+    let mut serializer = Serializer::with_options(Output::new(writer, options), options.clone());
+    setup(&mut serializer)?;
     value.serialize(&mut serializer)?;
-    Ok(serializer.output)
+    serializer.finish()?;
+    io::Write::flush(&mut serializer.writer)?;
+    Ok(())
+}
+
+// Like `serialize_into`, collecting the output in a string
+fn serialize_to_string<T, F>(value: &T, options: &SerializeOptions, setup: F) -> Result<String>
+where
+    T: ?Sized + Serialize,
+    F: FnOnce(&mut Serializer<Output<&mut Vec<u8>>>) -> Result<()>,
+{
+    let mut output = Vec::new();
+    serialize_into(&mut output, value, options, setup)?;
+    // Only `str` data and ASCII line breaks are written
+    Ok(String::from_utf8(output).expect("output is valid UTF-8"))
+}
+
+// Writer controlling the line breaks at both ends of the document
+struct Output<W> {
+    writer: W,
+    // Drop the blank line written before a section header that starts the document
+    skip_leading_blank: bool,
+    trailing_newline: bool,
+    line_ending: &'static [u8],
+    // A line break held back until something follows it
+    pending_newline: bool,
+}
+
+impl<W: io::Write> Output<W> {
+    fn new(writer: W, options: &SerializeOptions) -> Self {
+        Output {
+            writer,
+            skip_leading_blank: options.blank_line_between_sections,
+            trailing_newline: options.trailing_newline,
+            line_ending: options.line_ending.as_str().as_bytes(),
+            pending_newline: false,
+        }
+    }
+}
+
+impl<W: io::Write> io::Write for Output<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+        if std::mem::take(&mut self.skip_leading_blank) && !rest.is_empty() {
+            rest = rest.strip_prefix(self.line_ending).unwrap_or(rest);
+        }
+        if rest.is_empty() {
+            return Ok(buf.len());
+        }
+
+        if std::mem::take(&mut self.pending_newline) {
+            self.writer.write_all(self.line_ending)?;
+        }
+        match rest.strip_suffix(self.line_ending) {
+            Some(line) if !self.trailing_newline => {
+                self.writer.write_all(line)?;
+                self.pending_newline = true;
+            }
+            _ => self.writer.write_all(rest)?,
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
 }
 
-// Helper to collect section names
-struct SectionCollector {
-    sections: Vec<String>,
+// Helper struct to detect if a value serializes as a struct or map, which become sections
+struct SectionDetector {
+    is_section: bool,
+    // Set for a sequence of structs or maps, which become a section each
+    is_repeated_section: bool,
+    // Maps are written as values instead when inlined
+    inline_maps: bool,
 }
 
-impl ser::Serializer for &mut SectionCollector {
+impl SectionDetector {
+    fn new(inline_maps: bool) -> Self {
+        SectionDetector {
+            is_section: false,
+            is_repeated_section: false,
+            inline_maps,
+        }
+    }
+}
+
+impl ser::Serializer for &mut SectionDetector {
     type Ok = ();
     type Error = Error;
     type SerializeSeq = Self;
@@ -46,6 +624,7 @@ impl ser::Serializer for &mut SectionCollector {
     type SerializeStructVariant = Self;
 
     fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        self.is_section = true;
         Ok(self)
     }
 
@@ -77,6 +656,12 @@ impl ser::Serializer for &mut SectionCollector {
     fn serialize_u64(self, _v: u64) -> Result<()> {
         Ok(())
     }
+    fn serialize_i128(self, _v: i128) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_u128(self, _v: u128) -> Result<()> {
+        Ok(())
+    }
     fn serialize_f32(self, _v: f32) -> Result<()> {
         Ok(())
     }
@@ -156,6 +741,7 @@ impl ser::Serializer for &mut SectionCollector {
         Ok(self)
     }
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        self.is_section = !self.inline_maps;
         Ok(self)
     }
     fn serialize_struct_variant(
@@ -169,32 +755,24 @@ impl ser::Serializer for &mut SectionCollector {
     }
 }
 
-impl ser::SerializeStruct for &mut SectionCollector {
+impl ser::SerializeSeq for &mut SectionDetector {
     type Ok = ();
     type Error = Error;
-
-    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        // Check if this field is a struct that will become a section
-        let mut detector = StructDetector::new();
-        let _ = value.serialize(&mut detector);
-
-        if detector.is_struct {
-            self.sections.push(key.to_string());
-        }
-
+        let mut element = SectionDetector::new(self.inline_maps);
+        let _ = value.serialize(&mut element);
+        self.is_repeated_section |= element.is_section;
         Ok(())
     }
-
     fn end(self) -> Result<()> {
         Ok(())
     }
 }
 
-// Implement dummy traits for SectionCollector
-impl ser::SerializeSeq for &mut SectionCollector {
+impl ser::SerializeTuple for &mut SectionDetector {
     type Ok = ();
     type Error = Error;
     fn serialize_element<T>(&mut self, _value: &T) -> Result<()>
@@ -208,10 +786,10 @@ impl ser::SerializeSeq for &mut SectionCollector {
     }
 }
 
-impl ser::SerializeTuple for &mut SectionCollector {
+impl ser::SerializeTupleStruct for &mut SectionDetector {
     type Ok = ();
     type Error = Error;
-    fn serialize_element<T>(&mut self, _value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, _value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
@@ -222,7 +800,7 @@ impl ser::SerializeTuple for &mut SectionCollector {
     }
 }
 
-impl ser::SerializeTupleStruct for &mut SectionCollector {
+impl ser::SerializeTupleVariant for &mut SectionDetector {
     type Ok = ();
     type Error = Error;
     fn serialize_field<T>(&mut self, _value: &T) -> Result<()>
@@ -236,10 +814,16 @@ impl ser::SerializeTupleStruct for &mut SectionCollector {
     }
 }
 
-impl ser::SerializeTupleVariant for &mut SectionCollector {
+impl ser::SerializeMap for &mut SectionDetector {
     type Ok = ();
     type Error = Error;
-    fn serialize_field<T>(&mut self, _value: &T) -> Result<()>
+    fn serialize_key<T>(&mut self, _key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(())
+    }
+    fn serialize_value<T>(&mut self, _value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
@@ -250,16 +834,10 @@ impl ser::SerializeTupleVariant for &mut SectionCollector {
     }
 }
 
-impl ser::SerializeMap for &mut SectionCollector {
+impl ser::SerializeStruct for &mut SectionDetector {
     type Ok = ();
     type Error = Error;
-    fn serialize_key<T>(&mut self, _key: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        Ok(())
-    }
-    fn serialize_value<T>(&mut self, _value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, _key: &'static str, _value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
@@ -270,7 +848,7 @@ impl ser::SerializeMap for &mut SectionCollector {
     }
 }
 
-impl ser::SerializeStructVariant for &mut SectionCollector {
+impl ser::SerializeStructVariant for &mut SectionDetector {
     type Ok = ();
     type Error = Error;
     fn serialize_field<T>(&mut self, _key: &'static str, _value: &T) -> Result<()>
@@ -284,105 +862,1050 @@ impl ser::SerializeStructVariant for &mut SectionCollector {
     }
 }
 
-// Helper struct to detect if a value serializes as a struct
-struct StructDetector {
-    is_struct: bool,
-}
+impl<W: io::Write> Serializer<W> {
+    /// Creates a serializer writing to `writer` with the default options.
+    pub fn new(writer: W) -> Self {
+        Serializer::with_options(writer, SerializeOptions::default())
+    }
+
+    /// Creates a serializer writing to `writer` with the given formatting options.
+    pub fn with_options(writer: W, options: SerializeOptions) -> Self {
+        Serializer {
+            writer,
+            current_section: None,
+            struct_name: None,
+            section_fields: Vec::new(),
+            options,
+            lines: Vec::new(),
+            map_key: None,
+            sections: Vec::new(),
+            spare: Vec::new(),
+            nested: false,
+            depth: 0,
+            repeated_section: None,
+            describe: None,
+        }
+    }
 
-impl StructDetector {
-    fn new() -> Self {
-        StructDetector { is_struct: false }
+    /// Clears the state left by the previous value, even one that failed to serialize, so another
+    /// one can be serialized without allocating a new serializer. Its buffers keep their capacity,
+    /// and the buffers of the sections written so far are reused for the sections of later values.
+    ///
+    /// The writer is left as is, clear it through [`get_mut`](Self::get_mut) if needed.
+    pub fn reset(&mut self) {
+        self.current_section = None;
+        self.struct_name = None;
+        self.section_fields.clear();
+        self.lines.clear();
+        self.map_key = None;
+        // Sections left behind by a value that failed to serialize
+        while let Some(section) = self.sections.pop() {
+            self.recycle(section);
+        }
+        self.repeated_section = None;
+        self.describe = None;
+    }
+
+    // Keep the buffers of a section for a later one, along with those of its subsections and of
+    // any part of the pool it still holds
+    fn recycle(&mut self, section: Serializer<Vec<u8>>) {
+        let mut index = self.spare.len();
+        self.spare.push(section);
+        while index < self.spare.len() {
+            let section = &mut self.spare[index];
+            let mut sections = std::mem::take(&mut section.sections);
+            let mut spare = std::mem::take(&mut section.spare);
+            section.reset();
+            section.writer.clear();
+            self.spare.append(&mut sections);
+            self.spare.append(&mut spare);
+            // Emptied, but with its capacity
+            self.spare[index].sections = sections;
+            index += 1;
+        }
     }
-}
 
-impl ser::Serializer for &mut StructDetector {
-    type Ok = ();
-    type Error = Error;
-    type SerializeSeq = Self;
-    type SerializeTuple = Self;
-    type SerializeTupleStruct = Self;
-    type SerializeTupleVariant = Self;
-    type SerializeMap = Self;
-    type SerializeStruct = Self;
-    type SerializeStructVariant = Self;
+    /// Returns a reference to the writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
 
-    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
-        self.is_struct = true;
-        Ok(self)
+    /// Returns a mutable reference to the writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
     }
 
-    // All other methods just return Ok(())
-    fn serialize_bool(self, _v: bool) -> Result<()> {
-        Ok(())
+    /// Consumes the serializer, returning the writer.
+    pub fn into_inner(self) -> W {
+        self.writer
     }
-    fn serialize_i8(self, _v: i8) -> Result<()> {
-        Ok(())
+
+    // Write a struct field or map entry, either as a key/value line or as a section
+    fn write_entry<T>(&mut self, key: &str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.write_entry_value(key, value)
+            .map_err(|e| self.field_error(e, key))
     }
-    fn serialize_i16(self, _v: i16) -> Result<()> {
-        Ok(())
+
+    fn field_error(&self, error: Error, key: &str) -> Error {
+        let section = self.current_section.as_deref().unwrap_or_default();
+        error.at_field(section, key)
     }
-    fn serialize_i32(self, _v: i32) -> Result<()> {
-        Ok(())
+
+    // Nested sections recurse through here, once per level, so everything not generic over the
+    // value is done by separate functions to keep each level's stack small
+    fn write_entry_value<T>(&mut self, key: &str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        // First, detect if the value is a struct or map
+        let mut detector = SectionDetector::new(self.options.inline_maps);
+        let _ = value.serialize(&mut detector);
+
+        if detector.is_section {
+            let result = value.serialize(self.new_section_for(key)?);
+            self.reclaim_spare();
+            result
+        } else if detector.is_repeated_section {
+            // Each element gets a section of the same name, see `SerializeSeq`
+            self.repeated_section = Some(self.new_section_path(key)?);
+            let result = value.serialize(&mut *self);
+            self.repeated_section = None;
+            result
+        } else {
+            self.write_value_line(key, value)
+        }
     }
-    fn serialize_i64(self, _v: i64) -> Result<()> {
-        Ok(())
+
+    // The path and description of the section written for the field `key`
+    fn new_section_path(&self, key: &str) -> Result<(String, Option<&'static str>)> {
+        let key = &*self.options.key_transform.apply(key);
+
+        // Nested structs and maps are written as a section, with the path of the parent section
+        // as prefix, e.g. `[server.tls]`
+        let path = match self.current_section.as_deref() {
+            Some(parent) if !parent.is_empty() => format!("{parent}.{key}"),
+            _ => key.to_string(),
+        };
+
+        // Distinct names may be spelled the same after the key transform, e.g. `db` and `DB`
+        if self.options.key_transform != KeyTransform::None
+            && self
+                .sections
+                .iter()
+                .any(|section| section.current_section.as_ref() == Some(&path))
+        {
+            return Err(Error::DuplicateSection { name: path });
+        }
+
+        Ok((path, self.description(key)))
     }
-    fn serialize_u8(self, _v: u8) -> Result<()> {
-        Ok(())
+
+    fn description(&self, key: &str) -> Option<&'static str> {
+        let describe = self.describe?;
+        describe(self.current_section.as_deref().unwrap_or_default(), key)
     }
-    fn serialize_u16(self, _v: u16) -> Result<()> {
+
+    // Write a regular value or Option as a key/value line. Kept apart from `write_entry_value`,
+    // which nested sections recurse through, so its locals don't add to the stack of each level.
+    fn write_value_line<T>(&mut self, key: &str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = &*self.options.key_transform.apply(key);
+        let description = self.description(key);
+        let key = &*self.options.escape_key(key)?;
+        let mut value_serializer = ValueSerializer::new(&self.options);
+        value.serialize(&mut value_serializer)?;
+        let ValueSerializer {
+            output, none, flag, ..
+        } = value_serializer;
+
+        if let Some(description) = description
+            && !(none && self.options.option_style == OptionStyle::Omit)
+        {
+            self.push_line(Line::Comment(description))?;
+        }
+
+        if none {
+            // This was None, fields of a struct that are sections elsewhere are left out
+            // when the section is written
+            if self.options.option_style != OptionStyle::Omit {
+                self.write_unset_key(key)?;
+            }
+        } else if flag {
+            self.push_line(Line::Bare {
+                key: key.to_string(),
+            })?;
+        } else {
+            // This was Some(value) or a regular value
+            self.write_key_value(key, output)?;
+        }
         Ok(())
     }
-    fn serialize_u32(self, _v: u32) -> Result<()> {
-        Ok(())
+
+    // Remember the fields written as a section, given the number of sections before the field
+    fn record_section_field(&mut self, key: &'static str, sections: usize) {
+        if self.sections.len() > sections
+            && let Some(name) = self.struct_name
+        {
+            let key = self.options.key_transform.apply(key).into_owned();
+            self.section_fields.push((name, key));
+        }
     }
-    fn serialize_u64(self, _v: u64) -> Result<()> {
+
+    // Add an empty section for the field `key`
+    fn new_section_for(&mut self, key: &str) -> Result<&mut Serializer<Vec<u8>>> {
+        let (path, description) = self.new_section_path(key)?;
+        self.new_section(path, description)
+    }
+
+    // Add an empty section to serialize a value into, held back as keys following it still
+    // belong to the parent. It is kept on the heap rather than the stack of each level.
+    fn new_section(
+        &mut self,
+        path: String,
+        description: Option<&'static str>,
+    ) -> Result<&mut Serializer<Vec<u8>>> {
+        if self.depth >= self.options.max_depth {
+            return Err(too_deep(self.options.max_depth));
+        }
+        let mut section = self.spare.pop().unwrap_or_else(|| {
+            let mut section = Serializer::with_options(Vec::new(), self.options.clone());
+            section.nested = true;
+            section
+        });
+        section.describe = self.describe;
+        section.depth = self.depth + 1;
+        // Its subsections are taken from the same pool
+        section.spare = std::mem::take(&mut self.spare);
+        section.write_section_header(&path, description)?;
+        section.current_section = Some(path);
+        self.sections.push(section);
+        Ok(self.sections.last_mut().expect("section was just added"))
+    }
+
+    // Take back the pool lent to the section serialized last
+    fn reclaim_spare(&mut self) {
+        if let Some(section) = self.sections.last_mut() {
+            self.spare = std::mem::take(&mut section.spare);
+        }
+    }
+
+    fn write_key_value(&mut self, key: &str, value: String) -> Result<()> {
+        self.push_line(Line::KeyValue {
+            key: key.to_string(),
+            value,
+        })
+    }
+
+    fn write_unset_key(&mut self, key: &str) -> Result<()> {
+        self.push_line(Line::Unset {
+            key: key.to_string(),
+        })
+    }
+
+    fn write_section_header(&mut self, name: &str, description: Option<&str>) -> Result<()> {
+        self.flush_lines()?;
+        let eol = self.options.line_ending.as_str();
+        if self.options.blank_line_between_sections {
+            write!(self.writer, "{eol}")?;
+        }
+        if let Some(description) = description {
+            write_comment(
+                &mut self.writer,
+                self.options.comment_char,
+                description,
+                eol,
+            )?;
+        }
+        if self.options.header_padding {
+            write!(self.writer, "[ {name} ]{eol}")?;
+        } else {
+            write!(self.writer, "[{name}]{eol}")?;
+        }
         Ok(())
     }
-    fn serialize_f32(self, _v: f32) -> Result<()> {
+
+    fn push_line(&mut self, line: Line) -> Result<()> {
+        self.lines.push(line);
+
+        // Aligned lines have to wait for the whole section and sections for the root, root keys
+        // are written right away
+        if self.options.align || self.nested {
+            Ok(())
+        } else {
+            self.flush_lines()
+        }
+    }
+
+    // Enter the root section, unless a struct or map is serialized within it
+    fn begin_root(&mut self) -> Result<()> {
+        if self.current_section.is_none() {
+            self.current_section = Some("".to_string());
+            if let Some(name) = self.options.root_section.clone() {
+                self.write_section_header(&name, None)?;
+            }
+        }
         Ok(())
     }
-    fn serialize_f64(self, _v: f64) -> Result<()> {
+
+    // Write out the buffered lines followed by the held back sections
+    fn finish(&mut self) -> Result<()> {
+        // Sections stay buffered until the root is done, once all fields written as sections are
+        // known
+        if self.nested {
+            return Ok(());
+        }
+
+        // Sections are walked with a stack rather than recursion, as they may be nested
+        // arbitrarily deep
+        let mut section_fields = std::mem::take(&mut self.section_fields);
+        let mut pending: Vec<&Serializer<Vec<u8>>> = self.sections.iter().collect();
+        while let Some(section) = pending.pop() {
+            section_fields.extend(section.section_fields.iter().cloned());
+            pending.extend(&section.sections);
+        }
+        // A recursive struct adds the same field once per level
+        section_fields.sort_unstable();
+        section_fields.dedup();
+
+        self.flush_lines()?;
+        self.order_sections();
+
+        // Each section is written before its subsections, straight into the output
+        let mut pending = std::mem::take(&mut self.sections);
+        pending.reverse();
+        while let Some(mut section) = pending.pop() {
+            section.render(&section_fields)?;
+            self.writer.write_all(&section.writer)?;
+            pending.extend(section.sections.drain(..).rev());
+            self.recycle(section);
+        }
+        self.sections = pending;
+        section_fields.clear();
+        self.section_fields = section_fields;
+        Ok(())
+    }
+
+    // Arrange the held back sections as requested by the options
+    fn order_sections(&mut self) {
+        match &self.options.section_order {
+            SectionOrder::Declaration => {}
+            SectionOrder::Alphabetical => self
+                .sections
+                .sort_by(|a, b| a.current_section.cmp(&b.current_section)),
+            // Stable, so unlisted sections keep their order
+            SectionOrder::Explicit(names) => self.sections.sort_by_key(|section| {
+                names
+                    .iter()
+                    .position(|name| section.current_section.as_ref() == Some(name))
+                    .unwrap_or(names.len())
+            }),
+        }
+    }
+
+    // Write out the buffered lines of the current section
+    fn flush_lines(&mut self) -> Result<()> {
+        let commented = self.options.option_style == OptionStyle::Commented;
+        let width = if self.options.align {
+            self.lines
+                .iter()
+                .map(|line| match line {
+                    // Comments take a line of their own
+                    Line::Comment(_) => 0,
+                    Line::KeyValue { key, .. } => key.chars().count(),
+                    Line::Unset { key } if commented => key.chars().count() + 2,
+                    Line::Unset { key } => key.chars().count(),
+                    // Nothing follows a bare key to line up
+                    Line::Bare { .. } => 0,
+                })
+                .max()
+                .unwrap_or(0)
+        } else {
+            0
+        };
+
+        let comment_char = self.options.comment_char;
+        let eol = self.options.line_ending.as_str();
+        let delimiter = if self.options.space_around_delimiter {
+            format!(" {} ", self.options.delimiter)
+        } else {
+            self.options.delimiter.to_string()
+        };
+
+        for line in self.lines.drain(..) {
+            match line {
+                Line::Comment(text) => {
+                    write_comment(&mut self.writer, comment_char, text, eol)?;
+                }
+                Line::KeyValue { key, value } => {
+                    write!(self.writer, "{key:<width$}{delimiter}{value}{eol}")?;
+                }
+                Line::Bare { key } => {
+                    write!(self.writer, "{key}{eol}")?;
+                }
+                Line::Unset { key } if commented => {
+                    let width = width.saturating_sub(2);
+                    write!(self.writer, "{comment_char} {key:<width$}{delimiter}{eol}")?;
+                }
+                Line::Unset { key } => {
+                    write!(self.writer, "{key:<width$}{delimiter}{eol}")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Write each line of a description as a comment
+fn write_comment<W: io::Write>(
+    writer: &mut W,
+    comment_char: char,
+    text: &str,
+    eol: &str,
+) -> io::Result<()> {
+    for line in text.lines() {
+        match line.trim_end() {
+            "" => write!(writer, "{comment_char}{eol}")?,
+            line => write!(writer, "{comment_char} {line}{eol}")?,
+        }
+    }
+    Ok(())
+}
+
+impl Serializer<Vec<u8>> {
+    // Write out the lines of the section and order its subsections, leaving out `None` fields
+    // that are written as a section elsewhere, as in a struct holding optional copies of itself.
+    // A `None` can't tell whether it stands for a section, so this goes by the struct the field
+    // is in.
+    fn render(&mut self, section_fields: &[(&'static str, String)]) -> Result<()> {
+        if let Some(name) = self.struct_name {
+            let mut lines = Vec::with_capacity(self.lines.len());
+            for line in self.lines.drain(..) {
+                match &line {
+                    Line::Unset { key }
+                        if section_fields
+                            .iter()
+                            .any(|(struct_name, field)| *struct_name == name && field == key) =>
+                    {
+                        // Along with its description
+                        if matches!(lines.last(), Some(Line::Comment(_))) {
+                            lines.pop();
+                        }
+                    }
+                    _ => lines.push(line),
+                }
+            }
+            self.lines = lines;
+        }
+        self.flush_lines()?;
+        self.order_sections();
+        Ok(())
+    }
+}
+
+impl<W: io::Write> ser::Serializer for &mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = ser::Impossible<(), Error>;
+    type SerializeTupleStruct = ser::Impossible<(), Error>;
+    type SerializeTupleVariant = ser::Impossible<(), Error>;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = ser::Impossible<(), Error>;
+
+    fn serialize_bool(self, _v: bool) -> Result<()> {
+        Err(Error::unsupported_root("bool"))
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<()> {
+        Err(Error::unsupported_root("i8"))
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<()> {
+        Err(Error::unsupported_root("i16"))
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<()> {
+        Err(Error::unsupported_root("i32"))
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<()> {
+        Err(Error::unsupported_root("i64"))
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<()> {
+        Err(Error::unsupported_root("u8"))
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<()> {
+        Err(Error::unsupported_root("u16"))
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<()> {
+        Err(Error::unsupported_root("u32"))
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<()> {
+        Err(Error::unsupported_root("u64"))
+    }
+
+    fn serialize_i128(self, _v: i128) -> Result<()> {
+        Err(Error::unsupported_root("i128"))
+    }
+
+    fn serialize_u128(self, _v: u128) -> Result<()> {
+        Err(Error::unsupported_root("u128"))
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<()> {
+        Err(Error::unsupported_root("f32"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<()> {
+        Err(Error::unsupported_root("f64"))
+    }
+
+    fn serialize_char(self, _v: char) -> Result<()> {
+        Err(Error::unsupported_root("char"))
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<()> {
+        Err(Error::unsupported_root("str"))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<()> {
+        Err(Error::unsupported_root("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Err(Error::unsupported_root("none"))
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Err(Error::unsupported_root("unit"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Err(Error::unsupported_root("unit struct"))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        Err(Error::unsupported_root("enum variant"))
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(Error::unsupported_root("enum variant"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        // Only the elements of a field can be written as sections, not those of the root
+        match self.repeated_section {
+            Some(_) => Ok(self),
+            None => Err(Error::unsupported_root("sequence")),
+        }
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(Error::unsupported_root("tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(Error::unsupported_root("tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(Error::unsupported_root("tuple variant"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        // Like structs, map entries become keys and sections
+        self.begin_root()?;
+        Ok(self)
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        // Don't create section headers here - they're created in serialize_field
+        self.begin_root()?;
+        self.struct_name = Some(name);
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(Error::unsupported_root("struct variant"))
+    }
+}
+
+// Elements of a sequence of structs or maps, each written as a section of the same name
+impl<W: io::Write> ser::SerializeSeq for &mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let (path, description) = match &mut self.repeated_section {
+            // Only the first of the sections is described
+            Some((path, description)) => (path.clone(), description.take()),
+            None => Default::default(),
+        };
+        let result = value.serialize(self.new_section(path, description)?);
+        self.reclaim_spare();
+        result?;
+
+        // `[servers.tls]` would be shared by all `[servers]` sections when read back
+        if self
+            .sections
+            .last()
+            .is_some_and(|section| !section.sections.is_empty())
+        {
+            return Err(Error::unsupported("sections within repeated sections"));
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: io::Write> ser::SerializeMap for &mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut key_serializer = MapKeySerializer::new();
+        key.serialize(&mut key_serializer)?;
+        self.map_key = Some(key_serializer.output);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = self.map_key.take().unwrap_or_default();
+        self.write_entry(&key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: io::Write> ser::SerializeStruct for &mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let sections = self.sections.len();
+        self.write_entry(key, value)?;
+        self.record_section_field(key, sections);
+        Ok(())
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+// Serialize a map key, which has to be a plain scalar
+pub(crate) fn key_to_string<T>(key: &T) -> Result<String>
+where
+    T: ?Sized + Serialize,
+{
+    let mut key_serializer = MapKeySerializer::new();
+    key.serialize(&mut key_serializer)?;
+    Ok(key_serializer.output)
+}
+
+// Serialize a single value as written after a key, `None` for values written as a commented line
+pub(crate) fn value_to_string<T>(value: &T, options: &SerializeOptions) -> Result<Option<String>>
+where
+    T: ?Sized + Serialize,
+{
+    let mut serializer = ValueSerializer::new(options);
+    value.serialize(&mut serializer)?;
+    Ok((!serializer.none).then_some(serializer.output))
+}
+
+// Serializer for the escaped value on the right hand side of a key
+struct ValueSerializer<'a> {
+    output: String,
+    options: &'a SerializeOptions,
+    // Set for None and unit values, which are written as commented lines
+    none: bool,
+    // Set for `true` written as a bare key
+    flag: bool,
+    // Sequence elements and inline map values can't be sequences or maps themselves
+    in_seq: bool,
+    elements: usize,
+    // Key of the inline map entry whose value is serialized next
+    map_key: Option<String>,
+}
+
+impl<'a> ValueSerializer<'a> {
+    fn new(options: &'a SerializeOptions) -> Self {
+        ValueSerializer {
+            output: String::new(),
+            options,
+            none: false,
+            flag: false,
+            in_seq: false,
+            elements: 0,
+            map_key: None,
+        }
+    }
+
+    fn escape_value(&self, value: &str) -> Result<String> {
+        if !self.options.escape {
+            // A line break would end the line in the middle of the value, and an unescaped
+            // trailing backslash would continue it on the next line
+            let backslashes = value.chars().rev().take_while(|&c| c == '\\').count();
+            return if value.contains(['\n', '\r']) || backslashes % 2 == 1 {
+                Err(Error::UnrepresentableValue {
+                    value: value.to_string(),
+                })
+            } else {
+                Ok(value.to_string())
+            };
+        }
+
+        if self.options.escape_mode == EscapeMode::Percent {
+            return Ok(percent_encode(value, self.options.inline_comments));
+        }
+
+        let escaped = value
+            .replace('\\', "\\\\")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r")
+            .replace('\t', "\\t")
+            .replace('"', "\\\"");
+
+        Ok(match self.options.inline_comments {
+            InlineComments::Never => escaped,
+            InlineComments::Anywhere => escaped.replace(';', "\\;").replace('#', "\\#"),
+            InlineComments::AfterWhitespace => escape_after_whitespace(&escaped),
+        })
+    }
+
+    // The key of an inline map entry ends at the first `=`, so one within it is escaped as well
+    fn escape_entry_key(&self, key: &str) -> Result<String> {
+        if !self.options.escape && key.contains('=') {
+            return Err(Error::UnrepresentableValue {
+                value: key.to_string(),
+            });
+        }
+        let escaped = self.escape_value(key)?;
+        Ok(match (self.options.escape, self.options.escape_mode) {
+            (false, _) => escaped,
+            (true, EscapeMode::Backslash) => escaped.replace('=', "\\="),
+            (true, EscapeMode::Percent) => escaped.replace('=', "%3D"),
+        })
+    }
+
+    fn push_float(&mut self, v: impl fmt::Display, nan: bool) {
+        // Rust writes `NaN`, use the same lowercase spelling as `inf`
+        if nan {
+            self.output.push_str("nan");
+            return;
+        }
+
+        match self.options.float_decimals {
+            Some(decimals) => {
+                let rounded = format!("{v:.decimals$}");
+                // Drop the zeros padding the rounded value to the full number of decimals
+                if rounded.contains('.') {
+                    let trimmed = rounded.trim_end_matches('0').trim_end_matches('.');
+                    self.output.push_str(trimmed);
+                } else {
+                    self.output.push_str(&rounded);
+                }
+            }
+            None => self.output.push_str(&v.to_string()),
+        }
+    }
+
+    // Serialize an element of a sequence or inline map, which can't be a sequence itself
+    fn element<T>(&self, value: &T) -> Result<String>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut element = ValueSerializer::new(self.options);
+        element.in_seq = true;
+        value.serialize(&mut element)?;
+        Ok(element.output)
+    }
+
+    // Append an element after the separator, quoting it where needed
+    fn push_element(&mut self, element: &str) {
+        if self.elements > 0 {
+            self.output.push_str(&self.options.seq_separator);
+        }
+        if self.needs_quotes(element) {
+            self.output.push('"');
+            self.output.push_str(element);
+            self.output.push('"');
+        } else {
+            self.output.push_str(element);
+        }
+        self.elements += 1;
+    }
+
+    // Quote elements that would otherwise be split or trimmed when read back
+    fn needs_quotes(&self, element: &str) -> bool {
+        let separator = self.options.seq_separator.trim();
+        let contains_separator = if separator.is_empty() {
+            element.contains(char::is_whitespace)
+        } else {
+            element.contains(separator)
+        };
+
+        element.is_empty()
+            || contains_separator
+            || element.starts_with(char::is_whitespace)
+            || element.ends_with(char::is_whitespace)
+    }
+}
+
+// Percent-encode the characters that are escaped with a backslash otherwise. The backslash itself
+// is encoded too, as it still escapes comment characters and continues lines when reading.
+fn percent_encode(value: &str, inline_comments: InlineComments) -> String {
+    let mut output = String::with_capacity(value.len());
+    for c in value.chars() {
+        let comment = matches!(c, ';' | '#') && inline_comments != InlineComments::Never;
+        if comment || c.is_ascii_control() || matches!(c, '%' | '\\' | '"') {
+            push_percent_encoded(&mut output, c);
+        } else {
+            output.push(c);
+        }
+    }
+    output
+}
+
+fn push_percent_encoded(output: &mut String, c: char) {
+    for byte in c.encode_utf8(&mut [0; 4]).bytes() {
+        output.push_str(&format!("%{byte:02X}"));
+    }
+}
+
+// Escape `;` and `#` where they'd start a comment for `InlineComments::AfterWhitespace`, which
+// includes the start of the value, following the whitespace after the delimiter
+fn escape_after_whitespace(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    let mut after_whitespace = true;
+
+    for c in value.chars() {
+        if (c == ';' || c == '#') && after_whitespace {
+            output.push('\\');
+        }
+        output.push(c);
+        after_whitespace = c.is_whitespace();
+    }
+
+    output
+}
+
+impl ser::Serializer for &mut ValueSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = ser::Impossible<(), Error>;
+    type SerializeMap = Self;
+    type SerializeStruct = ser::Impossible<(), Error>;
+    type SerializeStructVariant = ser::Impossible<(), Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        if v && self.options.bare_flags && !self.in_seq {
+            self.flag = true;
+            return Ok(());
+        }
+        self.output.push_str(self.options.bool_style.spelling(v));
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.output.push_str(&v.to_string());
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.output.push_str(&v.to_string());
+        Ok(())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.output.push_str(&v.to_string());
+        Ok(())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        self.output.push_str(&v.to_string());
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        // Not widened to f64, which would write `1.1` as `1.100000023841858`
+        self.push_float(v, v.is_nan());
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.push_float(v, v.is_nan());
         Ok(())
     }
-    fn serialize_char(self, _v: char) -> Result<()> {
-        Ok(())
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
     }
-    fn serialize_str(self, _v: &str) -> Result<()> {
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        let escaped = self.escape_value(v)?;
+        // Values are trimmed when read back, unless quoted. Sequence elements are quoted as a whole.
+        // An empty string is quoted where an empty value stands for `None`.
+        let padded = v.starts_with(char::is_whitespace) || v.ends_with(char::is_whitespace);
+        let empty = v.is_empty() && self.options.option_style == OptionStyle::EmptyValue;
+        if !self.in_seq && (padded || empty) {
+            self.output.push('"');
+            self.output.push_str(&escaped);
+            self.output.push('"');
+        } else {
+            self.output.push_str(&escaped);
+        }
         Ok(())
     }
-    fn serialize_bytes(self, _v: &[u8]) -> Result<()> {
-        Ok(())
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        // Binary data isn't necessarily valid UTF-8, encode it losslessly where possible
+        #[cfg(feature = "base64")]
+        {
+            use base64::Engine;
+            self.serialize_str(&base64::engine::general_purpose::STANDARD.encode(v))
+        }
+        #[cfg(not(feature = "base64"))]
+        self.serialize_str(&String::from_utf8_lossy(v))
     }
+
     fn serialize_none(self) -> Result<()> {
+        self.none = true;
         Ok(())
     }
+
     fn serialize_some<T>(self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
         value.serialize(self)
     }
+
     fn serialize_unit(self) -> Result<()> {
+        self.none = true;
         Ok(())
     }
+
     fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.none = true;
         Ok(())
     }
+
     fn serialize_unit_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
     ) -> Result<()> {
-        Ok(())
+        self.serialize_str(variant)
     }
+
     fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
         value.serialize(self)
     }
+
     fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
@@ -393,21 +1916,29 @@ impl ser::Serializer for &mut StructDetector {
     where
         T: ?Sized + Serialize,
     {
-        Ok(())
+        Err(Error::unsupported("enum variants"))
     }
+
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        if self.in_seq {
+            return Err(Error::unsupported("nested sequences"));
+        }
         Ok(self)
     }
-    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
-        Ok(self)
+
+    // Tuples and arrays are written like sequences
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
     }
+
     fn serialize_tuple_struct(
         self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        Ok(self)
+        self.serialize_seq(Some(len))
     }
+
     fn serialize_tuple_variant(
         self,
         _name: &'static str,
@@ -415,11 +1946,20 @@ impl ser::Serializer for &mut StructDetector {
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        Ok(self)
+        Err(Error::unsupported("tuple variants"))
     }
+
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        if self.in_seq {
+            return Err(Error::unsupported("nested maps"));
+        }
         Ok(self)
     }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(Error::unsupported("structs in values"))
+    }
+
     fn serialize_struct_variant(
         self,
         _name: &'static str,
@@ -427,150 +1967,118 @@ impl ser::Serializer for &mut StructDetector {
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        Ok(self)
+        Err(Error::unsupported("struct variants"))
     }
 }
 
-impl ser::SerializeSeq for &mut StructDetector {
+impl ser::SerializeSeq for &mut ValueSerializer<'_> {
     type Ok = ();
     type Error = Error;
-    fn serialize_element<T>(&mut self, _value: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        Ok(())
-    }
-    fn end(self) -> Result<()> {
-        Ok(())
-    }
-}
 
-impl ser::SerializeTuple for &mut StructDetector {
-    type Ok = ();
-    type Error = Error;
-    fn serialize_element<T>(&mut self, _value: &T) -> Result<()>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
+        let element = self.element(value)?;
+        self.push_element(&element);
         Ok(())
     }
-    fn end(self) -> Result<()> {
-        Ok(())
-    }
-}
 
-impl ser::SerializeTupleStruct for &mut StructDetector {
-    type Ok = ();
-    type Error = Error;
-    fn serialize_field<T>(&mut self, _value: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        Ok(())
-    }
     fn end(self) -> Result<()> {
         Ok(())
     }
 }
 
-impl ser::SerializeTupleVariant for &mut StructDetector {
+impl ser::SerializeTuple for &mut ValueSerializer<'_> {
     type Ok = ();
     type Error = Error;
-    fn serialize_field<T>(&mut self, _value: &T) -> Result<()>
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        Ok(())
+        ser::SerializeSeq::serialize_element(self, value)
     }
+
     fn end(self) -> Result<()> {
         Ok(())
     }
 }
 
-impl ser::SerializeMap for &mut StructDetector {
+impl ser::SerializeTupleStruct for &mut ValueSerializer<'_> {
     type Ok = ();
     type Error = Error;
-    fn serialize_key<T>(&mut self, _key: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        Ok(())
-    }
-    fn serialize_value<T>(&mut self, _value: &T) -> Result<()>
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        Ok(())
+        ser::SerializeSeq::serialize_element(self, value)
     }
+
     fn end(self) -> Result<()> {
         Ok(())
     }
 }
 
-impl ser::SerializeStruct for &mut StructDetector {
+// Inline maps are written as separator-joined `key=value` elements
+impl ser::SerializeMap for &mut ValueSerializer<'_> {
     type Ok = ();
     type Error = Error;
-    fn serialize_field<T>(&mut self, _key: &'static str, _value: &T) -> Result<()>
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
+        let mut key_serializer = MapKeySerializer::new();
+        key.serialize(&mut key_serializer)?;
+        self.map_key = Some(key_serializer.output);
         Ok(())
     }
-    fn end(self) -> Result<()> {
-        Ok(())
-    }
-}
 
-impl ser::SerializeStructVariant for &mut StructDetector {
-    type Ok = ();
-    type Error = Error;
-    fn serialize_field<T>(&mut self, _key: &'static str, _value: &T) -> Result<()>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
+        let key = self.map_key.take().unwrap_or_default();
+        let key = self.escape_entry_key(&key)?;
+        let value = self.element(value)?;
+        self.push_element(&format!("{key}={value}"));
         Ok(())
     }
+
     fn end(self) -> Result<()> {
         Ok(())
     }
 }
 
-impl Serializer {
-    fn escape_value(value: &str) -> String {
-        value
-            .replace('\\', "\\\\")
-            .replace('\n', "\\n")
-            .replace('\r', "\\r")
-            .replace('\t', "\\t")
-            .replace('"', "\\\"")
-            .replace(';', "\\;")
-            .replace('#', "\\#")
-    }
+// Serializer for map keys, which have to be plain scalars
+struct MapKeySerializer {
+    output: String,
+}
 
-    fn write_key_value(&mut self, key: &str, value: &str) {
-        self.output.push_str(key);
-        self.output.push_str(" = ");
-        self.output.push_str(&Self::escape_value(value));
-        self.output.push('\n');
+impl MapKeySerializer {
+    fn new() -> Self {
+        MapKeySerializer {
+            output: String::new(),
+        }
     }
+}
 
-    fn write_commented_key(&mut self, key: &str) {
-        self.output.push_str("; ");
-        self.output.push_str(key);
-        self.output.push_str(" = \n");
-    }
+fn unsupported_key() -> Error {
+    Error::unsupported("non-scalar map keys")
 }
 
-impl ser::Serializer for &mut Serializer {
+impl ser::Serializer for &mut MapKeySerializer {
     type Ok = ();
     type Error = Error;
-    type SerializeSeq = Self;
-    type SerializeTuple = Self;
-    type SerializeTupleStruct = Self;
-    type SerializeTupleVariant = Self;
-    type SerializeMap = Self;
-    type SerializeStruct = Self;
-    type SerializeStructVariant = Self;
+    type SerializeSeq = ser::Impossible<(), Error>;
+    type SerializeTuple = ser::Impossible<(), Error>;
+    type SerializeTupleStruct = ser::Impossible<(), Error>;
+    type SerializeTupleVariant = ser::Impossible<(), Error>;
+    type SerializeMap = ser::Impossible<(), Error>;
+    type SerializeStruct = ser::Impossible<(), Error>;
+    type SerializeStructVariant = ser::Impossible<(), Error>;
 
     fn serialize_bool(self, v: bool) -> Result<()> {
         self.output.push_str(if v { "true" } else { "false" });
@@ -611,15 +2119,24 @@ impl ser::Serializer for &mut Serializer {
         Ok(())
     }
 
-    fn serialize_f32(self, v: f32) -> Result<()> {
-        self.serialize_f64(f64::from(v))
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.output.push_str(&v.to_string());
+        Ok(())
     }
 
-    fn serialize_f64(self, v: f64) -> Result<()> {
+    fn serialize_u128(self, v: u128) -> Result<()> {
         self.output.push_str(&v.to_string());
         Ok(())
     }
 
+    fn serialize_f32(self, _v: f32) -> Result<()> {
+        Err(unsupported_key())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<()> {
+        Err(unsupported_key())
+    }
+
     fn serialize_char(self, v: char) -> Result<()> {
         self.output.push(v);
         Ok(())
@@ -630,27 +2147,27 @@ impl ser::Serializer for &mut Serializer {
         Ok(())
     }
 
-    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-        self.serialize_str(&String::from_utf8_lossy(v))
+    fn serialize_bytes(self, _v: &[u8]) -> Result<()> {
+        Err(unsupported_key())
     }
 
     fn serialize_none(self) -> Result<()> {
-        Ok(())
+        Err(unsupported_key())
     }
 
-    fn serialize_some<T>(self, value: &T) -> Result<()>
+    fn serialize_some<T>(self, _value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(self)
+        Err(unsupported_key())
     }
 
     fn serialize_unit(self) -> Result<()> {
-        Ok(())
+        Err(unsupported_key())
     }
 
     fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
-        Ok(())
+        Err(unsupported_key())
     }
 
     fn serialize_unit_variant(
@@ -679,15 +2196,15 @@ impl ser::Serializer for &mut Serializer {
     where
         T: ?Sized + Serialize,
     {
-        Err(Error::UnsupportedFeature("enum variants".to_string()))
+        Err(unsupported_key())
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
-        Err(Error::UnsupportedFeature("sequences".to_string()))
+        Err(unsupported_key())
     }
 
     fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
-        Err(Error::UnsupportedFeature("tuples".to_string()))
+        Err(unsupported_key())
     }
 
     fn serialize_tuple_struct(
@@ -695,7 +2212,7 @@ impl ser::Serializer for &mut Serializer {
         _name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        Err(Error::UnsupportedFeature("tuple structs".to_string()))
+        Err(unsupported_key())
     }
 
     fn serialize_tuple_variant(
@@ -705,19 +2222,15 @@ impl ser::Serializer for &mut Serializer {
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        Err(Error::UnsupportedFeature("tuple variants".to_string()))
+        Err(unsupported_key())
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        Ok(self)
+        Err(unsupported_key())
     }
 
     fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
-        // Don't create section headers here - they're created in serialize_field
-        if self.current_section.is_none() {
-            self.current_section = Some("".to_string());
-        }
-        Ok(self)
+        Err(unsupported_key())
     }
 
     fn serialize_struct_variant(
@@ -727,173 +2240,6 @@ impl ser::Serializer for &mut Serializer {
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        Err(Error::UnsupportedFeature("struct variants".to_string()))
-    }
-}
-
-impl ser::SerializeSeq for &mut Serializer {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_element<T>(&mut self, _value: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        Err(Error::UnsupportedFeature("sequences".to_string()))
-    }
-
-    fn end(self) -> Result<()> {
-        Ok(())
-    }
-}
-
-impl ser::SerializeTuple for &mut Serializer {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_element<T>(&mut self, _value: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        Err(Error::UnsupportedFeature("tuples".to_string()))
-    }
-
-    fn end(self) -> Result<()> {
-        Ok(())
-    }
-}
-
-impl ser::SerializeTupleStruct for &mut Serializer {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_field<T>(&mut self, _value: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        Err(Error::UnsupportedFeature("tuple structs".to_string()))
-    }
-
-    fn end(self) -> Result<()> {
-        Ok(())
-    }
-}
-
-impl ser::SerializeTupleVariant for &mut Serializer {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_field<T>(&mut self, _value: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        Err(Error::UnsupportedFeature("tuple variants".to_string()))
-    }
-
-    fn end(self) -> Result<()> {
-        Ok(())
-    }
-}
-
-impl ser::SerializeMap for &mut Serializer {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_key<T>(&mut self, _key: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        Ok(())
-    }
-
-    fn serialize_value<T>(&mut self, _value: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        Ok(())
-    }
-
-    fn end(self) -> Result<()> {
-        Ok(())
-    }
-}
-
-impl ser::SerializeStruct for &mut Serializer {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        // First, detect if the value is a struct
-        let mut detector = StructDetector::new();
-        let _ = value.serialize(&mut detector);
-
-        if detector.is_struct {
-            // This is a nested struct - serialize it as a section
-            if !self.output.is_empty() && !self.output.ends_with('\n') {
-                self.output.push('\n');
-            }
-            self.output.push('[');
-            self.output.push_str(key);
-            self.output.push_str("]\n");
-
-            // Serialize the struct's fields
-            let mut nested_serializer = Serializer {
-                output: String::new(),
-                current_section: Some(key.to_string()),
-                section_names: self.section_names.clone(),
-            };
-            value.serialize(&mut nested_serializer)?;
-
-            // Add the fields (the nested serializer won't have section headers)
-            self.output.push_str(&nested_serializer.output);
-        } else {
-            // Regular value or Option
-            let mut temp_serializer = Serializer {
-                output: String::new(),
-                current_section: self.current_section.clone(),
-                section_names: self.section_names.clone(),
-            };
-
-            match value.serialize(&mut temp_serializer) {
-                Ok(_) => {
-                    if temp_serializer.output.is_empty() {
-                        // This was None
-                        // Skip commented lines for fields that are section names
-                        if !self.section_names.contains(&key.to_string()) {
-                            self.write_commented_key(key);
-                        }
-                    } else {
-                        // This was Some(value) or a regular value
-                        self.write_key_value(key, &temp_serializer.output);
-                    }
-                }
-                Err(e) => return Err(e),
-            }
-        }
-
-        Ok(())
-    }
-
-    fn end(self) -> Result<()> {
-        Ok(())
-    }
-}
-
-impl ser::SerializeStructVariant for &mut Serializer {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_field<T>(&mut self, _key: &'static str, _value: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        Err(Error::UnsupportedFeature("struct variants".to_string()))
-    }
-
-    fn end(self) -> Result<()> {
-        Ok(())
+        Err(unsupported_key())
     }
 }
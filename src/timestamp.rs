@@ -0,0 +1,136 @@
+//! Timestamps as RFC 3339 values, for use with `#[serde(with = "serini::timestamp")]`.
+//!
+//! Timestamps are written like `2024-05-01T12:30:00.123456789Z`, with as many fractional digits as
+//! needed to keep the full precision, and read back with any offset:
+//!
+//! ```rust
+//! use chrono::{DateTime, Utc};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, Serialize, Deserialize, PartialEq)]
+//! struct Job {
+//!     #[serde(with = "serini::timestamp")]
+//!     started: DateTime<Utc>,
+//!     #[serde(default, with = "serini::timestamp::option")]
+//!     finished: Option<DateTime<Utc>>,
+//! }
+//!
+//! let job: Job = serini::from_str("started = 2024-05-01T14:30:00+02:00\n").unwrap();
+//! assert_eq!(job.started.to_rfc3339(), "2024-05-01T12:30:00+00:00");
+//! assert_eq!(job.finished, None);
+//! assert_eq!(serini::from_str::<Job>(&serini::to_string(&job).unwrap()).unwrap(), job);
+//! ```
+//!
+//! Any `DateTime<Tz>` can be written. Reading works for time zones a `DateTime<FixedOffset>`
+//! converts into, such as `Utc` and `FixedOffset` itself.
+
+use chrono::{DateTime, FixedOffset, SecondsFormat, TimeZone};
+use serde::{Deserialize, Deserializer, Serializer, de};
+use std::{
+    fmt::{self, Display},
+    marker::PhantomData,
+};
+
+/// Serializes a timestamp as an RFC 3339 string.
+pub fn serialize<Tz, S>(value: &DateTime<Tz>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    Tz: TimeZone,
+    Tz::Offset: Display,
+    S: Serializer,
+{
+    serializer.serialize_str(&value.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+/// Deserializes a timestamp from an RFC 3339 string.
+pub fn deserialize<'de, Tz, D>(deserializer: D) -> Result<DateTime<Tz>, D::Error>
+where
+    Tz: TimeZone,
+    DateTime<Tz>: From<DateTime<FixedOffset>>,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_str(TimestampVisitor(PhantomData))
+}
+
+// Parses within the deserializer, so errors carry the line of the value
+struct TimestampVisitor<Tz>(PhantomData<Tz>);
+
+impl<Tz> de::Visitor<'_> for TimestampVisitor<Tz>
+where
+    Tz: TimeZone,
+    DateTime<Tz>: From<DateTime<FixedOffset>>,
+{
+    type Value = DateTime<Tz>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an RFC 3339 timestamp")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        DateTime::parse_from_rfc3339(v)
+            .map(DateTime::from)
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+    }
+}
+
+/// Optional timestamps, for use with `#[serde(with = "serini::timestamp::option")]`.
+///
+/// `None` is written like any other `None`, see [`OptionStyle`](crate::OptionStyle). Serde only
+/// reads a missing key as `None` for fields with `#[serde(default)]` once `with` is given.
+pub mod option {
+    use super::*;
+
+    /// Serializes an optional timestamp as an RFC 3339 string.
+    pub fn serialize<Tz, S>(value: &Option<DateTime<Tz>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        Tz: TimeZone,
+        Tz::Offset: Display,
+        S: Serializer,
+    {
+        match value {
+            Some(value) => serializer.serialize_some(&Timestamp(value)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// Deserializes an optional timestamp from an RFC 3339 string.
+    pub fn deserialize<'de, Tz, D>(deserializer: D) -> Result<Option<DateTime<Tz>>, D::Error>
+    where
+        Tz: TimeZone,
+        DateTime<Tz>: From<DateTime<FixedOffset>>,
+        D: Deserializer<'de>,
+    {
+        Ok(Option::<Timestamp<DateTime<Tz>>>::deserialize(deserializer)?.map(|value| value.0))
+    }
+
+    // Lets `Option` wrap the functions of the parent module
+    struct Timestamp<T>(T);
+
+    impl<Tz> serde::Serialize for Timestamp<&DateTime<Tz>>
+    where
+        Tz: TimeZone,
+        Tz::Offset: Display,
+    {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            super::serialize(self.0, serializer)
+        }
+    }
+
+    impl<'de, Tz> Deserialize<'de> for Timestamp<DateTime<Tz>>
+    where
+        Tz: TimeZone,
+        DateTime<Tz>: From<DateTime<FixedOffset>>,
+    {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            super::deserialize(deserializer).map(Timestamp)
+        }
+    }
+}
@@ -0,0 +1,818 @@
+use crate::{
+    DeserializeOptions, Error, SerializeOptions,
+    de::{ValueDeserializer, too_deep},
+    error::Result,
+    ser::{key_to_string, value_to_string},
+};
+use serde::{
+    Deserialize, Serialize,
+    de::{self, DeserializeOwned, IntoDeserializer, value::MapDeserializer},
+    ser::{self, SerializeMap},
+};
+use std::fmt;
+
+/// A document, section or value of any shape, for inspecting INI files without a target type.
+///
+/// A document is read as a [`Value::Section`] holding its root keys and top-level sections,
+/// each section holding its keys and subsections in turn. Booleans and numbers are read as such
+/// and everything else as a string, unless
+/// [`DeserializeOptions::infer_types`](crate::DeserializeOptions::infer_types) is turned off.
+///
+/// ```rust
+/// use serini::{Value, from_str};
+///
+/// let doc: Value = from_str("name = app\n[server]\nport = 8080\n").unwrap();
+/// let port = doc.get("server").and_then(|server| server.get("port"));
+/// assert_eq!(port.and_then(Value::as_integer), Some(8080));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Bool(bool),
+    Integer(i128),
+    Float(f64),
+    /// Keys and subsections in file order.
+    Section(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Returns the entry of a section with the given key, `None` for other values.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_section()?
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+
+    /// The string, if this is a [`Value::String`].
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The boolean, if this is a [`Value::Bool`].
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The integer, if this is a [`Value::Integer`].
+    pub fn as_integer(&self) -> Option<i128> {
+        match self {
+            Value::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The float, if this is a [`Value::Float`].
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The keys and subsections, if this is a [`Value::Section`].
+    pub fn as_section(&self) -> Option<&[(String, Value)]> {
+        match self {
+            Value::Section(entries) => Some(entries),
+            _ => None,
+        }
+    }
+}
+
+/// Converts a value to a [`Value`].
+///
+/// The value has to be a struct or map, like the root of a document. Strings stay strings, so
+/// `"007"` isn't turned into the number 7. Values written as one line that aren't a single
+/// boolean, number or string, like sequences, are kept as the string they are written as.
+/// Repeated sections are merged into one, as they are when read back.
+pub fn to_value<T>(value: &T) -> Result<Value>
+where
+    T: ?Sized + Serialize,
+{
+    match value.serialize(ValueSerializer { depth: 0 })? {
+        Entry::Value(value @ Value::Section(_)) => Ok(value),
+        Entry::Value(Value::String(_)) => Err(Error::unsupported_root("str")),
+        Entry::Value(Value::Bool(_)) => Err(Error::unsupported_root("bool")),
+        Entry::Value(Value::Integer(_)) => Err(Error::unsupported_root("integer")),
+        Entry::Value(Value::Float(_)) => Err(Error::unsupported_root("float")),
+        Entry::Text => Err(Error::unsupported_root("sequence")),
+        Entry::None => Err(Error::unsupported_root("option")),
+    }
+}
+
+/// Converts a [`Value`] to another type.
+///
+/// Strings read into an untyped field stay strings. Fields of other types read values by their
+/// INI spelling, so `Value::String("8080")` can be read into a `u16` and `Value::Integer(1)`
+/// into a `bool`.
+pub fn from_value<T>(value: Value) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    T::deserialize(value)
+}
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Value::String(value) => serializer.serialize_str(value),
+            Value::Bool(value) => serializer.serialize_bool(*value),
+            Value::Integer(value) => serializer.serialize_i128(*value),
+            Value::Float(value) => serializer.serialize_f64(*value),
+            Value::Section(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> de::Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an INI value or section")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> std::result::Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> std::result::Result<Value, E> {
+        Ok(Value::Integer(v.into()))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> std::result::Result<Value, E> {
+        Ok(Value::Integer(v.into()))
+    }
+
+    fn visit_i128<E>(self, v: i128) -> std::result::Result<Value, E> {
+        Ok(Value::Integer(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> std::result::Result<Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> std::result::Result<Value, E> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn visit_string<E>(self, v: String) -> std::result::Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> std::result::Result<Value, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        let mut entries = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(Value::Section(entries))
+    }
+}
+
+// What a value turns into: a `Value`, nothing for `None` and units, which are left out like the
+// commented lines they are written as, or the text of a line that is no single `Value`
+enum Entry {
+    Value(Value),
+    None,
+    Text,
+}
+
+// Builds a `Value` from a value serialized as a key's value or a section
+struct ValueSerializer {
+    // Number of sections a struct or map serialized here is nested in
+    depth: usize,
+}
+
+impl ValueSerializer {
+    // The entry for a field or map value, its text read back the way a document is read
+    fn entry<T>(value: &T, depth: usize) -> Result<Option<Value>>
+    where
+        T: ?Sized + Serialize,
+    {
+        match value.serialize(ValueSerializer { depth })? {
+            Entry::Value(value) => Ok(Some(value)),
+            Entry::None => Ok(None),
+            Entry::Text => {
+                let Some(text) = value_to_string(value, &SerializeOptions::default())? else {
+                    return Ok(None);
+                };
+                let text = ValueDeserializer::read(&text, &DeserializeOptions::default())?;
+                Ok(Some(Value::String(text.into_owned())))
+            }
+        }
+    }
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Entry;
+    type Error = Error;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = SectionSerializer;
+    type SerializeStruct = SectionSerializer;
+    type SerializeStructVariant = SeqSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Entry> {
+        Ok(Entry::Value(Value::Bool(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Entry> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Entry> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Entry> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Entry> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Entry> {
+        Ok(Entry::Value(Value::Integer(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Entry> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Entry> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Entry> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Entry> {
+        self.serialize_i128(v.into())
+    }
+
+    // Integers beyond `i128` are kept as written
+    fn serialize_u128(self, v: u128) -> Result<Entry> {
+        match i128::try_from(v) {
+            Ok(v) => self.serialize_i128(v),
+            Err(_) => self.serialize_str(&v.to_string()),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Entry> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Entry> {
+        Ok(Entry::Value(Value::Float(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Entry> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Entry> {
+        Ok(Entry::Value(Value::String(v.to_string())))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Entry> {
+        Ok(Entry::Text)
+    }
+
+    fn serialize_none(self) -> Result<Entry> {
+        Ok(Entry::None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Entry>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Entry> {
+        Ok(Entry::None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Entry> {
+        Ok(Entry::None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Entry> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Entry>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Entry>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(Entry::Text)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<SeqSerializer> {
+        Ok(SeqSerializer {
+            merged: None,
+            text: false,
+            depth: self.depth,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<SeqSerializer> {
+        Ok(SeqSerializer::text(self.depth))
+    }
+
+    // Sections are built by recursion, like those of a document
+    fn serialize_map(self, _len: Option<usize>) -> Result<SectionSerializer> {
+        let max_depth = SerializeOptions::default().max_depth;
+        if self.depth > max_depth {
+            return Err(too_deep(max_depth));
+        }
+        Ok(SectionSerializer {
+            entries: Vec::new(),
+            key: None,
+            depth: self.depth,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SectionSerializer> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<SeqSerializer> {
+        Ok(SeqSerializer::text(self.depth))
+    }
+}
+
+// Keys and subsections of a struct or map
+struct SectionSerializer {
+    entries: Vec<(String, Value)>,
+    key: Option<String>,
+    depth: usize,
+}
+
+impl SectionSerializer {
+    fn push<T>(&mut self, key: String, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let value =
+            ValueSerializer::entry(value, self.depth + 1).map_err(|e| e.at_field("", &key))?;
+        if let Some(value) = value {
+            self.entries.push((key, value));
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for SectionSerializer {
+    type Ok = Entry;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.key = Some(key_to_string(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = self.key.take().unwrap_or_default();
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Entry> {
+        Ok(Entry::Value(Value::Section(self.entries)))
+    }
+}
+
+impl ser::SerializeStruct for SectionSerializer {
+    type Ok = Entry;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Entry> {
+        Ok(Entry::Value(Value::Section(self.entries)))
+    }
+}
+
+// Elements of a sequence, which is written as one line unless all of its elements are sections
+struct SeqSerializer {
+    // Keys of the repeated sections so far, which are read back as one section
+    merged: Option<Vec<(String, Value)>>,
+    text: bool,
+    depth: usize,
+}
+
+impl SeqSerializer {
+    fn text(depth: usize) -> Self {
+        SeqSerializer {
+            merged: None,
+            text: true,
+            depth,
+        }
+    }
+
+    fn push<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        if self.text {
+            return Ok(());
+        }
+        match value.serialize(ValueSerializer { depth: self.depth })? {
+            Entry::Value(Value::Section(entries)) => {
+                let merged = self.merged.get_or_insert_with(Vec::new);
+                for (key, value) in entries {
+                    match merged.iter_mut().find(|(k, _)| *k == key) {
+                        Some((_, existing)) => *existing = value,
+                        None => merged.push((key, value)),
+                    }
+                }
+            }
+            _ => self.text = true,
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Entry {
+        match self.merged {
+            Some(entries) if !self.text => Entry::Value(Value::Section(entries)),
+            _ => Entry::Text,
+        }
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Entry;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Entry> {
+        Ok(SeqSerializer::end(self))
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Entry;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Entry> {
+        Ok(SeqSerializer::end(self))
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Entry;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Entry> {
+        Ok(SeqSerializer::end(self))
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = Entry;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, _value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(())
+    }
+
+    fn end(self) -> Result<Entry> {
+        Ok(Entry::Text)
+    }
+}
+
+impl ser::SerializeStructVariant for SeqSerializer {
+    type Ok = Entry;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, _key: &'static str, _value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(())
+    }
+
+    fn end(self) -> Result<Entry> {
+        Ok(Entry::Text)
+    }
+}
+
+impl Value {
+    // The value as written after a key, for fields reading it by its INI spelling
+    fn text(&self) -> Result<String> {
+        Ok(value_to_string(self, &SerializeOptions::default())?.unwrap_or_default())
+    }
+}
+
+// Reads typed values from their INI spelling, like the fields of a document
+macro_rules! forward_to_text {
+    ($($method:ident($($arg:ident: $ty:ty),*))*) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value>
+            where
+                V: de::Visitor<'de>,
+            {
+                if let Value::Section(_) = self {
+                    return self.deserialize_any(visitor);
+                }
+                let text = self.text()?;
+                let options = DeserializeOptions::default();
+                ValueDeserializer::new(&text, &options)?.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            Value::String(v) => visitor.visit_string(v),
+            Value::Bool(v) => visitor.visit_bool(v),
+            Value::Integer(v) => match (i64::try_from(v), u64::try_from(v)) {
+                (Ok(v), _) => visitor.visit_i64(v),
+                (_, Ok(v)) => visitor.visit_u64(v),
+                _ => visitor.visit_i128(v),
+            },
+            Value::Float(v) => visitor.visit_f64(v),
+            Value::Section(entries) => {
+                let mut map = MapDeserializer::new(entries.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    // Strings are kept as they are, rather than read from their spelling
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            Value::String(v) => visitor.visit_string(v),
+            Value::Section(_) => self.deserialize_any(visitor),
+            _ => visitor.visit_string(self.text()?),
+        }
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        // Values left out are `None`, any that is present is `Some`
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // A section names its variant with the tag key, like a section of a document
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let Value::Section(mut entries) = self else {
+            let text = self.text()?;
+            let options = DeserializeOptions::default();
+            return ValueDeserializer::new(&text, &options)?
+                .deserialize_enum(name, variants, visitor);
+        };
+        let tag = DeserializeOptions::default().enum_tag;
+        let Some(index) = entries.iter().position(|(key, _)| *key == tag) else {
+            return Err(Error::MissingField {
+                field: tag,
+                section: String::new(),
+            });
+        };
+        let (_, variant) = entries.remove(index);
+        let variant = match variant {
+            Value::String(variant) => variant,
+            other => other.text()?,
+        };
+        visitor.visit_enum(SectionEnum { variant, entries })
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    forward_to_text! {
+        deserialize_bool() deserialize_i8() deserialize_i16() deserialize_i32()
+        deserialize_i64() deserialize_i128() deserialize_u8() deserialize_u16()
+        deserialize_u32() deserialize_u64() deserialize_u128() deserialize_f32()
+        deserialize_f64() deserialize_char() deserialize_bytes() deserialize_byte_buf()
+        deserialize_unit() deserialize_unit_struct(name: &'static str)
+        deserialize_seq() deserialize_tuple(len: usize)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+// An enum read from a section, the keys other than the tag holding the variant's fields
+struct SectionEnum {
+    variant: String,
+    entries: Vec<(String, Value)>,
+}
+
+impl<'de> de::EnumAccess<'de> for SectionEnum {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(mut self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(Value::String(std::mem::take(&mut self.variant)))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for SectionEnum {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(Value::Section(self.entries))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::unsupported("tuple variants"))
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        de::Deserializer::deserialize_any(Value::Section(self.entries), visitor)
+    }
+}